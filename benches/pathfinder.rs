@@ -0,0 +1,187 @@
+//! Run with `cargo bench --features testutil`.
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use flying_pathfinding::grid::Grid;
+use flying_pathfinding::pathfinding::{
+    find_path, find_path_with_options, NeighborOrder, Pathfinder, SearchOptions,
+};
+use flying_pathfinding::provider::{GeometryProvider, GridProvider, Shape, SingleGridProvider};
+use flying_pathfinding::testutil::{generate_maze, generate_random};
+use nalgebra::Vector3;
+
+/// Samples of the searches slow enough to make the default 100 take too long.
+const LARGE_SAMPLES: usize = 10;
+
+struct OpenGridProvider {}
+
+impl GridProvider for OpenGridProvider {
+    fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+        vector.z != 0.0
+    }
+}
+
+/// Long wall in front of the goal, forces the search to explore a wide area.
+struct WalledGridProvider {}
+
+impl GridProvider for WalledGridProvider {
+    fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+        vector.z != 0.0 || (vector.x == 5.0 && vector.y.abs() < 200.0)
+    }
+}
+
+fn open(c: &mut Criterion) {
+    let start = Vector3::new(0.0, 0.0, 0.0);
+    let goal = Vector3::new(12.0, 7.0, 0.0);
+
+    c.bench_function("find_path", |b| {
+        b.iter(|| find_path(start, goal, OpenGridProvider {}).unwrap())
+    });
+
+    let mut pathfinder = Pathfinder::new();
+
+    c.bench_function("Pathfinder::solve", |b| {
+        b.iter(|| pathfinder.solve(start, goal, OpenGridProvider {}).unwrap())
+    });
+}
+
+fn large(c: &mut Criterion) {
+    let mut group = c.benchmark_group("large");
+    group.sample_size(LARGE_SAMPLES);
+
+    let start = Vector3::new(0.0, 0.0, 0.0);
+    let goal = Vector3::new(10.0, 0.0, 0.0);
+
+    // Nodes the search holds at most, used as the capacity hint.
+    let mut pathfinder = Pathfinder::new();
+    pathfinder
+        .solve(start, goal, WalledGridProvider {})
+        .unwrap();
+    let capacity_hint = pathfinder.stats().max_reachable + pathfinder.stats().expanded;
+
+    group.bench_function("Pathfinder::new", |b| {
+        b.iter(|| {
+            Pathfinder::new()
+                .solve(start, goal, WalledGridProvider {})
+                .unwrap()
+        })
+    });
+
+    group.bench_function("Pathfinder::with_capacity", |b| {
+        b.iter(|| {
+            Pathfinder::with_capacity(capacity_hint)
+                .solve(start, goal, WalledGridProvider {})
+                .unwrap()
+        })
+    });
+
+    // Large open grid backed by a flat bitvec, where the query order decides memory locality.
+    let grid_provider = SingleGridProvider::new(Grid::new(256, 16));
+    let goal = Vector3::new(60.0, 40.0, 6.0);
+
+    for &neighbor_order in &[
+        NeighborOrder::ZInnermost,
+        NeighborOrder::XInnermost,
+        NeighborOrder::Morton,
+    ] {
+        let options = SearchOptions {
+            neighbor_order,
+            ..SearchOptions::default()
+        };
+
+        let name = format!("find_path {:?} order", neighbor_order);
+
+        group.bench_function(name, |b| {
+            b.iter(|| find_path_with_options(start, goal, &grid_provider, &options).unwrap())
+        });
+    }
+
+    group.finish();
+}
+
+fn generated(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generated");
+    group.sample_size(LARGE_SAMPLES);
+
+    // Generated maps, seeded so every run searches the same grids.
+    let maze_provider = SingleGridProvider::new(generate_maze(63, 1, 1));
+    let maze_start = Vector3::new(1.0, 1.0, 0.0);
+    let maze_goal = Vector3::new(61.0, 61.0, 0.0);
+
+    group.bench_function("find_path maze", |b| {
+        b.iter(|| find_path(maze_start, maze_goal, &maze_provider).unwrap())
+    });
+
+    let start = Vector3::new(0.0, 0.0, 0.0);
+    let mut random_grid = generate_random(64, 8, 0.2, 1);
+    let random_goal = Vector3::new(60.0, 50.0, 6.0);
+    random_grid.set_cell(0, 0, 0, false);
+    random_grid.set_cell(60, 50, 6, false);
+
+    let random_provider = SingleGridProvider::new(random_grid);
+
+    group.bench_function("find_path random", |b| {
+        b.iter(|| find_path(start, random_goal, &random_provider).unwrap())
+    });
+
+    group.finish();
+}
+
+fn nearby(c: &mut Criterion) {
+    let mut group = c.benchmark_group("nearby");
+    group.sample_size(LARGE_SAMPLES);
+
+    // Back-to-back queries to nearby goals against a provider costly to ask,
+    // a field of spheres tested one by one.
+    let shapes = (0..64)
+        .map(|i| Shape::Sphere {
+            center: Vector3::new(
+                4.0 + (i % 8) as f32 * 3.0,
+                -12.0 + (i / 8) as f32 * 3.0,
+                0.0,
+            ),
+            radius: 0.5,
+        })
+        .collect();
+
+    let geometry_provider = GeometryProvider::new(shapes);
+    let bounds = (
+        Vector3::new(-4.0, -16.0, -2.0),
+        Vector3::new(32.0, 16.0, 2.0),
+    );
+    let start = Vector3::new(0.0, 0.0, 0.0);
+    let nearby_goals: Vec<Vector3<f32>> = (0..8)
+        .map(|i| Vector3::new(26.0 + (i % 2) as f32, -3.0 + i as f32, 0.0))
+        .collect();
+
+    let options = SearchOptions {
+        bounds: Some(bounds),
+        ..SearchOptions::default()
+    };
+
+    let mut goals = nearby_goals.iter().cycle();
+
+    group.bench_function("find_path", |b| {
+        b.iter(|| {
+            let goal = *goals.next().unwrap();
+            find_path_with_options(start, goal, &geometry_provider, &options).unwrap()
+        })
+    });
+
+    let mut pathfinder =
+        Pathfinder::with_provider(&geometry_provider).with_options(SearchOptions {
+            cache_obstacles: true,
+            ..options
+        });
+
+    group.bench_function("BoundPathfinder::find", |b| {
+        b.iter(|| {
+            let goal = *goals.next().unwrap();
+            pathfinder.find(start, goal).unwrap()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, open, large, generated, nearby);
+criterion_main!(benches);
@@ -0,0 +1,177 @@
+use crate::pathfinding::{find_path, path_cost, snap_to_grid};
+use crate::provider::GridProvider;
+use nalgebra::Vector3;
+use std::collections::HashMap;
+
+type CellKey = (i32, i32, i32);
+
+/// Adds the penalty of the cells already used by found paths to the extra cost.
+struct PenaltyProvider<'a, G: GridProvider> {
+    base: &'a G,
+    uses: &'a HashMap<CellKey, u32>,
+    diversity: f32,
+}
+
+impl<'a, G: GridProvider> GridProvider for PenaltyProvider<'a, G> {
+    fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+        self.base.is_obstacle(vector)
+    }
+
+    fn is_obstacle_cell(&self, x: i32, y: i32, z: i32) -> bool {
+        self.base.is_obstacle_cell(x, y, z)
+    }
+
+    fn extra_cost(&self, vector: Vector3<f32>) -> f32 {
+        let uses = self.uses.get(&cell_key(&vector)).copied().unwrap_or(0);
+        self.base.extra_cost(vector) + self.diversity * uses as f32
+    }
+
+    fn bounds(&self) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        self.base.bounds()
+    }
+
+    fn prefetch(&self, min: Vector3<f32>, max: Vector3<f32>) {
+        self.base.prefetch(min, max)
+    }
+}
+
+/// Finds up to `k` distinct paths from one vector to another, sorted by cost.
+/// The first one is the `find_path` path. Every next search adds `diversity`
+/// to the extra cost of a cell for each found path through it, so larger values
+/// push later paths further away from the earlier ones. Costs are returned
+/// without the penalties, like `find_path_with_cost` reports them.
+///
+/// Fewer paths are returned when the penalties stop producing new ones,
+/// after at most `2 * k` searches.
+pub fn find_k_paths<G: GridProvider>(
+    start: Vector3<f32>,
+    goal: Vector3<f32>,
+    grid_provider: G,
+    k: usize,
+    diversity: f32,
+) -> Vec<(Vec<Vector3<f32>>, f32)> {
+    let snapped_start = snap_to_grid(start);
+    let snapped_goal = snap_to_grid(goal);
+
+    let mut uses: HashMap<CellKey, u32> = HashMap::new();
+    let mut paths: Vec<(Vec<Vector3<f32>>, f32)> = Vec::new();
+
+    for _ in 0..2 * k {
+        if paths.len() == k {
+            break;
+        }
+
+        let penalty_provider = PenaltyProvider {
+            base: &grid_provider,
+            uses: &uses,
+            diversity,
+        };
+
+        let path = match find_path(start, goal, &penalty_provider) {
+            Some(path) => path,
+            None => break,
+        };
+
+        // The goal is shared by every path, penalizing it changes nothing.
+        for vector in &path[..path.len() - 1] {
+            *uses.entry(cell_key(vector)).or_insert(0) += 1;
+        }
+
+        if paths.iter().any(|(found, _)| *found == path) {
+            continue;
+        }
+
+        // Reaching the start itself takes no move.
+        let cost = if snapped_start == snapped_goal {
+            0.0
+        } else {
+            match path_cost(&snapped_start, &snapped_goal, &path, &grid_provider) {
+                Some(cost) => cost,
+                None => break,
+            }
+        };
+
+        paths.push((path, cost));
+    }
+
+    paths.sort_by(|(_, cost1), (_, cost2)| cost1.total_cmp(cost2));
+    paths
+}
+
+fn cell_key(vector: &Vector3<f32>) -> CellKey {
+    (vector.x as i32, vector.y as i32, vector.z as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::alternatives::find_k_paths;
+    use crate::grid::Grid;
+    use crate::pathfinding::find_path_with_cost;
+    use crate::provider::{FnProvider, SingleGridProvider};
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_two_gaps() {
+        // Wall at x = 5 with gaps at y = 3 and y = -3.
+        let grid_provider = FnProvider::new(|vector| {
+            vector.z != 0.0 || (vector.x == 5.0 && vector.y.abs() <= 6.0 && vector.y.abs() != 3.0)
+        });
+
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(10.0, 0.0, 0.0);
+
+        let paths = find_k_paths(start, goal, &grid_provider, 2, 1.0);
+        assert_eq!(paths.len(), 2);
+
+        let (first, first_cost) = &paths[0];
+        let (second, second_cost) = &paths[1];
+
+        let through = |path: &Vec<Vector3<f32>>, y: f32| path.contains(&Vector3::new(5.0, y, 0.0));
+
+        assert_ne!(first, second);
+        assert!(through(first, 3.0) != through(second, 3.0));
+        assert!(through(first, -3.0) != through(second, -3.0));
+
+        let (_, optimal_cost) = find_path_with_cost(start, goal, &grid_provider).unwrap();
+
+        assert!((first_cost - optimal_cost).abs() < 1e-4);
+        assert!((second_cost - optimal_cost).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_sorted_by_cost() {
+        let grid_provider = FnProvider::new(|vector| vector.z != 0.0);
+
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(6.0, 0.0, 0.0);
+
+        let paths = find_k_paths(start, goal, &grid_provider, 3, 2.0);
+
+        assert_eq!(paths.len(), 3);
+        assert!(paths.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+        assert!((paths[0].1 - 6.0).abs() < 1e-4);
+        assert!(paths.iter().all(|(path, _)| path.last() == Some(&goal)));
+    }
+
+    #[test]
+    fn test_unreachable_bounded() {
+        // The goal is sealed in a solid shell, the grid bounds end every search.
+        let mut grid = Grid::new(8, 8);
+
+        for z in 3..6 {
+            for y in 3..6 {
+                for x in 3..6 {
+                    grid.set_obstacle(x, y, z);
+                }
+            }
+        }
+
+        grid.set_cell(4, 4, 4, false);
+
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(4.0, 4.0, 4.0);
+        let paths = find_k_paths(start, goal, SingleGridProvider::new(grid), 2, 1.0);
+
+        assert!(paths.is_empty());
+    }
+}
@@ -0,0 +1,196 @@
+use crate::grid::{Grid, GridError};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Magic bytes which start the archive file.
+const MAGIC: [u8; 4] = *b"FPGA";
+/// Current version of the archive file format.
+///
+/// Layout: magic, version, tile count, index entries of grid x, grid y,
+/// offset from the start of the file and length in bytes, then the tiles
+/// in the grid file format.
+const VERSION: u32 = 1;
+/// Length of the header before the index entries.
+const HEADER_LENGTH: u64 = 12;
+/// Length of an index entry.
+const ENTRY_LENGTH: u64 = 24;
+
+/// Single file holding many grid tiles by their grid id, an alternative
+/// to the one file per tile layout of `FolderGridProvider`.
+/// Only the index is read on open, tiles are read on demand.
+pub struct GridArchive {
+    /// Shared cursor of the tile reads.
+    file: RefCell<File>,
+    /// (offset, length) of every tile.
+    index: BTreeMap<(u32, u32), (u64, u64)>,
+}
+
+impl GridArchive {
+    /// Writes the tiles into the archive file. Fails without writing anything
+    /// when a grid id is repeated.
+    pub fn create<'a, P, I>(path: P, tiles: I) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+        I: IntoIterator<Item = ((u32, u32), &'a Grid)>,
+    {
+        let mut encoded: BTreeMap<(u32, u32), Vec<u8>> = BTreeMap::new();
+
+        for (grid_id, grid) in tiles {
+            let mut bytes = Vec::new();
+            grid.write_to(&mut bytes)?;
+
+            if encoded.insert(grid_id, bytes).is_some() {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Grid {:?} is in the archive twice", grid_id),
+                ));
+            }
+        }
+
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+
+        let mut writer = BufWriter::new(file);
+
+        writer.write_all(&MAGIC)?;
+        writer.write_u32::<BigEndian>(VERSION)?;
+        writer.write_u32::<BigEndian>(encoded.len() as u32)?;
+
+        let mut offset = HEADER_LENGTH + ENTRY_LENGTH * encoded.len() as u64;
+
+        for ((grid_x, grid_y), bytes) in &encoded {
+            writer.write_u32::<BigEndian>(*grid_x)?;
+            writer.write_u32::<BigEndian>(*grid_y)?;
+            writer.write_u64::<BigEndian>(offset)?;
+            writer.write_u64::<BigEndian>(bytes.len() as u64)?;
+
+            offset += bytes.len() as u64;
+        }
+
+        for bytes in encoded.values() {
+            writer.write_all(bytes)?;
+        }
+
+        writer.flush()
+    }
+
+    /// Opens the archive file and reads its index.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<GridArchive, GridError> {
+        let file = File::open(path)?;
+        let mut reader = BufReader::new(&file);
+
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+
+        if magic != MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "Not a grid archive file").into());
+        }
+
+        let version = reader.read_u32::<BigEndian>()?;
+
+        if version != VERSION {
+            return Err(GridError::UnsupportedVersion(version));
+        }
+
+        let count = reader.read_u32::<BigEndian>()?;
+        let mut index = BTreeMap::new();
+
+        for _ in 0..count {
+            let grid_x = reader.read_u32::<BigEndian>()?;
+            let grid_y = reader.read_u32::<BigEndian>()?;
+            let offset = reader.read_u64::<BigEndian>()?;
+            let length = reader.read_u64::<BigEndian>()?;
+
+            index.insert((grid_x, grid_y), (offset, length));
+        }
+
+        drop(reader);
+
+        Ok(GridArchive {
+            file: RefCell::new(file),
+            index,
+        })
+    }
+
+    /// Grid ids of the tiles in the archive, in ascending order.
+    pub fn grid_ids(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        self.index.keys().copied()
+    }
+
+    pub fn contains(&self, grid_x: u32, grid_y: u32) -> bool {
+        self.index.contains_key(&(grid_x, grid_y))
+    }
+
+    /// Reads the tile from the file, `None` if the archive doesn't have it.
+    pub fn read(&self, grid_x: u32, grid_y: u32) -> Result<Option<Grid>, GridError> {
+        let (offset, length) = match self.index.get(&(grid_x, grid_y)) {
+            Some(entry) => *entry,
+            None => return Ok(None),
+        };
+
+        let mut file = self.file.borrow_mut();
+        file.seek(SeekFrom::Start(offset))?;
+
+        let mut reader = BufReader::new((&mut *file).take(length));
+
+        Grid::read_from(&mut reader, false).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::archive::GridArchive;
+    use crate::grid::Grid;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_archive() {
+        let temp_file = NamedTempFile::new().unwrap();
+
+        let mut first = Grid::new(3, 3);
+        first.set_obstacle(1, 1, 1);
+
+        let mut second = Grid::new(5, 2);
+        second.set_obstacle(4, 0, 1);
+        second.set_obstacle(0, 4, 0);
+
+        let third = Grid::new(2, 7);
+
+        GridArchive::create(
+            temp_file.path(),
+            vec![((35, 29), &first), ((0, 0), &second), ((35, 30), &third)],
+        )
+        .unwrap();
+
+        let archive = GridArchive::open(temp_file.path()).unwrap();
+
+        assert_eq!(
+            archive.grid_ids().collect::<Vec<_>>(),
+            vec![(0, 0), (35, 29), (35, 30)]
+        );
+
+        // Read out of order, each tile on its own.
+        assert_eq!(archive.read(35, 30).unwrap(), Some(third));
+        assert_eq!(archive.read(35, 29).unwrap(), Some(first));
+        assert_eq!(archive.read(0, 0).unwrap(), Some(second));
+        assert!(!archive.contains(1, 1));
+        assert_eq!(archive.read(1, 1).unwrap(), None);
+    }
+
+    #[test]
+    fn test_archive_duplicate_grid_id() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let grid = Grid::new(3, 3);
+
+        let result = GridArchive::create(temp_file.path(), vec![((1, 1), &grid), ((1, 1), &grid)]);
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,301 @@
+use crate::pathfinding::snap_to_grid;
+use crate::provider::GridProvider;
+use nalgebra::Vector3;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, BinaryHeap, HashMap, HashSet};
+
+type CellKey = (i32, i32, i32);
+
+/// Cells occupied by already planned agents at every time step.
+/// One time step is one move of the search, diagonal or not.
+#[derive(Debug, Default)]
+pub struct ReservationTable {
+    /// Time steps each cell is occupied at.
+    cells: HashMap<CellKey, BTreeSet<u32>>,
+    /// Moves (from, to, arrival time), used to forbid agents swapping cells.
+    moves: HashSet<(CellKey, CellKey, u32)>,
+    /// Cells agents stay in after arriving at their goal, with the arrival time.
+    parked: HashMap<CellKey, u32>,
+    /// Last time step with a reservation, the table is static after it.
+    latest: u32,
+}
+
+impl ReservationTable {
+    pub fn new() -> Self {
+        ReservationTable::default()
+    }
+
+    /// Reserves the path starting at time zero, the agent stays at the last cell afterwards.
+    /// The path is in the `find_path_cooperative` format, without the start.
+    pub fn reserve_path(&mut self, start: Vector3<f32>, path: &[Vector3<f32>]) {
+        let mut previous = cell_key(&snap_to_grid(start));
+        self.reserve_cell(previous, 0);
+
+        for (index, vector) in path.iter().enumerate() {
+            let time = index as u32 + 1;
+            let cell = cell_key(&snap_to_grid(*vector));
+
+            self.reserve_cell(cell, time);
+            self.moves.insert((previous, cell, time));
+            previous = cell;
+        }
+
+        self.parked.insert(previous, path.len() as u32);
+        self.latest = self.latest.max(path.len() as u32);
+    }
+
+    /// Whether the cell is occupied at the time step.
+    pub fn is_reserved(&self, vector: Vector3<f32>, time: u32) -> bool {
+        self.is_cell_reserved(cell_key(&snap_to_grid(vector)), time)
+    }
+
+    fn reserve_cell(&mut self, cell: CellKey, time: u32) {
+        self.cells.entry(cell).or_default().insert(time);
+        self.latest = self.latest.max(time);
+    }
+
+    fn is_cell_reserved(&self, cell: CellKey, time: u32) -> bool {
+        let occupied = self
+            .cells
+            .get(&cell)
+            .is_some_and(|times| times.contains(&time));
+
+        occupied || self.parked.get(&cell).is_some_and(|since| *since <= time)
+    }
+
+    /// Whether the move swaps cells with an agent moving the other way.
+    fn is_swap(&self, from: CellKey, to: CellKey, time: u32) -> bool {
+        self.moves.contains(&(to, from, time))
+    }
+
+    /// Whether the cell is occupied at any time step after the time.
+    fn is_reserved_after(&self, cell: CellKey, time: u32) -> bool {
+        self.cells
+            .get(&cell)
+            .is_some_and(|times| times.range(time + 1..).next().is_some())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    vector: Vector3<f32>,
+    time: u32,
+    cost: f32,
+    estimated_cost: f32,
+    previous_node_index: Option<usize>,
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let total_cost1 = self.cost + self.estimated_cost;
+        let total_cost2 = other.cost + other.estimated_cost;
+
+        // Reversed to turn the max binary heap into a min binary heap.
+        total_cost2.partial_cmp(&total_cost1).unwrap()
+    }
+}
+
+impl Eq for Node {}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+/// Finds the path from one vector to another avoiding agents already planned
+/// through the reservation table, then reserves the found path in it.
+/// Planning agents one after another through the same table yields
+/// a collision-free schedule.
+///
+/// The search moves over space and time: every step either moves to an adjacent
+/// cell or waits in place for one time step. The path has one vector per time
+/// step, waiting repeats the vector, and like `find_path` the start is not included.
+pub fn find_path_cooperative<G: GridProvider>(
+    start: Vector3<f32>,
+    goal: Vector3<f32>,
+    grid_provider: G,
+    reservations: &mut ReservationTable,
+) -> Option<Vec<Vector3<f32>>> {
+    let start = snap_to_grid(start);
+    let goal = snap_to_grid(goal);
+
+    if reservations.is_cell_reserved(cell_key(&start), 0) {
+        return None;
+    }
+
+    // After the latest reservation waiting changes nothing, so later time steps
+    // share one state. This keeps the search finite when the goal is unreachable.
+    let horizon = reservations.latest + 1;
+
+    let mut reachable = BinaryHeap::new();
+    let mut explored: HashSet<(CellKey, u32)> = HashSet::new();
+    let mut nodes: Vec<Node> = Vec::new();
+
+    reachable.push(Node {
+        vector: start,
+        time: 0,
+        cost: 0.0,
+        estimated_cost: (start - goal).magnitude(),
+        previous_node_index: None,
+    });
+
+    while let Some(current) = reachable.pop() {
+        let current_cell = cell_key(&current.vector);
+
+        if current.vector == goal && !reservations.is_reserved_after(current_cell, current.time) {
+            let mut path = Vec::new();
+            let mut node = &current;
+
+            // The start node at time zero isn't part of the path.
+            while let Some(index) = node.previous_node_index {
+                path.push(node.vector);
+                node = &nodes[index];
+            }
+
+            path.reverse();
+
+            reservations.reserve_path(start, &path);
+
+            return Some(path);
+        }
+
+        if !explored.insert((current_cell, current.time.min(horizon))) {
+            continue;
+        }
+
+        let time = current.time + 1;
+        let current_index = nodes.len();
+        nodes.push(current.clone());
+
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    let offset = Vector3::new(x as f32, y as f32, z as f32);
+                    let adjacent_vector = current.vector + offset;
+                    let adjacent_cell = cell_key(&adjacent_vector);
+
+                    if explored.contains(&(adjacent_cell, time.min(horizon))) {
+                        continue;
+                    }
+
+                    if reservations.is_cell_reserved(adjacent_cell, time)
+                        || reservations.is_swap(current_cell, adjacent_cell, time)
+                    {
+                        continue;
+                    }
+
+                    let cost = if offset == Vector3::zeros() {
+                        // Waiting costs as much as the shortest move.
+                        1.0
+                    } else {
+                        let (x, y, z) = adjacent_cell;
+
+                        if grid_provider.is_obstacle_cell(x, y, z) {
+                            continue;
+                        }
+
+                        offset.magnitude() + grid_provider.extra_cost(adjacent_vector)
+                    };
+
+                    reachable.push(Node {
+                        vector: adjacent_vector,
+                        time,
+                        cost: current.cost + cost,
+                        estimated_cost: (adjacent_vector - goal).magnitude(),
+                        previous_node_index: Some(current_index),
+                    });
+                }
+            }
+        }
+    }
+
+    None
+}
+
+fn cell_key(vector: &Vector3<f32>) -> CellKey {
+    (vector.x as i32, vector.y as i32, vector.z as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cooperative::{find_path_cooperative, ReservationTable};
+    use crate::pathfinding::find_path;
+    use crate::provider::FnProvider;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_single_agent() {
+        let grid_provider = FnProvider::new(|vector| vector.z != 0.0);
+        let mut reservations = ReservationTable::new();
+
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(5.0, 3.0, 0.0);
+
+        let path = find_path_cooperative(start, goal, &grid_provider, &mut reservations);
+
+        assert_eq!(
+            path.map(|path| path.len()),
+            find_path(start, goal, &grid_provider).map(|path| path.len())
+        );
+        assert!(reservations.is_reserved(start, 0));
+        assert!(reservations.is_reserved(goal, 100));
+    }
+
+    #[test]
+    fn test_agents_through_door() {
+        // Wall at x = 3 with a single door at (3, 0, 0).
+        let grid_provider =
+            FnProvider::new(|vector| vector.z != 0.0 || (vector.x == 3.0 && vector.y != 0.0));
+
+        let door = Vector3::new(3.0, 0.0, 0.0);
+        let mut reservations = ReservationTable::new();
+
+        let first_start = Vector3::new(0.0, 0.0, 0.0);
+        let first = find_path_cooperative(
+            first_start,
+            Vector3::new(6.0, 0.0, 0.0),
+            &grid_provider,
+            &mut reservations,
+        )
+        .unwrap();
+
+        let second_start = Vector3::new(0.0, 1.0, 0.0);
+        let second = find_path_cooperative(
+            second_start,
+            Vector3::new(6.0, 1.0, 0.0),
+            &grid_provider,
+            &mut reservations,
+        )
+        .unwrap();
+
+        // Alone both agents would pass the door at the same time.
+        let first_door = first.iter().position(|vector| *vector == door);
+        let second_door = second.iter().position(|vector| *vector == door);
+
+        assert_eq!(first.len(), 6);
+        assert_eq!(first_door, Some(2));
+        assert!(second_door.unwrap() > 2);
+        assert_eq!(second.last(), Some(&Vector3::new(6.0, 1.0, 0.0)));
+
+        let position = |start: Vector3<f32>, path: &[Vector3<f32>], time: usize| match time {
+            0 => start,
+            _ => *path.get(time - 1).unwrap_or_else(|| path.last().unwrap()),
+        };
+
+        for time in 0..=second.len() {
+            assert_ne!(
+                position(first_start, &first, time),
+                position(second_start, &second, time),
+                "Agents collide at time {}",
+                time
+            );
+        }
+    }
+}
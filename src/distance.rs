@@ -0,0 +1,144 @@
+use crate::grid::Grid;
+
+/// Neighbor offset with the distance to it.
+type MaskEntry = (i64, i64, i64, f32);
+
+/// Approximate euclidean distance from every cell to the nearest obstacle.
+#[derive(Debug, Clone)]
+pub struct DistanceField {
+    pub width: u32,
+    pub height: u32,
+    distances: Vec<f32>,
+}
+
+impl DistanceField {
+    /// Computes the distance field with a two-pass 3D chamfer transform.
+    /// Cells of a grid without obstacles are infinitely far.
+    pub fn new(grid: &Grid) -> Self {
+        let width = grid.width;
+        let height = grid.height;
+
+        let mut distances = vec![f32::INFINITY; width as usize * width as usize * height as usize];
+
+        for z in 0..height {
+            for y in 0..width {
+                for x in 0..width {
+                    if grid.is_obstacle(x, y, z) {
+                        distances[Grid::index(x, y, z, width, height)] = 0.0;
+                    }
+                }
+            }
+        }
+
+        let mut field = DistanceField {
+            width,
+            height,
+            distances,
+        };
+
+        let (forward, backward) = chamfer_masks();
+
+        for z in 0..height {
+            for y in 0..width {
+                for x in 0..width {
+                    field.relax(x, y, z, &forward);
+                }
+            }
+        }
+
+        for z in (0..height).rev() {
+            for y in (0..width).rev() {
+                for x in (0..width).rev() {
+                    field.relax(x, y, z, &backward);
+                }
+            }
+        }
+
+        field
+    }
+
+    /// Returns the distance from the cell to the nearest obstacle.
+    pub fn distance_at(&self, x: u32, y: u32, z: u32) -> f32 {
+        self.distances[Grid::index(x, y, z, self.width, self.height)]
+    }
+
+    fn relax(&mut self, x: u32, y: u32, z: u32, mask: &[MaskEntry]) {
+        let index = Grid::index(x, y, z, self.width, self.height);
+        let mut distance = self.distances[index];
+
+        for &(dx, dy, dz, weight) in mask {
+            let nx = x as i64 + dx;
+            let ny = y as i64 + dy;
+            let nz = z as i64 + dz;
+
+            if nx < 0 || ny < 0 || nz < 0 {
+                continue;
+            }
+
+            if nx >= self.width as i64 || ny >= self.width as i64 || nz >= self.height as i64 {
+                continue;
+            }
+
+            let neighbor_index =
+                Grid::index(nx as u32, ny as u32, nz as u32, self.width, self.height);
+            distance = distance.min(self.distances[neighbor_index] + weight);
+        }
+
+        self.distances[index] = distance;
+    }
+}
+
+/// Splits 26 neighbors into cells preceding and following in scan order.
+fn chamfer_masks() -> (Vec<MaskEntry>, Vec<MaskEntry>) {
+    let mut forward = Vec::new();
+    let mut backward = Vec::new();
+
+    for dz in -1i64..=1 {
+        for dy in -1i64..=1 {
+            for dx in -1i64..=1 {
+                if dx == 0 && dy == 0 && dz == 0 {
+                    continue;
+                }
+
+                let weight = ((dx * dx + dy * dy + dz * dz) as f32).sqrt();
+
+                if (dz, dy, dx) < (0, 0, 0) {
+                    forward.push((dx, dy, dz, weight));
+                } else {
+                    backward.push((dx, dy, dz, weight));
+                }
+            }
+        }
+    }
+
+    (forward, backward)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid::Grid;
+
+    #[test]
+    fn test_distance_field() {
+        let mut grid = Grid::new(10, 10);
+        grid.set_obstacle(0, 0, 0);
+
+        let distance_field = grid.distance_field();
+
+        assert_eq!(distance_field.distance_at(0, 0, 0), 0.0);
+        assert_eq!(distance_field.distance_at(1, 0, 0), 1.0);
+        assert_eq!(distance_field.distance_at(0, 0, 1), 1.0);
+        assert_eq!(distance_field.distance_at(1, 1, 0), 2.0f32.sqrt());
+        assert_eq!(distance_field.distance_at(1, 1, 1), 3.0f32.sqrt());
+        assert!((distance_field.distance_at(9, 0, 0) - 9.0).abs() < 1e-4);
+        assert!(distance_field.distance_at(9, 9, 9) > 15.0);
+    }
+
+    #[test]
+    fn test_empty_distance_field() {
+        let grid = Grid::new(3, 3);
+        let distance_field = grid.distance_field();
+
+        assert_eq!(distance_field.distance_at(1, 1, 1), f32::INFINITY);
+    }
+}
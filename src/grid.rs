@@ -1,10 +1,215 @@
+use crate::distance::DistanceField;
+use crate::path::supercover;
+use crate::region::LabeledGrid;
 use bitvec::prelude::*;
-use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use byteorder::{BigEndian, ByteOrder, ReadBytesExt, WriteBytesExt};
+use nalgebra::Vector3;
+use std::collections::{HashSet, VecDeque};
+use std::convert::TryFrom;
+use std::fmt::{self, Debug, Display, Formatter};
 use std::fs::{File, OpenOptions};
-use std::io::{Error, Read, Write};
+use std::hash::{Hash, Hasher};
+use std::io::{BufRead, BufReader, BufWriter, Error, ErrorKind, Read, Write};
 use std::path::Path;
 
+/// Magic bytes which start the versioned grid file.
+const MAGIC: [u8; 4] = *b"FPGR";
+/// Current version of the grid file format.
+///
+/// Layout: magic, version, width, height, payload, CRC32 of the payload.
+/// Files without the magic bytes are legacy: width, height, payload.
+const VERSION: u32 = 1;
+/// Offset of the payload in versioned grid files. The header is kept a multiple
+/// of 16 bytes, new header fields must be padded up to it, so the payload
+/// stays aligned when the file is memory mapped.
+pub const PAYLOAD_OFFSET: usize = 16;
+/// Magic bytes which start the edit log appended after the grid, see `AppendableGrid`.
+/// Other data after the grid is left alone.
+pub(crate) const EDIT_LOG_MAGIC: [u8; 4] = *b"FPED";
+/// Length of an edit record in the edit log.
+/// Layout: x, y, z, value byte.
+const EDIT_RECORD_LENGTH: usize = 13;
+
+/// What follows the grid in a grid file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Trailer {
+    Empty,
+    EditLog,
+    /// Data other than an edit log, it isn't read.
+    Unknown,
+}
+
 #[derive(Debug)]
+pub enum GridError {
+    Io(Error),
+    /// Grid file was written by a newer or unknown format version.
+    UnsupportedVersion(u32),
+    /// Payload doesn't match the checksum stored in the file.
+    ChecksumMismatch {
+        expected: u32,
+        actual: u32,
+    },
+    /// Backing buffer holds a different number of bytes than the cells need.
+    InvalidLength {
+        expected: usize,
+        actual: usize,
+    },
+    /// Bits past the last cell are set.
+    NonZeroPadding,
+    /// Line of a CSV file isn't an in-bounds `x,y,z` cell, lines count from one.
+    InvalidCsv {
+        line: usize,
+        message: String,
+    },
+}
+
+impl Display for GridError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            GridError::Io(error) => write!(f, "{}", error),
+            GridError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported grid file version {}", version)
+            }
+            GridError::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "Grid checksum mismatch, expected {:08x} but was {:08x}",
+                expected, actual
+            ),
+            GridError::InvalidLength { expected, actual } => write!(
+                f,
+                "Grid data is {} bytes long, expected {}",
+                actual, expected
+            ),
+            GridError::NonZeroPadding => write!(f, "Grid padding bits are set"),
+            GridError::InvalidCsv { line, message } => {
+                write!(f, "Invalid CSV on line {}: {}", line, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for GridError {}
+
+impl From<Error> for GridError {
+    fn from(error: Error) -> Self {
+        GridError::Io(error)
+    }
+}
+
+/// Which neighbors of a cell are considered adjacent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connectivity {
+    /// Neighbors sharing a face.
+    Six,
+    /// Neighbors sharing a face, an edge or a corner.
+    TwentySix,
+}
+
+impl Connectivity {
+    fn offsets(self) -> Vec<(i64, i64, i64)> {
+        let mut offsets = Vec::new();
+
+        for z in -1i64..=1 {
+            for y in -1i64..=1 {
+                for x in -1i64..=1 {
+                    let distance = x.abs() + y.abs() + z.abs();
+
+                    if distance == 0 || (self == Connectivity::Six && distance > 1) {
+                        continue;
+                    }
+
+                    offsets.push((x, y, z));
+                }
+            }
+        }
+
+        offsets
+    }
+}
+
+/// Inclusive min and max corners of a box of cells.
+pub type Bounds = ((u32, u32, u32), (u32, u32, u32));
+
+/// Grid-space cell coordinates, as opposed to world-space vectors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Cell {
+    pub x: u32,
+    pub y: u32,
+    pub z: u32,
+}
+
+impl Cell {
+    pub fn new(x: u32, y: u32, z: u32) -> Self {
+        Cell { x, y, z }
+    }
+
+    /// Rounds the vector to the nearest cell,
+    /// returns `None` for negative, overflowing or non-finite coordinates.
+    pub fn from_vector(vector: Vector3<f32>) -> Option<Cell> {
+        let axis = |axis: f32| {
+            let axis = axis.round();
+
+            if axis >= 0.0 && axis <= u32::MAX as f32 {
+                Some(axis as u32)
+            } else {
+                None
+            }
+        };
+
+        Some(Cell::new(axis(vector.x)?, axis(vector.y)?, axis(vector.z)?))
+    }
+}
+
+impl From<(u32, u32, u32)> for Cell {
+    fn from((x, y, z): (u32, u32, u32)) -> Self {
+        Cell::new(x, y, z)
+    }
+}
+
+impl From<Cell> for (u32, u32, u32) {
+    fn from(cell: Cell) -> Self {
+        (cell.x, cell.y, cell.z)
+    }
+}
+
+impl From<Cell> for Vector3<f32> {
+    fn from(cell: Cell) -> Self {
+        Vector3::new(cell.x as f32, cell.y as f32, cell.z as f32)
+    }
+}
+
+/// How `Grid::blit` combines source cells with destination cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlitOp {
+    /// Adds source obstacles.
+    Or,
+    /// Carves source obstacles out of the destination.
+    AndNot,
+    /// Toggles destination cells under source obstacles.
+    Xor,
+}
+
+/// Coordinate axis, e.g. the one a cylinder extends along.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+impl Axis {
+    fn index(self) -> usize {
+        match self {
+            Axis::X => 0,
+            Axis::Y => 1,
+            Axis::Z => 2,
+        }
+    }
+}
+
+/// Obstacle bits of a box of `width` × `width` × `height` cells. Only the x and y
+/// axes share the width, the height is independent, so tall shafts like
+/// 2 × 2 × 1000 or flat maps like 1000 × 1000 × 2 take no more bits than cells.
 pub struct Grid {
     pub width: u32,
     pub height: u32,
@@ -13,128 +218,1922 @@ pub struct Grid {
 
 impl Grid {
     pub fn new(width: u32, height: u32) -> Self {
-        let length = (width * width * height) as usize;
-        let length_with_padding = ((length as f32 / 8.0).ceil() * 8.0) as usize;
+        let length_with_padding = Self::cell_count(width, height).div_ceil(8) * 8;
+
+        let mut data = BitVec::with_capacity(length_with_padding);
+        data.resize(length_with_padding, false);
+
+        Grid {
+            width,
+            height,
+            data,
+        }
+    }
+
+    pub fn import<P: AsRef<Path>>(path: P) -> Result<Grid, GridError> {
+        Self::import_with(path, false)
+    }
+
+    /// Imports the grid and checks it with `validate`, for files from untrusted sources.
+    pub fn import_validated<P: AsRef<Path>>(path: P) -> Result<Grid, GridError> {
+        let grid = Self::import(path)?;
+        grid.validate()?;
+
+        Ok(grid)
+    }
+
+    /// Imports the grid, tolerating legacy files whose payload is
+    /// one byte short of the padded length. The missing byte is zero.
+    pub fn import_lenient<P: AsRef<Path>>(path: P) -> Result<Grid, GridError> {
+        Self::import_with(path, true)
+    }
+
+    /// Reads obstacles from `x,y,z` lines into a new grid, blank lines are skipped.
+    pub fn import_csv<R: Read>(reader: R, width: u32, height: u32) -> Result<Grid, GridError> {
+        let mut grid = Grid::new(width, height);
+
+        for (index, line) in BufReader::new(reader).lines().enumerate() {
+            let line = line?;
+            let invalid = |message: String| GridError::InvalidCsv {
+                line: index + 1,
+                message,
+            };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let cell = line
+                .split(',')
+                .map(|value| value.trim().parse::<u32>())
+                .collect::<Result<Vec<u32>, _>>()
+                .map_err(|error| invalid(error.to_string()))?;
+
+            let (x, y, z) = match cell[..] {
+                [x, y, z] => (x, y, z),
+                _ => return Err(invalid(format!("expected 3 values, found {}", cell.len()))),
+            };
+
+            if x >= width || y >= width || z >= height {
+                return Err(invalid(format!(
+                    "cell ({}, {}, {}) is out of bounds",
+                    x, y, z
+                )));
+            }
+
+            grid.set_obstacle(x, y, z);
+        }
+
+        Ok(grid)
+    }
+
+    /// Writes every obstacle as an `x,y,z` line, in the order of `obstacles`.
+    pub fn export_csv<W: Write>(&self, writer: W) -> Result<(), Error> {
+        let mut writer = BufWriter::new(writer);
+
+        for cell in self.obstacles() {
+            writeln!(writer, "{},{},{}", cell.x, cell.y, cell.z)?;
+        }
+
+        writer.flush()
+    }
+
+    fn import_with<P: AsRef<Path>>(path: P, lenient: bool) -> Result<Grid, GridError> {
+        let mut file = File::open(path)?;
+        let mut grid = Self::read_from(&mut file, lenient)?;
+        grid.replay_edits(&mut file, lenient)?;
+
+        Ok(grid)
+    }
+
+    /// Reads the grid in the file format from the reader.
+    pub(crate) fn read_from<R: Read>(file: &mut R, lenient: bool) -> Result<Grid, GridError> {
+        let mut magic = [0u8; 4];
+        file.read_exact(&mut magic)?;
+
+        // Legacy files have no header and start with the width right away.
+        if magic != MAGIC {
+            let width = BigEndian::read_u32(&magic);
+            let height = file.read_u32::<BigEndian>()?;
+            let vec = Self::read_payload(file, width, height, lenient)?;
+
+            return Ok(Self::from_payload(width, height, vec));
+        }
+
+        let version = file.read_u32::<BigEndian>()?;
+
+        if version != VERSION {
+            return Err(GridError::UnsupportedVersion(version));
+        }
+
+        let width = file.read_u32::<BigEndian>()?;
+        let height = file.read_u32::<BigEndian>()?;
+        let vec = Self::read_payload(file, width, height, false)?;
+
+        let expected = file.read_u32::<BigEndian>()?;
+        let actual = crc32(&vec);
+
+        if expected != actual {
+            return Err(GridError::ChecksumMismatch { expected, actual });
+        }
+
+        Ok(Self::from_payload(width, height, vec))
+    }
+
+    /// Applies the edit log which follows the grid in the reader, returns what
+    /// follows the grid. A torn record at the end of the log is an error, unless
+    /// lenient, then it's ignored as appending it was interrupted.
+    pub(crate) fn replay_edits<R: Read>(
+        &mut self,
+        reader: &mut R,
+        lenient: bool,
+    ) -> Result<Trailer, GridError> {
+        let mut trailer = Vec::new();
+        reader.read_to_end(&mut trailer)?;
+
+        if trailer.is_empty() {
+            return Ok(Trailer::Empty);
+        }
+
+        if !trailer.starts_with(&EDIT_LOG_MAGIC) {
+            return Ok(Trailer::Unknown);
+        }
+
+        let edits = trailer[EDIT_LOG_MAGIC.len()..].chunks_exact(EDIT_RECORD_LENGTH);
+
+        if !lenient && !edits.remainder().is_empty() {
+            return Err(Error::new(ErrorKind::InvalidData, "Grid edit is torn").into());
+        }
+
+        for record in edits {
+            let x = BigEndian::read_u32(&record[0..4]);
+            let y = BigEndian::read_u32(&record[4..8]);
+            let z = BigEndian::read_u32(&record[8..12]);
+
+            if x >= self.width || y >= self.width || z >= self.height {
+                return Err(
+                    Error::new(ErrorKind::InvalidData, "Grid edit is out of bounds").into(),
+                );
+            }
+
+            let index = Self::index(x, y, z, self.width, self.height);
+            self.data.set(index, record[12] != 0);
+        }
+
+        Ok(Trailer::EditLog)
+    }
+
+    /// Writes the edit record `replay_edits` reads.
+    pub(crate) fn write_edit<W: Write>(
+        writer: &mut W,
+        cell: Cell,
+        value: bool,
+    ) -> Result<(), Error> {
+        let mut record = [0u8; EDIT_RECORD_LENGTH];
+
+        BigEndian::write_u32(&mut record[0..4], cell.x);
+        BigEndian::write_u32(&mut record[4..8], cell.y);
+        BigEndian::write_u32(&mut record[8..12], cell.z);
+        record[12] = value as u8;
+
+        // One write keeps the record in one piece as far as the OS allows.
+        writer.write_all(&record)
+    }
+
+    fn read_payload<R: Read>(
+        reader: &mut R,
+        width: u32,
+        height: u32,
+        lenient: bool,
+    ) -> Result<Vec<u8>, GridError> {
+        let bytes = Self::cell_count(width, height).div_ceil(8);
+        let mut vec = vec![0u8; bytes];
+
+        if lenient {
+            let mut payload = Vec::with_capacity(vec.len());
+            reader.take(vec.len() as u64).read_to_end(&mut payload)?;
+
+            if payload.len() + 1 < vec.len() {
+                return Err(
+                    Error::new(ErrorKind::UnexpectedEof, "Grid payload is too short").into(),
+                );
+            }
+
+            vec[..payload.len()].copy_from_slice(&payload);
+        } else {
+            reader.read_exact(&mut vec)?;
+        }
+
+        Ok(vec)
+    }
+
+    /// Payload bytes of the whole cells, then the last partial byte with
+    /// the padding bits past the last cell masked out. Loaded files may have
+    /// padding bits set, see `validate`, they mustn't tell grids apart.
+    fn masked_payload(&self) -> (&[u8], u8) {
+        let length = Self::cell_count(self.width, self.height);
+        let bytes = self.data.as_slice();
+        let (whole, rest) = (length / 8, length % 8);
+
+        let tail = match bytes.get(whole) {
+            Some(byte) if rest > 0 => byte & ((1u8 << rest) - 1),
+            _ => 0,
+        };
+
+        (&bytes[..whole.min(bytes.len())], tail)
+    }
+
+    fn from_payload(width: u32, height: u32, vec: Vec<u8>) -> Grid {
+        let data = BitVec::from_vec(vec);
+
+        Grid {
+            width,
+            height,
+            data,
+        }
+    }
+
+    pub fn set_obstacle(&mut self, x: u32, y: u32, z: u32) {
+        let index = Self::index(x, y, z, self.width, self.height);
+
+        // Debug builds check the index against the data as well, in case it
+        // and the bounds checks of `index` ever disagree.
+        #[cfg(debug_assertions)]
+        self.data.set(index, true);
+
+        // It's safe because we already checked the bounds.
+        #[cfg(not(debug_assertions))]
+        unsafe {
+            self.data.set_unchecked(index, true);
+        }
+    }
+
+    pub fn is_obstacle(&self, x: u32, y: u32, z: u32) -> bool {
+        let index = Self::index(x, y, z, self.width, self.height);
+
+        #[cfg(debug_assertions)]
+        return *self
+            .data
+            .get(index)
+            .unwrap_or_else(|| panic!("Cell index {} is past the grid data", index));
+
+        // It's safe because we already checked the bounds.
+        #[cfg(not(debug_assertions))]
+        unsafe {
+            *self.data.get_unchecked(index)
+        }
+    }
+
+    pub fn set_obstacle_at<C: Into<Cell>>(&mut self, cell: C) {
+        let cell = cell.into();
+        self.set_obstacle(cell.x, cell.y, cell.z)
+    }
+
+    pub fn is_obstacle_at<C: Into<Cell>>(&self, cell: C) -> bool {
+        let cell = cell.into();
+        self.is_obstacle(cell.x, cell.y, cell.z)
+    }
+
+    /// Sets obstacles on all cells whose centers lie within the radius from the center.
+    /// Cells outside of the grid are skipped.
+    pub fn set_obstacle_sphere(&mut self, center: Vector3<f32>, radius: f32) {
+        let extent = Vector3::repeat(radius);
+
+        self.set_cells_where(center - extent, center + extent, true, |cell| {
+            (cell - center).norm_squared() <= radius * radius
+        });
+    }
+
+    /// Sets obstacles on all cells of the cylinder standing on the base center and
+    /// extending along the positive axis. Cells whose centers are within the radius
+    /// from the axis and whose offset along it is in `[0, height)` are set,
+    /// so the cylinder is `height` cells tall. Cells outside of the grid are skipped.
+    pub fn set_obstacle_cylinder(
+        &mut self,
+        base: Vector3<f32>,
+        radius: f32,
+        height: f32,
+        axis: Axis,
+    ) {
+        let axis = axis.index();
+
+        let mut min = base - Vector3::repeat(radius);
+        let mut max = base + Vector3::repeat(radius);
+        min[axis] = base[axis];
+        max[axis] = base[axis] + height;
+
+        self.set_cells_where(min, max, true, |cell| {
+            let mut offset = cell - base;
+            let along = offset[axis];
+            offset[axis] = 0.0;
+
+            along >= 0.0 && along < height && offset.norm_squared() <= radius * radius
+        });
+    }
+
+    /// Clears obstacles on all cells within the radius from the cells the path
+    /// passes through, including cells its segments only touch at an edge or
+    /// a corner, see `path::supercover`. Cells outside of the grid are skipped.
+    pub fn carve_path(&mut self, path: &[Vector3<f32>], radius: u32) {
+        let radius = radius as f32;
+        let extent = Vector3::repeat(radius);
+
+        let segments = path.windows(2).map(|segment| (segment[0], segment[1]));
+        let single = path
+            .first()
+            .filter(|_| path.len() == 1)
+            .map(|point| (*point, *point));
+
+        for (from, to) in segments.chain(single) {
+            for center in supercover(from, to) {
+                self.set_cells_where(center - extent, center + extent, false, |cell| {
+                    (cell - center).norm_squared() <= radius * radius
+                });
+            }
+        }
+    }
+
+    /// Sets the value of the cells within the inclusive box which match the predicate.
+    fn set_cells_where<F>(
+        &mut self,
+        min: Vector3<f32>,
+        max: Vector3<f32>,
+        value: bool,
+        predicate: F,
+    ) where
+        F: Fn(Vector3<f32>) -> bool,
+    {
+        let size = [self.width, self.width, self.height];
+
+        // Clamps to the grid, `None` if the box misses it along the axis.
+        let range = |axis: usize| {
+            let from = (min[axis].ceil() as i64).max(0);
+            let to = (max[axis].floor() as i64).min(size[axis] as i64 - 1);
+
+            if from <= to {
+                Some(from as u32..=to as u32)
+            } else {
+                None
+            }
+        };
+
+        let (xs, ys, zs) = match (range(0), range(1), range(2)) {
+            (Some(xs), Some(ys), Some(zs)) => (xs, ys, zs),
+            _ => return,
+        };
+
+        for z in zs {
+            for y in ys.clone() {
+                for x in xs.clone() {
+                    if predicate(Vector3::new(x as f32, y as f32, z as f32)) {
+                        let index = Self::index(x, y, z, self.width, self.height);
+                        self.data.set(index, value);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Sets the cell value, returns whether the cell is in bounds.
+    /// Negative and overflowing coordinates are treated as out of bounds, not wrapped,
+    /// and writes to them are ignored.
+    pub fn set_cell(&mut self, x: i32, y: i32, z: i32, value: bool) -> bool {
+        match self.cell_index(x, y, z) {
+            Some(index) => {
+                self.data.set(index, value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the cell value, or `None` if the cell is out of bounds.
+    /// Negative and overflowing coordinates are treated as out of bounds, not wrapped.
+    pub fn get_cell(&self, x: i32, y: i32, z: i32) -> Option<bool> {
+        self.cell_index(x, y, z)
+            .map(|index| *self.data.get(index).unwrap())
+    }
+
+    /// Creates the grid from a 3D boolean array of shape `[width, width, height]`
+    /// indexed as `[x][y][z]` and flattened in row-major order, so `z` is the
+    /// fastest changing axis. This is the layout `ndarray::Array3::as_slice`
+    /// returns for a standard layout array. Truthy cells become obstacles.
+    pub fn from_flat_array3(cells: &[bool], shape: [usize; 3]) -> Grid {
+        let [width, depth, height] = shape;
+
+        assert_eq!(width, depth, "Grid must have the same X and Y dimensions");
+        assert_eq!(
+            cells.len(),
+            width * depth * height,
+            "Array length doesn't match its shape"
+        );
+
+        let mut grid = Grid::new(width as u32, height as u32);
+
+        for (index, cell) in cells.iter().enumerate() {
+            if *cell {
+                let z = index % height;
+                let y = index / height % depth;
+                let x = index / height / depth;
+
+                grid.set_obstacle(x as u32, y as u32, z as u32);
+            }
+        }
+
+        grid
+    }
+
+    /// Creates the grid from terrain elevations, one per column indexed as
+    /// `x + width * y`, so `x` is the fastest changing axis. Every cell of the
+    /// column below its elevation becomes an obstacle, elevations above
+    /// the grid height fill the whole column.
+    pub fn from_heightmap(heights: &[u32], width: u32, height: u32) -> Grid {
+        assert_eq!(
+            heights.len(),
+            width as usize * width as usize,
+            "Heightmap must have width * width elevations"
+        );
+
+        let mut grid = Grid::new(width, height);
+
+        for (index, elevation) in heights.iter().enumerate() {
+            let x = index as u32 % width;
+            let y = index as u32 / width;
+
+            for z in 0..(*elevation).min(height) {
+                grid.set_obstacle(x, y, z);
+            }
+        }
+
+        grid
+    }
+
+    /// Returns the grid as a flattened 3D boolean array with its shape,
+    /// in the same layout `from_flat_array3` accepts.
+    pub fn to_flat_array3(&self) -> (Vec<bool>, [usize; 3]) {
+        let shape = [
+            self.width as usize,
+            self.width as usize,
+            self.height as usize,
+        ];
+
+        let mut cells = Vec::with_capacity(shape.iter().product());
+
+        for x in 0..self.width {
+            for y in 0..self.width {
+                for z in 0..self.height {
+                    cells.push(self.is_obstacle(x, y, z));
+                }
+            }
+        }
+
+        (cells, shape)
+    }
+
+    /// Creates the grid from a 3D boolean array indexed as `[x][y][z]`,
+    /// in any memory layout. Truthy cells become obstacles.
+    #[cfg(feature = "ndarray")]
+    pub fn from_array3(array: &ndarray::Array3<bool>) -> Grid {
+        let (width, depth, height) = array.dim();
+
+        assert_eq!(width, depth, "Grid must have the same X and Y dimensions");
+
+        let mut grid = Grid::new(width as u32, height as u32);
+
+        for ((x, y, z), cell) in array.indexed_iter() {
+            if *cell {
+                grid.set_obstacle(x as u32, y as u32, z as u32);
+            }
+        }
+
+        grid
+    }
+
+    /// Returns the grid as a 3D boolean array indexed as `[x][y][z]`.
+    #[cfg(feature = "ndarray")]
+    pub fn to_array3(&self) -> ndarray::Array3<bool> {
+        let (cells, [width, depth, height]) = self.to_flat_array3();
+
+        // The flattened layout is the standard one, so the shape always fits.
+        ndarray::Array3::from_shape_vec((width, depth, height), cells).unwrap()
+    }
+
+    /// Copies obstacles of the tile into the grid, the tile origin lands at the offset.
+    /// Cells already marked as obstacles are kept.
+    ///
+    /// Panics if the tile doesn't fit into the grid at the offset.
+    pub fn merge_tile(&mut self, tile: &Grid, offset_x: u32, offset_y: u32) {
+        let fits = tile.width <= self.width
+            && offset_x <= self.width - tile.width
+            && offset_y <= self.width - tile.width
+            && tile.height <= self.height;
+
+        assert!(
+            fits,
+            "Tile {}x{} at {}, {} doesn't fit into grid {}x{}",
+            tile.width, tile.height, offset_x, offset_y, self.width, self.height
+        );
+
+        self.blit(tile, (offset_x, offset_y, 0), BlitOp::Or);
+    }
+
+    /// Returns inclusive min and max corners of all obstacles,
+    /// or `None` if the grid has no obstacles.
+    pub fn obstacle_bounds(&self) -> Option<Bounds> {
+        let mut bounds: Option<Bounds> = None;
+        let width = self.width as usize;
+        let length = Self::cell_count(self.width, self.height);
+
+        for (byte_index, byte) in self.data.as_slice().iter().enumerate() {
+            // Most bytes of a sparse grid are empty, skips them at once.
+            if *byte == 0 {
+                continue;
+            }
+
+            for bit in 0..8 {
+                if byte & (1 << bit) == 0 {
+                    continue;
+                }
+
+                let index = byte_index * 8 + bit;
+
+                // Legacy files may have padding bits set, those aren't cells.
+                if index >= length {
+                    break;
+                }
+
+                let x = (index % width) as u32;
+                let y = (index / width % width) as u32;
+                let z = (index / width / width) as u32;
+
+                bounds = Some(match bounds {
+                    Some((min, max)) => (
+                        (min.0.min(x), min.1.min(y), min.2.min(z)),
+                        (max.0.max(x), max.1.max(y), max.2.max(z)),
+                    ),
+                    None => ((x, y, z), (x, y, z)),
+                });
+            }
+        }
+
+        bounds
+    }
+
+    /// Grows obstacles, a cell becomes solid if any cell within
+    /// the Chebyshev `radius` is solid. Cells outside of the grid are ignored.
+    pub fn dilate(&self, radius: u32) -> Grid {
+        self.morph(radius, false)
+    }
+
+    /// Shrinks obstacles, a cell stays solid only if all cells within
+    /// the Chebyshev `radius` are solid. The dual of `dilate`,
+    /// cells outside of the grid are ignored.
+    pub fn erode(&self, radius: u32) -> Grid {
+        self.morph(radius, true)
+    }
+
+    /// Applies dilation, or erosion when `all` is set, over a cube window.
+    fn morph(&self, radius: u32, all: bool) -> Grid {
+        let mut grid = Grid::new(self.width, self.height);
+
+        for (x, y, z) in self.cells() {
+            let window = |axis: u32, size: u32| {
+                axis.saturating_sub(radius)..=axis.saturating_add(radius).min(size - 1)
+            };
+
+            let mut neighbors = window(z, self.height).flat_map(|nz| {
+                window(y, self.width)
+                    .flat_map(move |ny| window(x, self.width).map(move |nx| (nx, ny, nz)))
+            });
+
+            let solid = if all {
+                neighbors.all(|(nx, ny, nz)| self.is_obstacle(nx, ny, nz))
+            } else {
+                neighbors.any(|(nx, ny, nz)| self.is_obstacle(nx, ny, nz))
+            };
+
+            if solid {
+                grid.set_obstacle(x, y, z);
+            }
+        }
+
+        grid
+    }
+
+    /// Copies the inclusive box between the corners into a new grid whose origin
+    /// is the min corner. Corners are clamped to the grid. The new grid is as wide
+    /// as the longer of the X and Y sides since grids are square, the extra cells are open.
+    pub fn crop(&self, min: (u32, u32, u32), max: (u32, u32, u32)) -> Grid {
+        if self.width == 0 || self.height == 0 {
+            return Grid::new(0, 0);
+        }
+
+        let clamp = |axis: u32, size: u32| axis.min(size - 1);
+
+        let (min_x, max_x) = (clamp(min.0, self.width), clamp(max.0, self.width));
+        let (min_y, max_y) = (clamp(min.1, self.width), clamp(max.1, self.width));
+        let (min_z, max_z) = (clamp(min.2, self.height), clamp(max.2, self.height));
+
+        let width = (max_x + 1)
+            .saturating_sub(min_x)
+            .max((max_y + 1).saturating_sub(min_y));
+        let height = (max_z + 1).saturating_sub(min_z);
+
+        let mut grid = Grid::new(width, height);
+
+        for z in min_z..=max_z {
+            for y in min_y..=max_y {
+                for x in min_x..=max_x {
+                    if self.is_obstacle(x, y, z) {
+                        grid.set_obstacle(x - min_x, y - min_y, z - min_z);
+                    }
+                }
+            }
+        }
+
+        grid
+    }
+
+    /// Combines the source grid into this one with the origin of the source at
+    /// the offset. Source cells landing outside of the grid are clipped.
+    pub fn blit(&mut self, src: &Grid, offset: (u32, u32, u32), op: BlitOp) {
+        for (x, y, z) in src.cells() {
+            if !src.is_obstacle(x, y, z) {
+                continue;
+            }
+
+            let (x, y, z) = (
+                x as u64 + offset.0 as u64,
+                y as u64 + offset.1 as u64,
+                z as u64 + offset.2 as u64,
+            );
+
+            if x >= self.width as u64 || y >= self.width as u64 || z >= self.height as u64 {
+                continue;
+            }
+
+            let (x, y, z) = (x as u32, y as u32, z as u32);
+            let index = Self::index(x, y, z, self.width, self.height);
+
+            let value = match op {
+                BlitOp::Or => true,
+                BlitOp::AndNot => false,
+                BlitOp::Xor => !self.is_obstacle(x, y, z),
+            };
+
+            self.data.set(index, value);
+        }
+    }
+
+    /// Returns obstacle cells adjacent to at least one open cell.
+    /// Cells outside of the grid are considered open.
+    pub fn surface_cells(
+        &self,
+        connectivity: Connectivity,
+    ) -> impl Iterator<Item = (u32, u32, u32)> + '_ {
+        let offsets = connectivity.offsets();
+
+        self.cells()
+            .filter(move |&(x, y, z)| self.is_obstacle(x, y, z))
+            .filter(move |&(x, y, z)| {
+                offsets
+                    .iter()
+                    .any(|&(dx, dy, dz)| self.is_open(x as i64 + dx, y as i64 + dy, z as i64 + dz))
+            })
+    }
+
+    /// Same as `surface_cells`, but yields typed cells.
+    pub fn surface_obstacles(&self, connectivity: Connectivity) -> impl Iterator<Item = Cell> + '_ {
+        self.surface_cells(connectivity).map(Cell::from)
+    }
+
+    /// Returns all obstacle cells.
+    pub fn obstacles(&self) -> impl Iterator<Item = Cell> + '_ {
+        self.cells()
+            .filter(move |&(x, y, z)| self.is_obstacle(x, y, z))
+            .map(Cell::from)
+    }
+
+    /// Returns the number of obstacle cells.
+    pub fn count_obstacles(&self) -> usize {
+        let (whole, tail) = self.masked_payload();
+
+        whole
+            .iter()
+            .map(|byte| byte.count_ones() as usize)
+            .sum::<usize>()
+            + tail.count_ones() as usize
+    }
+
+    /// Draws the layer at the altitude with one line per row, `#` for
+    /// obstacles and `.` for open cells, e.g. to print it while debugging.
+    pub fn layer_ascii(&self, z: u32) -> String {
+        let mut ascii = String::new();
+
+        for ((x, _), is_obstacle) in self.layer(z) {
+            ascii.push(if is_obstacle { '#' } else { '.' });
+
+            if x == self.width - 1 {
+                ascii.push('\n');
+            }
+        }
+
+        ascii
+    }
+
+    /// Returns the occupancy of every (x, y) column at the altitude, row by row.
+    pub fn layer(&self, z: u32) -> impl Iterator<Item = ((u32, u32), bool)> + '_ {
+        assert!(self.height > z, "Z-axis coordinate {} is out of bounds", z);
+
+        let width = self.width;
+
+        (0..width)
+            .flat_map(move |y| (0..width).map(move |x| (x, y)))
+            .map(move |(x, y)| ((x, y), self.is_obstacle(x, y, z)))
+    }
+
+    /// Returns the open cell closest to the point, searching outward in
+    /// 26-connected rings. Returns the point itself if it's open, or `None`
+    /// if the point is out of bounds or the grid is fully solid.
+    pub fn nearest_open(&self, x: u32, y: u32, z: u32) -> Option<(u32, u32, u32)> {
+        let start = self.cell_index(x as i32, y as i32, z as i32)?;
+        let offsets = Connectivity::TwentySix.offsets();
+
+        // The search usually stops within a few rings, so only the visited
+        // cells are stored rather than a flag for every cell of the grid.
+        let mut visited = HashSet::new();
+        visited.insert(start);
+
+        let mut queue = VecDeque::new();
+        queue.push_back((x, y, z));
+
+        while let Some((x, y, z)) = queue.pop_front() {
+            if !self.is_obstacle(x, y, z) {
+                return Some((x, y, z));
+            }
+
+            for &(dx, dy, dz) in &offsets {
+                let (nx, ny, nz) = (x as i64 + dx, y as i64 + dy, z as i64 + dz);
+
+                if let Some(index) = self.cell_index(nx as i32, ny as i32, nz as i32) {
+                    if visited.insert(index) {
+                        queue.push_back((nx as u32, ny as u32, nz as u32));
+                    }
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Returns whether no cell the segment between the cell centers passes through
+    /// is an obstacle, including cells it only touches at an edge or a corner and
+    /// both ends. Same cells as `path::supercover`, walked in exact integer steps.
+    /// Panics if either cell is out of bounds.
+    pub fn line_of_sight(&self, a: (u32, u32, u32), b: (u32, u32, u32)) -> bool {
+        let from = [a.0 as i64, a.1 as i64, a.2 as i64];
+        let to = [b.0 as i64, b.1 as i64, b.2 as i64];
+
+        let mut cell = from;
+        let mut crossings = [0i64; 3];
+        let lengths = [0, 1, 2].map(|axis| (to[axis] - from[axis]).abs());
+        let steps = [0, 1, 2].map(|axis| (to[axis] - from[axis]).signum());
+
+        let is_open =
+            |cell: [i64; 3]| !self.is_obstacle(cell[0] as u32, cell[1] as u32, cell[2] as u32);
+
+        if !is_open(cell) {
+            return false;
+        }
+
+        // The segment crosses the n-th cell boundary of an axis at the parameter
+        // (2n + 1) / (2 length), compared across axes by cross-multiplying.
+        let crossing = |axis: usize, crossings: &[i64; 3]| {
+            ((2 * crossings[axis] + 1) as i128, 2 * lengths[axis] as i128)
+        };
+
+        while crossings != lengths {
+            let axes: Vec<usize> = (0..3)
+                .filter(|&axis| crossings[axis] < lengths[axis])
+                .collect();
+
+            let (numerator, denominator) = axes
+                .iter()
+                .map(|&axis| crossing(axis, &crossings))
+                .min_by(|(n1, d1), (n2, d2)| (n1 * d2).cmp(&(n2 * d1)))
+                .unwrap();
+
+            let crossed: Vec<usize> = axes
+                .into_iter()
+                .filter(|&axis| {
+                    let (n, d) = crossing(axis, &crossings);
+                    n * denominator == numerator * d
+                })
+                .collect();
+
+            // Axes crossed at the same time, the segment passes an edge or a corner.
+            for mask in 1..(1 << crossed.len()) - 1 {
+                let mut touched = cell;
+
+                for (bit, &axis) in crossed.iter().enumerate() {
+                    if mask & (1 << bit) != 0 {
+                        touched[axis] += steps[axis];
+                    }
+                }
+
+                if !is_open(touched) {
+                    return false;
+                }
+            }
+
+            for &axis in &crossed {
+                cell[axis] += steps[axis];
+                crossings[axis] += 1;
+            }
+
+            if !is_open(cell) {
+                return false;
+            }
+        }
+
+        true
+    }
+
+    /// Returns the open cells with `line_of_sight` from the observer, casting a ray
+    /// to every cell. Counting the observers which see a cell gives its exposure.
+    /// Nothing is visible from within an obstacle. Panics if the observer is out of bounds.
+    pub fn visible_from(
+        &self,
+        observer: (u32, u32, u32),
+    ) -> impl Iterator<Item = (u32, u32, u32)> + '_ {
+        // Checked up front, the iterator is lazy.
+        Self::index(observer.0, observer.1, observer.2, self.width, self.height);
+
+        self.cells()
+            .filter(move |&cell| self.line_of_sight(observer, cell))
+    }
+
+    /// Swaps obstacles and open cells. Padding bits past the last cell stay clear.
+    pub fn invert(&mut self) {
+        for byte in self.data.as_mut_slice() {
+            *byte = !*byte;
+        }
+
+        let length = Self::cell_count(self.width, self.height);
+
+        for index in length..self.data.len() {
+            self.data.set(index, false);
+        }
+    }
+
+    /// Returns convex obstacle corners, obstacle cells which have an open face
+    /// neighbor along each of the three axes. For a box of obstacles these are
+    /// its eight vertex cells. Cells outside of the grid are considered open.
+    pub fn corner_points(&self) -> Vec<(u32, u32, u32)> {
+        self.cells()
+            .filter(|&(x, y, z)| self.is_obstacle(x, y, z))
+            .filter(|&(x, y, z)| {
+                let (x, y, z) = (x as i64, y as i64, z as i64);
+
+                (self.is_open(x - 1, y, z) || self.is_open(x + 1, y, z))
+                    && (self.is_open(x, y - 1, z) || self.is_open(x, y + 1, z))
+                    && (self.is_open(x, y, z - 1) || self.is_open(x, y, z + 1))
+            })
+            .collect()
+    }
+
+    /// Returns whether the cell is free, cells outside of the grid are free.
+    fn is_open(&self, x: i64, y: i64, z: i64) -> bool {
+        if x < 0 || y < 0 || z < 0 {
+            return true;
+        }
+
+        if x >= self.width as i64 || y >= self.width as i64 || z >= self.height as i64 {
+            return true;
+        }
+
+        !self.is_obstacle(x as u32, y as u32, z as u32)
+    }
+
+    /// Iterates over all cell coordinates of the grid.
+    fn cells(&self) -> impl Iterator<Item = (u32, u32, u32)> {
+        let width = self.width;
+        let height = self.height;
+
+        (0..height)
+            .flat_map(move |z| (0..width).flat_map(move |y| (0..width).map(move |x| (x, y, z))))
+    }
+
+    /// Labels connected regions of open cells using 26-connectivity.
+    pub fn connected_components(&self) -> LabeledGrid {
+        LabeledGrid::new(self)
+    }
+
+    /// Computes approximate euclidean distance from every cell to the nearest obstacle.
+    pub fn distance_field(&self) -> DistanceField {
+        DistanceField::new(self)
+    }
+
+    fn cell_index(&self, x: i32, y: i32, z: i32) -> Option<usize> {
+        if x < 0 || y < 0 || z < 0 {
+            return None;
+        }
+
+        let (x, y, z) = (x as u32, y as u32, z as u32);
+
+        if x >= self.width || y >= self.width || z >= self.height {
+            return None;
+        }
+
+        Some(Self::index(x, y, z, self.width, self.height))
+    }
+
+    /// Index of the cell in the bits, `z` is the slowest changing axis.
+    /// Shared with the per-cell tables of `DistanceField` and `LabeledGrid`.
+    pub(crate) fn index(x: u32, y: u32, z: u32, width: u32, height: u32) -> usize {
+        assert!(width > x, "X-axis coordinate {} is out of bounds", x);
+        assert!(width > y, "Y-axis coordinate {} is out of bounds", y);
+        assert!(height > z, "Z-axis coordinate {} is out of bounds", z);
+
+        let width = width as usize;
+
+        x as usize + width * (y as usize + width * z as usize)
+    }
+
+    /// Checks that the data is exactly as long as the cells need, rounded up
+    /// to whole bytes, and that the padding bits past the last cell are clear.
+    /// Set padding bits aren't cells, they're ignored by comparisons and counts
+    /// but written back out by `export`.
+    pub fn validate(&self) -> Result<(), GridError> {
+        let length = Self::cell_count(self.width, self.height);
+        let expected = length.div_ceil(8);
+        let actual = self.data.as_slice().len();
+
+        if actual != expected || self.data.len() != expected * 8 {
+            return Err(GridError::InvalidLength { expected, actual });
+        }
+
+        if self.data[length..].any() {
+            return Err(GridError::NonZeroPadding);
+        }
+
+        Ok(())
+    }
+
+    /// Number of cells, computed without overflowing `u32` for large grids.
+    fn cell_count(width: u32, height: u32) -> usize {
+        width as usize * width as usize * height as usize
+    }
+
+    pub fn export<P: AsRef<Path>>(self, path: P) -> Result<(), Error> {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+
+        self.write_to(&mut file)
+    }
+
+    /// Returns the cells which differ between the grids of the same size.
+    pub fn diff<'a>(&'a self, other: &'a Grid) -> impl Iterator<Item = (u32, u32, u32)> + 'a {
+        let width = self.width as usize;
+
+        self.diff_indices(other).map(move |index| {
+            let (x, rest) = (index % width, index / width);
+            (x as u32, (rest % width) as u32, (rest / width) as u32)
+        })
+    }
+
+    /// Indices of the differing cells in ascending order, found a byte at a time.
+    fn diff_indices<'a>(&'a self, other: &'a Grid) -> impl Iterator<Item = usize> + 'a {
+        assert!(
+            self.width == other.width && self.height == other.height,
+            "Grids {}x{} and {}x{} differ in size",
+            self.width,
+            self.height,
+            other.width,
+            other.height
+        );
+
+        // Legacy files may have padding bits set, those aren't cells.
+        let length = Self::cell_count(self.width, self.height);
+
+        self.data
+            .as_slice()
+            .iter()
+            .zip(other.data.as_slice())
+            .enumerate()
+            .filter(|(_, (a, b))| a != b)
+            .flat_map(|(byte, (a, b))| {
+                let changed = a ^ b;
+                (0..8)
+                    .filter(move |bit| changed & (1 << bit) != 0)
+                    .map(move |bit| byte * 8 + bit)
+            })
+            .take_while(move |index| *index < length)
+    }
+
+    /// Writes the cells which differ from the base, so `apply_delta` on the base
+    /// gives this grid. Layout: width, height, count of the cells, then the gaps
+    /// between their ascending indices as LEB128 varints.
+    pub fn export_delta<W: Write>(&self, base: &Grid, writer: W) -> Result<(), Error> {
+        let indices: Vec<usize> = self.diff_indices(base).collect();
+        let mut writer = BufWriter::new(writer);
+
+        writer.write_u32::<BigEndian>(self.width)?;
+        writer.write_u32::<BigEndian>(self.height)?;
+        writer.write_u64::<BigEndian>(indices.len() as u64)?;
+
+        let mut previous = 0;
+
+        for index in indices {
+            let mut gap = (index - previous) as u64;
+            previous = index;
+
+            loop {
+                let byte = (gap & 0x7f) as u8;
+                gap >>= 7;
+
+                if gap == 0 {
+                    writer.write_u8(byte)?;
+                    break;
+                }
+
+                writer.write_u8(byte | 0x80)?;
+            }
+        }
+
+        writer.flush()
+    }
+
+    /// Flips the cells written by `export_delta`. Only the base the delta
+    /// was exported against turns into the updated grid. The grid is left
+    /// unchanged when the delta is invalid.
+    pub fn apply_delta<R: Read>(&mut self, reader: R) -> Result<(), GridError> {
+        let mut reader = BufReader::new(reader);
+        let invalid = |message| -> GridError { Error::new(ErrorKind::InvalidData, message).into() };
+
+        let width = reader.read_u32::<BigEndian>()?;
+        let height = reader.read_u32::<BigEndian>()?;
+
+        if width != self.width || height != self.height {
+            return Err(invalid("Grid delta is for a grid of another size"));
+        }
+
+        let count = reader.read_u64::<BigEndian>()?;
+        let length = Self::cell_count(width, height) as u64;
+        let mut indices = Vec::new();
+        let mut index = 0u64;
+
+        for _ in 0..count {
+            let mut gap = 0u64;
+            let mut shift = 0;
+
+            loop {
+                let byte = reader.read_u8()?;
+
+                if shift > 63 {
+                    return Err(invalid("Grid delta gap is too long"));
+                }
+
+                gap |= ((byte & 0x7f) as u64) << shift;
+                shift += 7;
+
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+
+            index = index.saturating_add(gap);
+
+            if index >= length {
+                return Err(invalid("Grid delta cell is out of bounds"));
+            }
+
+            indices.push(index as usize);
+        }
+
+        for index in indices {
+            let value = self.data[index];
+            self.data.set(index, !value);
+        }
+
+        Ok(())
+    }
+
+    /// Writes the grid in the file format into the writer.
+    pub(crate) fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        writer.write_all(&MAGIC)?;
+        writer.write_u32::<BigEndian>(VERSION)?;
+        writer.write_u32::<BigEndian>(self.width)?;
+        writer.write_u32::<BigEndian>(self.height)?;
+        writer.write_all(self.data.as_slice())?;
+        writer.write_u32::<BigEndian>(crc32(self.data.as_slice()))?;
+
+        Ok(())
+    }
+}
+
+/// Parses the grid file format, legacy files included.
+impl TryFrom<&[u8]> for Grid {
+    type Error = GridError;
+
+    fn try_from(mut bytes: &[u8]) -> Result<Self, Self::Error> {
+        let mut grid = Grid::read_from(&mut bytes, false)?;
+        grid.replay_edits(&mut bytes, false)?;
+
+        Ok(grid)
+    }
+}
+
+/// Serializes the grid in the current file format.
+impl From<Grid> for Vec<u8> {
+    fn from(grid: Grid) -> Self {
+        let mut bytes = Vec::new();
+
+        // Writing into a vector never fails.
+        grid.write_to(&mut bytes).unwrap();
+
+        bytes
+    }
+}
+
+/// CRC-32 (IEEE) checksum.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+
+    for byte in bytes {
+        crc ^= *byte as u32;
+
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+/// Summarizes the grid instead of listing its bits.
+impl Debug for Grid {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let cells = Self::cell_count(self.width, self.height);
+        let obstacles = self.count_obstacles();
+        let density = if cells == 0 {
+            0.0
+        } else {
+            obstacles as f64 / cells as f64 * 100.0
+        };
+
+        f.debug_struct("Grid")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("obstacles", &obstacles)
+            .field("density", &format_args!("{:.2}%", density))
+            .finish()
+    }
+}
+
+impl PartialEq for Grid {
+    fn eq(&self, other: &Self) -> bool {
+        self.width == other.width
+            && self.height == other.height
+            && self.masked_payload() == other.masked_payload()
+    }
+}
+
+impl Eq for Grid {}
+
+impl Hash for Grid {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.width.hash(state);
+        self.height.hash(state);
+        // Padding bits are masked, so the hash is stable across load paths.
+        self.masked_payload().hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid::{crc32, Axis, BlitOp, Cell, Connectivity, Grid, GridError, PAYLOAD_OFFSET};
+    use crate::path::validate_path;
+    use crate::provider::SingleGridProvider;
+    use byteorder::{BigEndian, ReadBytesExt};
+    use nalgebra::Vector3;
+    use std::collections::hash_map::DefaultHasher;
+    use std::convert::TryFrom;
+    use std::hash::{Hash, Hasher};
+    use std::io::{Cursor, Read, Write};
+    use std::path::Path;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_export() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+
+        let mut grid = Grid::new(3, 3);
+        grid.set_obstacle(1, 1, 1);
+        grid.set_obstacle(2, 2, 2);
+        grid.export(temp_file.path()).unwrap();
+
+        let length = temp_file.as_file().metadata().unwrap().len();
+        let mut vec = vec![0u8; length as usize];
+        temp_file.read_exact(&mut vec).unwrap();
+
+        let mut cursor = Cursor::new(&vec);
+
+        assert_eq!(vec.len(), 24);
+        assert_eq!(&vec[0..4], b"FPGR");
+        cursor.set_position(4);
+        assert_eq!(cursor.read_u32::<BigEndian>().unwrap(), 1);
+        assert_eq!(cursor.read_u32::<BigEndian>().unwrap(), 3);
+        assert_eq!(cursor.read_u32::<BigEndian>().unwrap(), 3);
+        assert_eq!(cursor.read_u8().unwrap(), 0);
+        assert_eq!(cursor.read_u8().unwrap(), 0b0010_0000);
+        assert_eq!(cursor.read_u8().unwrap(), 0);
+        assert_eq!(cursor.read_u8().unwrap(), 0b0000_0100);
+        assert_eq!(
+            cursor.read_u32::<BigEndian>().unwrap(),
+            crc32(&[0, 0b0010_0000, 0, 0b0000_0100])
+        );
+
+        let grid = Grid::import(temp_file.path()).unwrap();
+        assert_obstacle(&grid);
+    }
+
+    #[test]
+    fn test_payload_offset() {
+        let temp_file = NamedTempFile::new().unwrap();
+
+        let mut grid = Grid::new(3, 3);
+        grid.set_obstacle(0, 0, 0);
+        grid.set_obstacle(2, 0, 0);
+        grid.set_obstacle(1, 1, 0);
+        grid.export(temp_file.path()).unwrap();
+
+        let bytes = std::fs::read(temp_file.path()).unwrap();
+
+        assert_eq!(PAYLOAD_OFFSET % 16, 0);
+        assert_eq!(&bytes[PAYLOAD_OFFSET - 4..PAYLOAD_OFFSET], &[0, 0, 0, 3]);
+        assert_eq!(bytes[PAYLOAD_OFFSET], 0b0001_0101);
+        assert_eq!(bytes.len(), PAYLOAD_OFFSET + 4 + 4);
+    }
+
+    #[test]
+    fn test_bytes_conversion() {
+        let mut grid = Grid::new(3, 3);
+        grid.set_obstacle(1, 1, 1);
+        grid.set_obstacle(2, 2, 2);
+
+        let bytes: Vec<u8> = grid.into();
+
+        assert_eq!(&bytes[0..4], b"FPGR");
+        assert_eq!(bytes.len(), 24);
+
+        let grid = Grid::try_from(bytes.as_slice()).unwrap();
+        assert_obstacle(&grid);
+
+        // Legacy layout from the test data parses as well.
+        let legacy = std::fs::read("test/grid.dat").unwrap();
+        let grid = Grid::try_from(legacy.as_slice()).unwrap();
+        assert_obstacle(&grid);
+
+        assert!(Grid::try_from(&bytes[..10]).is_err());
+
+        let mut corrupted = bytes.clone();
+        corrupted[PAYLOAD_OFFSET] ^= 0b0000_0001;
+
+        match Grid::try_from(corrupted.as_slice()) {
+            Err(GridError::ChecksumMismatch { .. }) => {}
+            result => panic!("Expected checksum mismatch, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_checksum_mismatch() {
+        let temp_file = NamedTempFile::new().unwrap();
+
+        let mut grid = Grid::new(3, 3);
+        grid.set_obstacle(1, 1, 1);
+        grid.set_obstacle(2, 2, 2);
+        grid.export(temp_file.path()).unwrap();
+
+        let mut bytes = std::fs::read(temp_file.path()).unwrap();
+        // Flips a bit of the first payload byte.
+        bytes[PAYLOAD_OFFSET] ^= 0b0000_0001;
+        std::fs::write(temp_file.path(), &bytes).unwrap();
+
+        match Grid::import(temp_file.path()) {
+            Err(GridError::ChecksumMismatch { .. }) => {}
+            result => panic!("Expected checksum mismatch, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let temp_file = NamedTempFile::new().unwrap();
+
+        let mut grid = Grid::new(3, 3);
+        grid.set_obstacle(2, 2, 2);
+        assert!(grid.validate().is_ok());
+
+        // 27 cells in 4 bytes, sets the first of the 5 padding bits.
+        grid.data.set(27, true);
+
+        match grid.validate() {
+            Err(GridError::NonZeroPadding) => {}
+            result => panic!("Expected non-zero padding, got {:?}", result),
+        }
+
+        grid.export(temp_file.path()).unwrap();
+
+        assert!(Grid::import(temp_file.path()).is_ok());
+
+        match Grid::import_validated(temp_file.path()) {
+            Err(GridError::NonZeroPadding) => {}
+            result => panic!("Expected non-zero padding, got {:?}", result),
+        }
+
+        let mut grid = Grid::new(3, 3);
+        grid.data.resize(40, false);
+
+        match grid.validate() {
+            Err(GridError::InvalidLength {
+                expected: 4,
+                actual: 5,
+            }) => {}
+            result => panic!("Expected invalid length, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_padding_ignored() {
+        let hash = |grid: &Grid| {
+            let mut hasher = DefaultHasher::new();
+            grid.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        // Legacy 3x3 files, the second sets the padding bit 27.
+        let mut clear = NamedTempFile::new().unwrap();
+        clear
+            .write_all(&[0, 0, 0, 3, 0, 0, 0, 3, 0, 0, 0, 0])
+            .unwrap();
+
+        let mut padded = NamedTempFile::new().unwrap();
+        padded
+            .write_all(&[0, 0, 0, 3, 0, 0, 0, 3, 0, 0, 0, 0b0000_1000])
+            .unwrap();
+
+        let clear = Grid::import(clear.path()).unwrap();
+        let padded = Grid::import(padded.path()).unwrap();
+
+        assert!(padded.validate().is_err());
+        assert_eq!(padded.obstacles().count(), 0);
+        assert_eq!(clear, padded);
+        assert_eq!(hash(&clear), hash(&padded));
+        assert_eq!(padded, Grid::new(3, 3));
+    }
+
+    #[test]
+    fn test_count_obstacles_padding() {
+        // Legacy 3x3 grid with the cell 26 and the padding bits 27 and 31 set.
+        let bytes: &[u8] = &[0, 0, 0, 3, 0, 0, 0, 3, 0, 0, 0, 0b1000_1100];
+        let grid = Grid::try_from(bytes).unwrap();
+
+        assert!(grid.is_obstacle(2, 2, 2));
+        assert_eq!(grid.obstacles().count(), 1);
+        assert_eq!(grid.count_obstacles(), 1);
+    }
+
+    #[test]
+    fn test_obstacle_bounds_padding() {
+        // Legacy 3x3 grid with only the padding bit 27 set.
+        let bytes: &[u8] = &[0, 0, 0, 3, 0, 0, 0, 3, 0, 0, 0, 0b0000_1000];
+        let grid = Grid::try_from(bytes).unwrap();
+
+        assert_eq!(grid, Grid::new(3, 3));
+        assert_eq!(grid.obstacle_bounds(), None);
+
+        // With the cell 26 set as well.
+        let bytes: &[u8] = &[0, 0, 0, 3, 0, 0, 0, 3, 0, 0, 0, 0b0000_1100];
+        let grid = Grid::try_from(bytes).unwrap();
+
+        assert_eq!(grid.obstacle_bounds(), Some(((2, 2, 2), (2, 2, 2))));
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    // 2000^3 cells, the last index would wrap around in u32 arithmetic.
+    #[cfg(target_pointer_width = "64")]
+    #[test]
+    fn test_index_beyond_u32() {
+        assert_eq!(Grid::cell_count(2000, 2000), 8_000_000_000);
+        assert_eq!(Grid::index(1999, 1999, 1999, 2000, 2000), 7_999_999_999);
+        assert_eq!(Grid::index(5, 7, 1500, 2000, 2000), 6_000_014_005);
+
+        let wrapped = 5u32.wrapping_add(2000u32.wrapping_mul(7 + 2000 * 1500));
+        assert_ne!(wrapped as usize, Grid::index(5, 7, 1500, 2000, 2000));
+    }
+
+    #[test]
+    fn test_tall_grid() {
+        let temp_file = NamedTempFile::new().unwrap();
+
+        let tall_grid = || {
+            let mut grid = Grid::new(2, 1000);
+            grid.set_obstacle(1, 0, 0);
+            grid.set_obstacle(0, 1, 500);
+            grid.set_obstacle(1, 1, 999);
+            grid
+        };
+
+        let grid = tall_grid();
+
+        assert!(grid.is_obstacle(0, 1, 500));
+        assert!(!grid.is_obstacle(1, 1, 998));
+        assert_eq!(grid.get_cell(0, 0, 999), Some(false));
+        assert_eq!(grid.get_cell(0, 0, 1000), None);
+        assert_eq!(grid.get_cell(2, 0, 0), None);
+
+        assert_eq!(
+            grid.obstacles().collect::<Vec<_>>(),
+            vec![
+                Cell::new(1, 0, 0),
+                Cell::new(0, 1, 500),
+                Cell::new(1, 1, 999)
+            ]
+        );
+
+        tall_grid().export(temp_file.path()).unwrap();
+
+        // Header and 4000 bits of payload.
+        let length = temp_file.as_file().metadata().unwrap().len();
+        assert_eq!(length, PAYLOAD_OFFSET as u64 + 500 + 4);
+
+        let imported = Grid::import(temp_file.path()).unwrap();
+
+        assert_eq!(imported.width, 2);
+        assert_eq!(imported.height, 1000);
+        assert_eq!(imported, grid);
+    }
+
+    #[test]
+    #[should_panic(expected = "X-axis coordinate 2 is out of bounds")]
+    fn test_tall_grid_width_bounds() {
+        Grid::new(2, 1000).set_obstacle(2, 0, 0);
+    }
+
+    #[test]
+    fn test_import() {
+        let path = Path::new("test/grid.dat");
+        let grid = Grid::import(path).unwrap();
+
+        assert_obstacle(&grid);
+    }
+
+    #[test]
+    fn test_import_lenient() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+
+        // The valid payload of a 3x3 grid is 4 bytes.
+        let bytes = [0, 0, 0, 3, 0, 0, 0, 3, 0, 0b0010_0000, 0];
+        temp_file.write_all(&bytes).unwrap();
+
+        assert!(Grid::import(temp_file.path()).is_err());
+
+        let grid = Grid::import_lenient(temp_file.path()).unwrap();
+        assert!(grid.is_obstacle(1, 1, 1));
+        assert_eq!(grid, {
+            let mut grid = Grid::new(3, 3);
+            grid.set_obstacle(1, 1, 1);
+            grid
+        });
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(&bytes[..10]).unwrap();
+
+        assert!(Grid::import_lenient(temp_file.path()).is_err());
+    }
+
+    #[test]
+    fn test_obstacle() {
+        let mut grid = Grid::new(3, 3);
+
+        grid.set_obstacle(1, 1, 1);
+        grid.set_obstacle(2, 2, 2);
+
+        assert_obstacle(&grid);
+    }
+
+    #[test]
+    fn test_cell() {
+        let mut grid = Grid::new(3, 3);
+
+        assert!(grid.set_cell(1, 1, 1, true));
+        assert!(grid.set_cell(2, 2, 2, true));
+        assert!(!grid.set_cell(-1, 0, 0, true));
+        assert!(!grid.set_cell(0, 3, 0, true));
+        assert!(!grid.set_cell(0, 0, i32::MAX, true));
+
+        assert_obstacle(&grid);
+
+        assert_eq!(grid.get_cell(1, 1, 1), Some(true));
+        assert_eq!(grid.get_cell(0, 0, 0), Some(false));
+        assert_eq!(grid.get_cell(0, -1, 0), None);
+        assert_eq!(grid.get_cell(3, 0, 0), None);
+
+        assert!(grid.set_cell(1, 1, 1, false));
+        assert_eq!(grid.get_cell(1, 1, 1), Some(false));
+    }
+
+    #[test]
+    fn test_typed_cell() {
+        let mut grid = Grid::new(3, 3);
+
+        grid.set_obstacle_at(Cell::new(1, 1, 1));
+        grid.set_obstacle_at((2, 2, 2));
+
+        assert_obstacle(&grid);
+        assert!(grid.is_obstacle_at(Cell::new(2, 2, 2)));
+        assert!(!grid.is_obstacle_at((0, 1, 2)));
+
+        let obstacles: Vec<Cell> = grid.obstacles().collect();
+        assert_eq!(obstacles, vec![Cell::new(1, 1, 1), Cell::new(2, 2, 2)]);
+
+        let surface: Vec<Cell> = grid.surface_obstacles(Connectivity::Six).collect();
+        assert_eq!(surface, obstacles);
+    }
+
+    #[test]
+    fn test_cell_vector() {
+        let cell = Cell::from_vector(Vector3::new(1.2, 0.0, 2.6)).unwrap();
+
+        assert_eq!(cell, Cell::new(1, 0, 3));
+        assert_eq!(Vector3::from(cell), Vector3::new(1.0, 0.0, 3.0));
+        assert_eq!(<(u32, u32, u32)>::from(cell), (1, 0, 3));
+
+        assert_eq!(Cell::from_vector(Vector3::new(-1.0, 0.0, 0.0)), None);
+        assert_eq!(Cell::from_vector(Vector3::new(0.0, f32::NAN, 0.0)), None);
+
+        assert!(Cell::new(0, 1, 0) > Cell::new(0, 0, 1));
+    }
+
+    #[test]
+    fn test_array3() {
+        let mut cells = vec![false; 3 * 3 * 3];
+        // [x][y][z] = [2][1][0]
+        cells[2 * 9 + 3] = true;
+
+        let grid = Grid::from_flat_array3(&cells, [3, 3, 3]);
+
+        assert!(grid.is_obstacle(2, 1, 0));
+        assert!(!grid.is_obstacle(0, 1, 2));
+
+        let mut grid = Grid::new(12, 2);
+
+        for (x, y, z) in obstacles2_data() {
+            grid.set_obstacle(x, y, z);
+        }
+
+        let (cells, shape) = grid.to_flat_array3();
+
+        assert_eq!(shape, [12, 12, 2]);
+        assert_eq!(Grid::from_flat_array3(&cells, shape), grid);
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn test_ndarray() {
+        use ndarray::ShapeBuilder;
+
+        let mut array = ndarray::Array3::from_elem((3, 3, 2), false);
+        array[[2, 1, 0]] = true;
+
+        let grid = Grid::from_array3(&array);
+
+        assert!(grid.is_obstacle(2, 1, 0));
+        assert!(!grid.is_obstacle(0, 1, 1));
+        assert_eq!(grid.count_obstacles(), 1);
+        assert_eq!(grid.to_array3(), array);
+
+        // Column-major arrays aren't in the flattened layout.
+        let mut fortran = ndarray::Array3::from_elem((3, 3, 2).f(), false);
+        fortran[[2, 1, 0]] = true;
+        assert_eq!(Grid::from_array3(&fortran), grid);
+    }
+
+    #[test]
+    fn test_obstacle_bounds() {
+        let mut grid = Grid::new(12, 2);
+        assert_eq!(grid.obstacle_bounds(), None);
+
+        for (x, y, z) in obstacles2_data() {
+            grid.set_obstacle(x, y, z);
+        }
+
+        assert_eq!(grid.obstacle_bounds(), Some(((1, 3, 1), (11, 8, 1))));
+
+        let mut grid = Grid::new(3, 3);
+        grid.set_obstacle(2, 0, 1);
+        assert_eq!(grid.obstacle_bounds(), Some(((2, 0, 1), (2, 0, 1))));
+    }
+
+    #[test]
+    fn test_crop() {
+        let mut grid = Grid::new(12, 2);
+
+        for (x, y, z) in obstacles2_data() {
+            grid.set_obstacle(x, y, z);
+        }
+
+        let (min, max) = grid.obstacle_bounds().unwrap();
+        let cropped = grid.crop(min, max);
+
+        assert_eq!(cropped.width, 11);
+        assert_eq!(cropped.height, 1);
+        assert_eq!(cropped.obstacles().count(), obstacles2_data().len());
+
+        for (x, y, z) in obstacles2_data() {
+            assert!(cropped.is_obstacle(x - min.0, y - min.1, z - min.2));
+        }
+
+        // Out of range corners are clamped to the grid.
+        assert_eq!(grid.crop((0, 0, 0), (100, 100, 100)), grid);
+    }
+
+    #[test]
+    fn test_erode_thin_wall() {
+        let mut grid = Grid::new(5, 5);
+
+        for y in 0..5 {
+            for z in 0..5 {
+                grid.set_obstacle(2, y, z);
+            }
+        }
+
+        assert_eq!(grid.erode(1).obstacles().count(), 0);
+        assert_eq!(grid.erode(0), grid);
+    }
+
+    #[test]
+    fn test_dilate_erode() {
+        let mut grid = Grid::new(7, 7);
+        grid.set_obstacle(3, 3, 3);
+
+        let dilated = grid.dilate(1);
+
+        assert_eq!(dilated.obstacles().count(), 27);
+        assert!(dilated.is_obstacle(2, 4, 2));
+        assert!(!dilated.is_obstacle(1, 3, 3));
+
+        assert_eq!(dilated.erode(1), grid);
 
-        let mut data = BitVec::with_capacity(length_with_padding);
-        data.resize(length_with_padding, false);
+        // Closing fills the one cell gap between two blocks.
+        let mut gap = Grid::new(7, 7);
 
-        Grid {
-            width,
-            height,
-            data,
+        for (x, y, z) in gap.cells().collect::<Vec<_>>() {
+            if x != 3 {
+                gap.set_obstacle(x, y, z);
+            }
         }
+
+        assert_eq!(gap.dilate(1).erode(1).obstacles().count(), 7 * 7 * 7);
     }
 
-    pub fn import<P: AsRef<Path>>(path: P) -> Result<Grid, Error> {
-        let mut file = File::open(path)?;
+    #[test]
+    fn test_merge_tile() {
+        let mut left = Grid::new(3, 3);
+        left.set_obstacle(0, 0, 0);
+        left.set_obstacle(2, 1, 2);
 
-        let width = file.read_u32::<BigEndian>()?;
-        let height = file.read_u32::<BigEndian>()?;
-        let length = width * width * height;
+        let mut right = Grid::new(3, 3);
+        right.set_obstacle(0, 2, 1);
+        right.set_obstacle(2, 2, 2);
 
-        let bytes = (length as f32 / 8.0).ceil();
-        let mut vec = vec![0u8; bytes as usize];
-        file.read_exact(&mut vec)?;
+        let mut grid = Grid::new(6, 3);
+        grid.merge_tile(&left, 0, 0);
+        grid.merge_tile(&right, 3, 0);
 
-        let data = BitVec::from_vec(vec);
+        let expected = [(0, 0, 0), (2, 1, 2), (3, 2, 1), (5, 2, 2)];
 
-        let grid = Grid {
-            width,
-            height,
-            data,
+        for x in 0..grid.width {
+            for y in 0..grid.width {
+                for z in 0..grid.height {
+                    assert_eq!(
+                        grid.is_obstacle(x, y, z),
+                        expected.contains(&(x, y, z)),
+                        "Merged grid doesn't match for x: {}, y: {}, z: {}",
+                        x,
+                        y,
+                        z
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_blit() {
+        let mut block = Grid::new(2, 2);
+
+        for (x, y, z) in block.cells().collect::<Vec<_>>() {
+            block.set_obstacle(x, y, z);
+        }
+
+        // Destination with a single obstacle inside of the stamped area.
+        let destination = || {
+            let mut grid = Grid::new(4, 4);
+            grid.set_obstacle(1, 1, 1);
+            grid
         };
 
-        Ok(grid)
+        let stamped = |x: u32, y: u32, z: u32| {
+            (1..3).contains(&x) && (1..3).contains(&y) && (1..3).contains(&z)
+        };
+
+        let mut grid = destination();
+        grid.blit(&block, (1, 1, 1), BlitOp::Or);
+        assert_eq!(grid.obstacles().count(), 8);
+        assert!(grid.obstacles().all(|cell| stamped(cell.x, cell.y, cell.z)));
+
+        let mut grid = destination();
+        grid.set_obstacle(0, 0, 0);
+        grid.blit(&block, (1, 1, 1), BlitOp::AndNot);
+        assert_eq!(
+            grid.obstacles().collect::<Vec<_>>(),
+            vec![Cell::new(0, 0, 0)]
+        );
+
+        let mut grid = destination();
+        grid.blit(&block, (1, 1, 1), BlitOp::Xor);
+        assert_eq!(grid.obstacles().count(), 7);
+        assert!(!grid.is_obstacle(1, 1, 1));
+        assert!(grid.is_obstacle(2, 2, 2));
+
+        // Clipped at the destination bounds.
+        let mut grid = destination();
+        grid.blit(&block, (3, 3, 3), BlitOp::Or);
+        assert_eq!(grid.obstacles().count(), 2);
+        assert!(grid.is_obstacle(3, 3, 3));
     }
 
-    pub fn set_obstacle(&mut self, x: u32, y: u32, z: u32) {
-        let index = Self::index(x, y, z, self.width);
+    #[test]
+    #[should_panic]
+    fn test_merge_tile_out_of_bounds() {
+        let mut grid = Grid::new(6, 3);
+        grid.merge_tile(&Grid::new(3, 3), 4, 0);
+    }
 
-        // It's safe because we already checked the bounds.
-        unsafe {
-            self.data.set_unchecked(index, true);
+    #[test]
+    fn test_nearest_open() {
+        let mut grid = Grid::new(7, 7);
+
+        for z in 2..=4 {
+            for y in 2..=4 {
+                for x in 2..=4 {
+                    grid.set_obstacle(x, y, z);
+                }
+            }
         }
-    }
 
-    pub fn is_obstacle(&self, x: u32, y: u32, z: u32) -> bool {
-        let index = Self::index(x, y, z, self.width);
+        let (x, y, z) = grid.nearest_open(3, 3, 3).unwrap();
 
-        // It's safe because we already checked the bounds.
-        unsafe { *self.data.get_unchecked(index) }
-    }
+        // The first open cell touches the block's surface.
+        assert!(!grid.is_obstacle(x, y, z));
+        assert_eq!(
+            [x, y, z].iter().map(|axis| (*axis as i32 - 3).abs()).max(),
+            Some(2)
+        );
 
-    fn index(x: u32, y: u32, z: u32, width: u32) -> usize {
-        assert!(width > x, "X-axis coordinate {} is out of bounds", x);
-        assert!(width > y, "Y-axis coordinate {} is out of bounds", y);
-        assert!(width > z, "Z-axis coordinate {} is out of bounds", z);
+        assert_eq!(grid.nearest_open(0, 0, 0), Some((0, 0, 0)));
+        assert_eq!(grid.nearest_open(7, 0, 0), None);
 
-        (x + width * (y + width * z)) as usize
+        let solid = grid.dilate(6);
+        assert_eq!(solid.nearest_open(3, 3, 3), None);
     }
 
-    pub fn export<P: AsRef<Path>>(self, path: P) -> Result<(), Error> {
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(path)?;
+    #[test]
+    fn test_invert() {
+        let mut grid = Grid::new(3, 3);
+        grid.set_obstacle(1, 1, 1);
+        grid.set_obstacle(2, 2, 2);
 
-        file.write_u32::<BigEndian>(self.width)?;
-        file.write_u32::<BigEndian>(self.height)?;
-        file.write_all(self.data.as_slice())?;
+        grid.invert();
 
-        Ok(())
-    }
-}
+        assert_eq!(grid.obstacles().count(), 27 - 2);
+        assert!(!grid.is_obstacle(1, 1, 1));
+        assert!(grid.is_obstacle(0, 0, 0));
+        // 27 cells in 4 bytes, the 5 padding bits of the last byte are clear.
+        assert_eq!(grid.data.as_slice()[3], 0b0000_0011);
 
-#[cfg(test)]
-mod tests {
-    use crate::grid::Grid;
-    use byteorder::{BigEndian, ReadBytesExt};
-    use std::io::{Cursor, Read};
-    use std::path::Path;
-    use tempfile::NamedTempFile;
+        grid.invert();
+
+        let mut original = Grid::new(3, 3);
+        original.set_obstacle(1, 1, 1);
+        original.set_obstacle(2, 2, 2);
+
+        assert_eq!(grid, original);
+    }
 
     #[test]
-    fn test_export() {
-        let mut temp_file = NamedTempFile::new().unwrap();
+    fn test_corner_points() {
+        let mut grid = Grid::new(7, 7);
+
+        for z in 2..=4 {
+            for y in 2..=4 {
+                for x in 2..=4 {
+                    grid.set_obstacle(x, y, z);
+                }
+            }
+        }
+
+        let mut expected = Vec::new();
+
+        for &z in &[2, 4] {
+            for &y in &[2, 4] {
+                for &x in &[2, 4] {
+                    expected.push((x, y, z));
+                }
+            }
+        }
+
+        assert_eq!(grid.corner_points(), expected);
 
         let mut grid = Grid::new(3, 3);
         grid.set_obstacle(1, 1, 1);
-        grid.set_obstacle(2, 2, 2);
-        grid.export(temp_file.path()).unwrap();
+        assert_eq!(grid.corner_points(), vec![(1, 1, 1)]);
+    }
 
-        let length = temp_file.as_file().metadata().unwrap().len();
-        let mut vec = vec![0u8; length as usize];
-        temp_file.read_exact(&mut vec).unwrap();
+    #[test]
+    fn test_surface_cells() {
+        let mut grid = Grid::new(7, 7);
 
-        let mut cursor = Cursor::new(&vec);
+        for x in 1..6 {
+            for y in 1..6 {
+                for z in 1..6 {
+                    grid.set_obstacle(x, y, z);
+                }
+            }
+        }
 
-        assert_eq!(vec.len(), 12);
-        assert_eq!(cursor.read_u32::<BigEndian>().unwrap(), 3);
-        assert_eq!(cursor.read_u32::<BigEndian>().unwrap(), 3);
-        assert_eq!(cursor.read_u8().unwrap(), 0);
-        assert_eq!(cursor.read_u8().unwrap(), 0b0010_0000);
-        assert_eq!(cursor.read_u8().unwrap(), 0);
-        assert_eq!(cursor.read_u8().unwrap(), 0b0000_0100);
+        let is_shell =
+            |&(x, y, z): &(u32, u32, u32)| [x, y, z].iter().any(|axis| *axis == 1 || *axis == 5);
+
+        let surface: Vec<_> = grid.surface_cells(Connectivity::Six).collect();
+
+        assert_eq!(surface.len(), 5 * 5 * 5 - 3 * 3 * 3);
+        assert!(surface.iter().all(is_shell));
+
+        let surface: Vec<_> = grid.surface_cells(Connectivity::TwentySix).collect();
+
+        assert_eq!(surface.len(), 5 * 5 * 5 - 3 * 3 * 3);
+        assert!(surface.iter().all(is_shell));
     }
 
     #[test]
-    fn test_import() {
-        let path = Path::new("test/grid.dat");
-        let grid = Grid::import(path).unwrap();
+    fn test_surface_cells_connectivity() {
+        let mut grid = Grid::new(5, 5);
 
-        assert_obstacle(&grid);
+        for x in 0..5 {
+            for y in 0..5 {
+                for z in 0..5 {
+                    grid.set_obstacle(x, y, z);
+                }
+            }
+        }
+
+        grid.set_cell(2, 2, 2, false);
+
+        // Grid border cells plus neighbors of the carved out cell.
+        assert_eq!(grid.surface_cells(Connectivity::Six).count(), 98 + 6);
+        assert_eq!(grid.surface_cells(Connectivity::TwentySix).count(), 98 + 26);
     }
 
     #[test]
-    fn test_obstacle() {
-        let mut grid = Grid::new(3, 3);
+    fn test_hash_eq() {
+        let mut grid1 = Grid::new(3, 3);
+        grid1.set_obstacle(1, 1, 1);
+        grid1.set_obstacle(2, 2, 2);
 
-        grid.set_obstacle(1, 1, 1);
-        grid.set_obstacle(2, 2, 2);
+        let grid2 = Grid::import(Path::new("test/grid.dat")).unwrap();
 
-        assert_obstacle(&grid);
+        assert_eq!(grid1, grid2);
+        assert_eq!(hash(&grid1), hash(&grid2));
+
+        grid1.set_obstacle(0, 0, 0);
+
+        assert_ne!(grid1, grid2);
+    }
+
+    fn hash(grid: &Grid) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        grid.hash(&mut hasher);
+        hasher.finish()
     }
 
     fn assert_obstacle(grid: &Grid) {
@@ -158,6 +2157,251 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_layer() {
+        let mut grid = Grid::new(12, 2);
+
+        for (x, y, z) in obstacles2_data() {
+            grid.set_obstacle(x, y, z);
+        }
+
+        let layer: Vec<((u32, u32), bool)> = grid.layer(1).collect();
+        assert_eq!(layer.len(), 12 * 12);
+
+        for ((x, y), is_obstacle) in layer {
+            assert_eq!(is_obstacle, obstacles2_data().contains(&(x, y, 1)));
+        }
+
+        assert!(grid.layer(0).all(|(_, is_obstacle)| !is_obstacle));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_layer_out_of_bounds() {
+        let _ = Grid::new(12, 2).layer(2);
+    }
+
+    #[test]
+    fn test_set_obstacle_sphere() {
+        let mut grid = Grid::new(8, 8);
+        grid.set_obstacle_sphere(Vector3::new(4.0, 4.0, 4.0), 2.0);
+
+        // 1 center, 6 at distance 1, 12 at sqrt(2), 8 at sqrt(3), 6 at distance 2.
+        assert_eq!(grid.obstacles().count(), 33);
+        assert!(grid.is_obstacle(4, 4, 6));
+        assert!(!grid.is_obstacle(5, 5, 6));
+
+        // Clamped at the grid corner, only the non-negative octant remains.
+        let mut grid = Grid::new(8, 8);
+        grid.set_obstacle_sphere(Vector3::new(0.0, 0.0, 0.0), 2.0);
+
+        assert_eq!(grid.obstacles().count(), 11);
+    }
+
+    #[test]
+    fn test_set_obstacle_cylinder() {
+        let mut grid = Grid::new(8, 8);
+        grid.set_obstacle_cylinder(Vector3::new(4.0, 4.0, 1.0), 1.0, 3.0, Axis::Z);
+
+        // Five cells per layer, on layers 1 to 3.
+        assert_eq!(grid.obstacles().count(), 15);
+        assert!(grid.is_obstacle(4, 5, 3));
+        assert!(!grid.is_obstacle(4, 4, 4));
+        assert!(!grid.is_obstacle(4, 4, 0));
+
+        let mut grid = Grid::new(8, 8);
+        grid.set_obstacle_cylinder(Vector3::new(-2.0, 4.0, 4.0), 1.0, 20.0, Axis::X);
+
+        assert_eq!(grid.obstacles().count(), 5 * 8);
+        assert!(grid.is_obstacle(7, 4, 5));
+    }
+
+    #[test]
+    fn test_carve_path() {
+        let walled_grid = || {
+            let mut grid = Grid::new(12, 4);
+
+            for y in 0..12 {
+                for z in 0..4 {
+                    grid.set_obstacle(5, y, z);
+                }
+            }
+
+            grid
+        };
+
+        let path = [
+            Vector3::new(1.0, 1.0, 1.0),
+            Vector3::new(9.0, 3.0, 1.0),
+            Vector3::new(9.0, 9.0, 1.0),
+        ];
+
+        assert!(!validate_path(
+            &path,
+            &SingleGridProvider::new(walled_grid())
+        ));
+
+        let mut grid = walled_grid();
+        grid.carve_path(&path, 0);
+
+        // Only the cell the path crosses the wall at is cleared.
+        assert_eq!(grid.obstacles().count(), 12 * 4 - 1);
+        assert!(!grid.is_obstacle(5, 2, 1));
+        assert!(validate_path(&path, &SingleGridProvider::new(grid)));
+    }
+
+    #[test]
+    fn test_carve_path_radius() {
+        let mut grid = Grid::new(8, 8);
+        grid.set_obstacle_sphere(Vector3::new(4.0, 4.0, 4.0), 3.0);
+
+        grid.carve_path(&[Vector3::new(4.0, 4.0, 4.0)], 1);
+
+        assert!(!grid.is_obstacle(4, 4, 4));
+        assert!(!grid.is_obstacle(4, 5, 4));
+        assert!(grid.is_obstacle(4, 5, 5));
+    }
+
+    #[test]
+    fn test_line_of_sight() {
+        let mut grid = Grid::new(8, 8);
+
+        assert!(grid.line_of_sight((0, 0, 0), (7, 3, 5)));
+        assert!(grid.line_of_sight((2, 2, 2), (2, 2, 2)));
+
+        grid.set_obstacle(4, 2, 3);
+
+        // The cell is on the line.
+        assert!(!grid.line_of_sight((0, 0, 0), (7, 3, 5)));
+        assert!(!grid.line_of_sight((7, 3, 5), (0, 0, 0)));
+        assert!(!grid.line_of_sight((4, 0, 3), (4, 7, 3)));
+        assert!(!grid.line_of_sight((4, 2, 3), (0, 0, 0)));
+
+        // The cell is off the line.
+        assert!(grid.line_of_sight((0, 0, 0), (7, 0, 0)));
+        assert!(grid.line_of_sight((0, 5, 3), (7, 5, 3)));
+        assert!(grid.line_of_sight((4, 3, 0), (4, 3, 7)));
+        assert!(grid.line_of_sight((5, 0, 3), (5, 7, 3)));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "past the grid data")]
+    fn test_index_past_data() {
+        let mut grid = Grid::new(4, 4);
+
+        // Data shorter than the cells, the bounds checks of `index` pass.
+        grid.data.truncate(8);
+        grid.is_obstacle(3, 3, 3);
+    }
+
+    #[test]
+    fn test_visible_from() {
+        let mut grid = Grid::new(8, 1);
+
+        // Wall at x = 4 with a gap from y = 5.
+        for y in 0..5 {
+            grid.set_obstacle(4, y, 0);
+        }
+
+        let visible: Vec<(u32, u32, u32)> = grid.visible_from((1, 3, 0)).collect();
+
+        assert!(visible.contains(&(1, 3, 0)));
+        assert!(visible.contains(&(3, 0, 0)));
+        assert!(!visible.contains(&(4, 3, 0)));
+
+        // Behind the wall.
+        assert!(!visible.contains(&(6, 3, 0)));
+        assert!(!visible.contains(&(7, 0, 0)));
+        assert!(!visible.contains(&(5, 5, 0)));
+
+        // Through the gap.
+        assert!(visible.contains(&(4, 6, 0)));
+
+        assert_eq!(grid.visible_from((4, 2, 0)).count(), 0);
+    }
+
+    #[test]
+    fn test_line_of_sight_matches_supercover() {
+        let mut grid = Grid::new(6, 6);
+        grid.set_obstacle(2, 3, 1);
+
+        let cells: Vec<(u32, u32, u32)> = grid.cells().collect();
+
+        for &from in cells.iter().step_by(7) {
+            for &to in cells.iter().step_by(5) {
+                let supercover = crate::path::supercover(
+                    Vector3::new(from.0 as f32, from.1 as f32, from.2 as f32),
+                    Vector3::new(to.0 as f32, to.1 as f32, to.2 as f32),
+                );
+                let expected = !supercover.contains(&Vector3::new(2.0, 3.0, 1.0));
+
+                assert_eq!(
+                    grid.line_of_sight(from, to),
+                    expected,
+                    "Line of sight differs from {:?} to {:?}",
+                    from,
+                    to
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_from_heightmap() {
+        let grid = Grid::from_heightmap(&[2; 16], 4, 5);
+
+        for (x, y, z) in grid.cells() {
+            assert_eq!(grid.is_obstacle(x, y, z), z < 2);
+        }
+
+        let mut heights = vec![0; 9];
+        heights[1 + 3 * 2] = 7;
+        let grid = Grid::from_heightmap(&heights, 3, 4);
+
+        assert_eq!(grid.obstacles().count(), 4);
+        assert!(grid.is_obstacle(1, 2, 3));
+        assert!(!grid.is_obstacle(2, 1, 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Heightmap must have width * width elevations")]
+    fn test_from_heightmap_length() {
+        Grid::from_heightmap(&[2; 12], 4, 5);
+    }
+
+    #[test]
+    fn test_debug() {
+        let mut grid = Grid::new(12, 2);
+
+        for (x, y, z) in obstacles2_data() {
+            grid.set_obstacle(x, y, z);
+        }
+
+        let obstacles = obstacles2_data().len();
+        assert_eq!(grid.count_obstacles(), obstacles);
+
+        let density = obstacles as f64 / (12 * 12 * 2) as f64 * 100.0;
+
+        assert_eq!(
+            format!("{:?}", grid),
+            format!(
+                "Grid {{ width: 12, height: 2, obstacles: {}, density: {:.2}% }}",
+                obstacles, density
+            )
+        );
+    }
+
+    #[test]
+    fn test_layer_ascii() {
+        let mut grid = Grid::new(3, 2);
+        grid.set_obstacle(0, 0, 1);
+        grid.set_obstacle(2, 1, 1);
+
+        assert_eq!(grid.layer_ascii(1), "#..\n..#\n...\n");
+        assert_eq!(grid.layer_ascii(0), "...\n...\n...\n");
+    }
+
     #[test]
     fn test_obstacles2() {
         let mut grid = Grid::new(12, 2);
@@ -182,6 +2426,95 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_csv() {
+        let mut grid = Grid::new(12, 2);
+
+        for (x, y, z) in obstacles2_data() {
+            grid.set_obstacle(x, y, z);
+        }
+
+        let mut csv = Vec::new();
+        grid.export_csv(&mut csv).unwrap();
+
+        assert_eq!(
+            String::from_utf8(csv.clone()).unwrap().lines().count(),
+            obstacles2_data().len()
+        );
+        assert_eq!(Grid::import_csv(&csv[..], 12, 2).unwrap(), grid);
+
+        let grid = Grid::import_csv(" 1, 2 ,0\n\n3,4,1\n".as_bytes(), 5, 2).unwrap();
+        assert_eq!(grid.count_obstacles(), 2);
+        assert!(grid.is_obstacle(1, 2, 0));
+        assert!(grid.is_obstacle(3, 4, 1));
+
+        let line = |csv: &str| match Grid::import_csv(csv.as_bytes(), 5, 2) {
+            Err(GridError::InvalidCsv { line, .. }) => line,
+            result => panic!("Unexpected result {:?}", result),
+        };
+
+        assert_eq!(line("1,1,1\n1,x,1\n"), 2);
+        assert_eq!(line("1,1\n"), 1);
+        assert_eq!(line("1,1,1\n\n0,0,2\n"), 3);
+        assert_eq!(line("5,0,0\n"), 1);
+    }
+
+    #[test]
+    fn test_delta() {
+        let base = || {
+            let mut grid = Grid::new(12, 2);
+            grid.set_obstacle(0, 0, 0);
+            grid.set_obstacle(11, 11, 1);
+            grid
+        };
+
+        let mut updated = base();
+        updated.set_cell(0, 0, 0, false);
+
+        for (x, y, z) in obstacles2_data() {
+            updated.set_obstacle(x, y, z);
+        }
+
+        let diff: Vec<(u32, u32, u32)> = base().diff(&updated).collect();
+
+        assert_eq!(diff.len(), obstacles2_data().len() + 1);
+        assert_eq!(diff[0], (0, 0, 0));
+        assert!(diff
+            .iter()
+            .all(|&(x, y, z)| base().is_obstacle(x, y, z) != updated.is_obstacle(x, y, z)));
+
+        let mut delta = Vec::new();
+        updated.export_delta(&base(), &mut delta).unwrap();
+
+        // Far smaller than the grid, a byte or two per changed cell.
+        assert!(delta.len() < 16 + 2 * (obstacles2_data().len() + 1));
+
+        let mut applied = base();
+        applied.apply_delta(&delta[..]).unwrap();
+        assert_eq!(applied, updated);
+
+        // The same delta flips the cells back.
+        applied.apply_delta(&delta[..]).unwrap();
+        assert_eq!(applied, base());
+
+        let mut empty = Vec::new();
+        base().export_delta(&base(), &mut empty).unwrap();
+        assert_eq!(empty.len(), 16);
+
+        assert!(Grid::new(3, 3).apply_delta(&delta[..]).is_err());
+        assert!(base().apply_delta(&delta[..delta.len() - 1]).is_err());
+
+        // Legacy 3x3 grid with the padding bit 31 set.
+        let padded: &[u8] = &[0, 0, 0, 3, 0, 0, 0, 3, 0, 0, 0, 0b1000_0000];
+        let padded = Grid::try_from(padded).unwrap();
+
+        assert_eq!(padded.diff(&Grid::new(3, 3)).count(), 0);
+
+        let mut delta = Vec::new();
+        padded.export_delta(&Grid::new(3, 3), &mut delta).unwrap();
+        assert_eq!(delta.len(), 16);
+    }
+
     fn obstacles2_data() -> Vec<(u32, u32, u32)> {
         vec![
             (1, 7, 1),
@@ -0,0 +1,187 @@
+use crate::grid::{Cell, Grid, GridError, Trailer, EDIT_LOG_MAGIC};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufWriter, Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// Grid file which records edits by appending them instead of rewriting
+/// the whole grid. `Grid::import` replays the edits on top of the base grid.
+pub struct AppendableGrid {
+    grid: Grid,
+    path: PathBuf,
+    file: File,
+    /// Whether the file already has the edit log marker after the base grid.
+    logged: bool,
+}
+
+impl Grid {
+    /// Opens the grid file for appending edits, see `AppendableGrid`.
+    /// Fails if the file has a torn edit or other data after the grid.
+    pub fn open_appendable<P: AsRef<Path>>(path: P) -> Result<AppendableGrid, GridError> {
+        let path = path.as_ref().to_path_buf();
+
+        let mut file = File::open(&path)?;
+        let mut grid = Grid::read_from(&mut file, false)?;
+
+        let logged = match grid.replay_edits(&mut file, false)? {
+            Trailer::Empty => false,
+            Trailer::EditLog => true,
+            Trailer::Unknown => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "Grid file has trailing data which isn't an edit log",
+                )
+                .into())
+            }
+        };
+
+        let file = OpenOptions::new().append(true).open(&path)?;
+
+        Ok(AppendableGrid {
+            grid,
+            path,
+            file,
+            logged,
+        })
+    }
+}
+
+impl AppendableGrid {
+    /// Returns the grid with all edits applied.
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    /// Sets the cell and appends the edit to the file.
+    pub fn append_edit(&mut self, cell: Cell, value: bool) -> Result<(), GridError> {
+        if !self
+            .grid
+            .set_cell(cell.x as i32, cell.y as i32, cell.z as i32, value)
+        {
+            return Err(Error::new(ErrorKind::InvalidInput, "Grid edit is out of bounds").into());
+        }
+
+        if !self.logged {
+            self.file.write_all(&EDIT_LOG_MAGIC)?;
+            self.logged = true;
+        }
+
+        Grid::write_edit(&mut self.file, cell, value)?;
+
+        Ok(())
+    }
+
+    /// Rewrites the base grid with all edits applied and drops the edit log.
+    /// The grid is written to a temporary file next to it which then replaces
+    /// the file, so it's never left half written.
+    pub fn compact(&mut self) -> Result<(), GridError> {
+        let mut temp_path = self.path.clone().into_os_string();
+        temp_path.push(".compact");
+        let temp_path = PathBuf::from(temp_path);
+
+        let file = File::create(&temp_path)?;
+        let mut writer = BufWriter::new(file);
+        self.grid.write_to(&mut writer)?;
+
+        let file = writer.into_inner().map_err(|error| error.into_error())?;
+        file.sync_all()?;
+
+        fs::rename(&temp_path, &self.path)?;
+
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        self.logged = false;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid::{Cell, Grid};
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_replay_edits() {
+        let temp_file = NamedTempFile::new().unwrap();
+
+        let mut grid = Grid::new(3, 3);
+        grid.set_obstacle(0, 0, 0);
+        grid.export(temp_file.path()).unwrap();
+
+        let base_length = std::fs::metadata(temp_file.path()).unwrap().len();
+
+        let mut appendable = Grid::open_appendable(temp_file.path()).unwrap();
+        appendable.append_edit(Cell::new(1, 1, 1), true).unwrap();
+        appendable.append_edit(Cell::new(2, 2, 2), true).unwrap();
+        appendable.append_edit(Cell::new(0, 0, 0), false).unwrap();
+        assert!(appendable.append_edit(Cell::new(3, 0, 0), true).is_err());
+
+        let mut expected = Grid::new(3, 3);
+        expected.set_obstacle(1, 1, 1);
+        expected.set_obstacle(2, 2, 2);
+
+        let expected_file = NamedTempFile::new().unwrap();
+        expected.export(expected_file.path()).unwrap();
+        let expected = Grid::import(expected_file.path()).unwrap();
+
+        assert_eq!(appendable.grid(), &expected);
+        assert_eq!(Grid::import(temp_file.path()).unwrap(), expected);
+        assert_eq!(
+            std::fs::metadata(temp_file.path()).unwrap().len(),
+            base_length + 4 + 3 * 13
+        );
+
+        appendable.compact().unwrap();
+
+        assert_eq!(
+            std::fs::metadata(temp_file.path()).unwrap().len(),
+            base_length
+        );
+        assert_eq!(
+            std::fs::read(temp_file.path()).unwrap(),
+            std::fs::read(expected_file.path()).unwrap()
+        );
+
+        appendable.append_edit(Cell::new(0, 1, 2), true).unwrap();
+        assert!(Grid::import(temp_file.path()).unwrap().is_obstacle(0, 1, 2));
+    }
+
+    #[test]
+    fn test_torn_edit() {
+        let temp_file = NamedTempFile::new().unwrap();
+        Grid::new(3, 3).export(temp_file.path()).unwrap();
+
+        let mut appendable = Grid::open_appendable(temp_file.path()).unwrap();
+        appendable.append_edit(Cell::new(1, 1, 1), true).unwrap();
+
+        let mut bytes = std::fs::read(temp_file.path()).unwrap();
+        bytes.extend_from_slice(&[0, 0, 0]);
+        std::fs::write(temp_file.path(), &bytes).unwrap();
+
+        assert!(Grid::import(temp_file.path()).is_err());
+        assert!(Grid::open_appendable(temp_file.path()).is_err());
+
+        let grid = Grid::import_lenient(temp_file.path()).unwrap();
+        assert_eq!(
+            grid.obstacles().collect::<Vec<_>>(),
+            vec![Cell::new(1, 1, 1)]
+        );
+    }
+
+    #[test]
+    fn test_trailing_data() {
+        let temp_file = NamedTempFile::new().unwrap();
+
+        let mut grid = Grid::new(3, 3);
+        grid.set_obstacle(0, 0, 0);
+        grid.export(temp_file.path()).unwrap();
+
+        // Data after the grid without the edit log marker isn't replayed,
+        // even if it looks like whole edit records.
+        let mut bytes = std::fs::read(temp_file.path()).unwrap();
+        bytes.extend_from_slice(&[0u8; 13]);
+        std::fs::write(temp_file.path(), &bytes).unwrap();
+
+        assert!(Grid::import(temp_file.path()).unwrap().is_obstacle(0, 0, 0));
+        assert!(Grid::open_appendable(temp_file.path()).is_err());
+    }
+}
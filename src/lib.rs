@@ -1,3 +1,20 @@
+pub mod alternatives;
+pub mod archive;
+pub mod cooperative;
+pub mod distance;
 pub mod grid;
+pub mod journal;
+#[cfg(feature = "mesh")]
+pub mod mesh;
+pub mod octree;
+pub mod path;
 pub mod pathfinding;
 pub mod provider;
+pub mod region;
+pub mod replay;
+mod rng;
+pub mod roadmap;
+#[cfg(feature = "testutil")]
+pub mod testutil;
+pub mod tour;
+pub mod transform;
@@ -0,0 +1,140 @@
+use crate::grid::{Cell, Connectivity, Grid};
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Error, Write};
+use std::path::Path;
+
+/// Cube corners relative to the cell center.
+const CORNERS: [(f32, f32, f32); 8] = [
+    (-0.5, -0.5, -0.5),
+    (0.5, -0.5, -0.5),
+    (0.5, 0.5, -0.5),
+    (-0.5, 0.5, -0.5),
+    (-0.5, -0.5, 0.5),
+    (0.5, -0.5, 0.5),
+    (0.5, 0.5, 0.5),
+    (-0.5, 0.5, 0.5),
+];
+
+/// Cube triangles as corner indices, wound counter-clockwise from outside.
+const TRIANGLES: [(usize, usize, usize); 12] = [
+    // Bottom.
+    (0, 2, 1),
+    (0, 3, 2),
+    // Top.
+    (4, 5, 6),
+    (4, 6, 7),
+    // Front.
+    (0, 1, 5),
+    (0, 5, 4),
+    // Back.
+    (3, 6, 2),
+    (3, 7, 6),
+    // Left.
+    (0, 4, 7),
+    (0, 7, 3),
+    // Right.
+    (1, 2, 6),
+    (1, 6, 5),
+];
+
+impl Grid {
+    /// Exports obstacles as a Wavefront OBJ mesh with one cube per cell,
+    /// centered on the cell coordinates. When `surface_only` is set, cells
+    /// hidden inside of obstacles are skipped to keep the mesh light.
+    pub fn export_obj<P: AsRef<Path>>(&self, path: P, surface_only: bool) -> Result<(), Error> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+
+        let mut writer = BufWriter::new(file);
+        self.write_obj(&mut writer, surface_only)?;
+        writer.flush()
+    }
+
+    /// Writes the OBJ mesh `export_obj` produces into the writer.
+    pub fn write_obj<W: Write>(&self, writer: &mut W, surface_only: bool) -> Result<(), Error> {
+        let cells: Vec<Cell> = if surface_only {
+            self.surface_obstacles(Connectivity::Six).collect()
+        } else {
+            self.obstacles().collect()
+        };
+
+        for cell in &cells {
+            for &(dx, dy, dz) in CORNERS.iter() {
+                writeln!(
+                    writer,
+                    "v {} {} {}",
+                    cell.x as f32 + dx,
+                    cell.y as f32 + dy,
+                    cell.z as f32 + dz
+                )?;
+            }
+        }
+
+        for cube in 0..cells.len() {
+            // OBJ vertex indices start at one.
+            let offset = cube * CORNERS.len() + 1;
+
+            for &(a, b, c) in TRIANGLES.iter() {
+                writeln!(writer, "f {} {} {}", offset + a, offset + b, offset + c)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid::Grid;
+    use tempfile::NamedTempFile;
+
+    fn count_lines(obj: &str, prefix: &str) -> usize {
+        obj.lines().filter(|line| line.starts_with(prefix)).count()
+    }
+
+    #[test]
+    fn test_export_obj_single_obstacle() {
+        let temp_file = NamedTempFile::new().unwrap();
+
+        let mut grid = Grid::new(3, 3);
+        grid.set_obstacle(1, 1, 1);
+        grid.export_obj(temp_file.path(), false).unwrap();
+
+        let obj = std::fs::read_to_string(temp_file.path()).unwrap();
+
+        assert_eq!(count_lines(&obj, "v "), 8);
+        assert_eq!(count_lines(&obj, "f "), 12);
+        assert!(obj.contains("v 0.5 0.5 0.5"));
+        assert!(obj.contains("v 1.5 1.5 1.5"));
+    }
+
+    #[test]
+    fn test_write_obj_surface_only() {
+        let mut grid = Grid::new(3, 3);
+
+        for z in 0..3 {
+            for y in 0..3 {
+                for x in 0..3 {
+                    grid.set_obstacle(x, y, z);
+                }
+            }
+        }
+
+        let mut all = Vec::new();
+        grid.write_obj(&mut all, false).unwrap();
+
+        let mut surface = Vec::new();
+        grid.write_obj(&mut surface, true).unwrap();
+
+        let all = String::from_utf8(all).unwrap();
+        let surface = String::from_utf8(surface).unwrap();
+
+        // Only the center cell is hidden.
+        assert_eq!(count_lines(&all, "f "), 27 * 12);
+        assert_eq!(count_lines(&surface, "f "), 26 * 12);
+        assert_eq!(count_lines(&surface, "v "), 26 * 8);
+    }
+}
@@ -0,0 +1,258 @@
+use crate::provider::GridProvider;
+use nalgebra::Vector3;
+
+enum Node {
+    /// Uniform region, every cell of it is an obstacle or none is.
+    Leaf(bool),
+    /// Octants ordered by x, then y, then z bit of the cell within the node.
+    Branch(Box<[Node; 8]>),
+}
+
+impl Node {
+    /// Sets the cell at the coordinates relative to the node of the given size,
+    /// returns whether it changed.
+    fn set(&mut self, x: u32, y: u32, z: u32, size: u32, obstacle: bool) -> bool {
+        if let Node::Leaf(value) = *self {
+            if value == obstacle {
+                return false;
+            }
+
+            if size == 1 {
+                *self = Node::Leaf(obstacle);
+                return true;
+            }
+
+            *self = Node::Branch(Box::new(std::array::from_fn(|_| Node::Leaf(value))));
+        }
+
+        let half = size / 2;
+
+        let children = match self {
+            Node::Branch(children) => children,
+            Node::Leaf(_) => unreachable!(),
+        };
+
+        let changed =
+            children[octant(x, y, z, half)].set(x % half, y % half, z % half, half, obstacle);
+
+        // Collapse the octants back once all of them are the same.
+        if let Node::Leaf(first) = children[0] {
+            if children
+                .iter()
+                .all(|child| matches!(child, Node::Leaf(value) if *value == first))
+            {
+                *self = Node::Leaf(first);
+            }
+        }
+
+        changed
+    }
+
+    fn count(&self) -> usize {
+        match self {
+            Node::Leaf(_) => 1,
+            Node::Branch(children) => 1 + children.iter().map(Node::count).sum::<usize>(),
+        }
+    }
+}
+
+fn octant(x: u32, y: u32, z: u32, half: u32) -> usize {
+    (x >= half) as usize | ((y >= half) as usize) << 1 | ((z >= half) as usize) << 2
+}
+
+/// Obstacles of a cube with a power of two side, in an octree which collapses
+/// uniform regions into single nodes. Suits huge and mostly empty worlds.
+/// Cells outside of the cube are open.
+pub struct OctreeGridProvider {
+    min: (i32, i32, i32),
+    size: u32,
+    root: Node,
+}
+
+impl OctreeGridProvider {
+    /// Empty cube with the minimum corner cell `min` and the side of `2 ^ depth` cells.
+    pub fn new(min: (i32, i32, i32), depth: u32) -> Self {
+        assert!(depth < 32, "Depth {} is too large", depth);
+
+        OctreeGridProvider {
+            min,
+            size: 1 << depth,
+            root: Node::Leaf(false),
+        }
+    }
+
+    /// Side of the cube in cells.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Marks the cell as an obstacle, returns whether it was open before.
+    ///
+    /// Panics if the cell is outside of the cube.
+    pub fn set_obstacle(&mut self, x: i32, y: i32, z: i32) -> bool {
+        self.set(x, y, z, true)
+    }
+
+    /// Clears the obstacle, returns whether the cell was an obstacle.
+    ///
+    /// Panics if the cell is outside of the cube.
+    pub fn remove_obstacle(&mut self, x: i32, y: i32, z: i32) -> bool {
+        self.set(x, y, z, false)
+    }
+
+    /// Returns the number of nodes, leaves and branches.
+    pub fn node_count(&self) -> usize {
+        self.root.count()
+    }
+
+    fn set(&mut self, x: i32, y: i32, z: i32, obstacle: bool) -> bool {
+        let (local_x, local_y, local_z) = self.local(x, y, z).unwrap_or_else(|| {
+            panic!("Cell x: {}, y: {}, z: {} is outside of the octree", x, y, z)
+        });
+
+        self.root
+            .set(local_x, local_y, local_z, self.size, obstacle)
+    }
+
+    /// Coordinates relative to the minimum corner, `None` outside of the cube.
+    fn local(&self, x: i32, y: i32, z: i32) -> Option<(u32, u32, u32)> {
+        let axis = |value: i32, min: i32| {
+            let local = value as i64 - min as i64;
+
+            if local >= 0 && local < self.size as i64 {
+                Some(local as u32)
+            } else {
+                None
+            }
+        };
+
+        Some((
+            axis(x, self.min.0)?,
+            axis(y, self.min.1)?,
+            axis(z, self.min.2)?,
+        ))
+    }
+}
+
+impl GridProvider for OctreeGridProvider {
+    fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+        if !(vector.x.is_finite() && vector.y.is_finite() && vector.z.is_finite()) {
+            return false;
+        }
+
+        let cell = vector.map(|axis| axis.round() as i32);
+        self.is_obstacle_cell(cell.x, cell.y, cell.z)
+    }
+
+    fn is_obstacle_cell(&self, x: i32, y: i32, z: i32) -> bool {
+        let (mut x, mut y, mut z) = match self.local(x, y, z) {
+            Some(local) => local,
+            None => return false,
+        };
+
+        let mut node = &self.root;
+        let mut half = self.size / 2;
+
+        loop {
+            match node {
+                Node::Leaf(obstacle) => return *obstacle,
+                Node::Branch(children) => {
+                    node = &children[octant(x, y, z, half)];
+
+                    x %= half;
+                    y %= half;
+                    z %= half;
+                    half /= 2;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid::Grid;
+    use crate::octree::OctreeGridProvider;
+    use crate::provider::{GridProvider, SingleGridProvider};
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_octree_matches_dense() {
+        let mut grid = Grid::new(16, 16);
+        let mut octree = OctreeGridProvider::new((-8, -8, -8), 4);
+
+        // Scattered cells plus a full 4 x 4 x 4 block which should collapse.
+        let mut cells: Vec<(u32, u32, u32)> = (0..40u32)
+            .map(|i| ((i * 7) % 16, (i * 11 + 3) % 16, (i * 5 + 1) % 16))
+            .collect();
+
+        for z in 8..12 {
+            for y in 4..8 {
+                for x in 12..16 {
+                    cells.push((x, y, z));
+                }
+            }
+        }
+
+        for (x, y, z) in cells {
+            grid.set_obstacle(x, y, z);
+            octree.set_obstacle(x as i32 - 8, y as i32 - 8, z as i32 - 8);
+        }
+
+        let dense = SingleGridProvider::new(grid);
+
+        let check = |octree: &OctreeGridProvider| {
+            for z in -1..17 {
+                for y in -1..17 {
+                    for x in -1..17 {
+                        assert_eq!(
+                            octree.is_obstacle_cell(x - 8, y - 8, z - 8),
+                            dense.is_obstacle_cell(x, y, z),
+                            "Providers differ at x: {}, y: {}, z: {}",
+                            x,
+                            y,
+                            z
+                        );
+                    }
+                }
+            }
+        };
+
+        check(&octree);
+        assert!(octree.is_obstacle(Vector3::new(4.2, -3.7, 0.4)));
+
+        // Setting a cell twice changes nothing, removing and adding it back
+        // restores the same tree.
+        let nodes = octree.node_count();
+        assert!(!octree.set_obstacle(4, -4, 0));
+        assert!(octree.remove_obstacle(4, -4, 0));
+        assert!(octree.set_obstacle(4, -4, 0));
+        assert_eq!(octree.node_count(), nodes);
+
+        check(&octree);
+    }
+
+    #[test]
+    fn test_octree_collapse() {
+        let mut octree = OctreeGridProvider::new((0, 0, 0), 10);
+        assert_eq!(octree.node_count(), 1);
+
+        octree.set_obstacle(513, 2, 1000);
+        assert_eq!(octree.node_count(), 1 + 8 * 10);
+
+        octree.remove_obstacle(513, 2, 1000);
+        assert_eq!(octree.node_count(), 1);
+
+        for z in 0..2 {
+            for y in 0..2 {
+                for x in 0..2 {
+                    octree.set_obstacle(x, y, z);
+                }
+            }
+        }
+
+        // A full octant is a single leaf.
+        assert_eq!(octree.node_count(), 1 + 8 * 9);
+        assert!(!octree.is_obstacle_cell(-1, 0, 0));
+    }
+}
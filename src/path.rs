@@ -0,0 +1,427 @@
+use crate::provider::GridProvider;
+use nalgebra::Vector3;
+
+/// Resamples the path at uniform arc-length intervals.
+/// The first and the last points are always preserved.
+pub fn resample_path(path: &[Vector3<f32>], spacing: f32) -> Vec<Vector3<f32>> {
+    assert!(spacing > 0.0, "Spacing {} must be positive", spacing);
+
+    if path.len() < 2 {
+        return path.to_vec();
+    }
+
+    let first = path[0];
+    let last = path[path.len() - 1];
+
+    let mut resampled = vec![first];
+
+    // Distance left to walk until the next sample.
+    let mut remaining = spacing;
+
+    for segment in path.windows(2) {
+        let (from, to) = (segment[0], segment[1]);
+        let length = (to - from).magnitude();
+        let mut walked = 0.0;
+
+        while length - walked >= remaining {
+            walked += remaining;
+            resampled.push(from + (to - from) * (walked / length));
+            remaining = spacing;
+        }
+
+        remaining -= length - walked;
+    }
+
+    // The last sample may land on the end point within the float error.
+    let sampled = resampled.len();
+    let end = &mut resampled[sampled - 1];
+
+    if sampled > 1 && (*end - last).magnitude() < spacing * 1e-3 {
+        *end = last;
+    } else {
+        resampled.push(last);
+    }
+
+    resampled
+}
+
+/// Keeps only the points where the path changes direction.
+/// The first and the last points are always preserved.
+pub fn compress_collinear(path: &[Vector3<f32>]) -> Vec<Vector3<f32>> {
+    if path.len() < 3 {
+        return path.to_vec();
+    }
+
+    let mut compressed = vec![path[0]];
+
+    for window in path.windows(3) {
+        let incoming = (window[1] - window[0]).normalize();
+        let outgoing = (window[2] - window[1]).normalize();
+
+        if (incoming - outgoing).magnitude() > 1e-6 {
+            compressed.push(window[1]);
+        }
+    }
+
+    compressed.push(path[path.len() - 1]);
+    compressed
+}
+
+/// Returns all lattice cells the segment passes through, including
+/// cells it only touches at an edge or a corner.
+/// Cell of the integer vector covers half a unit around it on each axis.
+pub fn supercover(from: Vector3<f32>, to: Vector3<f32>) -> Vec<Vector3<f32>> {
+    let mut cells = Vec::new();
+
+    let mut cell = from.map(|axis| axis.round());
+    let end_cell = to.map(|axis| axis.round());
+    let direction = to - from;
+
+    let mut step = Vector3::zeros();
+    let mut t_max = Vector3::repeat(f32::INFINITY);
+    let mut t_delta = Vector3::repeat(f32::INFINITY);
+
+    for axis in 0..3 {
+        if direction[axis] > 0.0 {
+            step[axis] = 1.0;
+            t_max[axis] = (cell[axis] + 0.5 - from[axis]) / direction[axis];
+            t_delta[axis] = 1.0 / direction[axis];
+        } else if direction[axis] < 0.0 {
+            step[axis] = -1.0;
+            t_max[axis] = (cell[axis] - 0.5 - from[axis]) / direction[axis];
+            t_delta[axis] = -1.0 / direction[axis];
+        }
+    }
+
+    cells.push(cell);
+
+    while cell != end_cell {
+        let t = t_max.min();
+
+        if t > 1.0 {
+            break;
+        }
+
+        // Axes crossed at the same time, the segment passes an edge or a corner.
+        let crossed: Vec<usize> = (0..3).filter(|&axis| t_max[axis] - t < 1e-5).collect();
+
+        if crossed.len() > 1 {
+            for mask in 1..(1 << crossed.len()) - 1 {
+                let mut touched = cell;
+
+                for (bit, &axis) in crossed.iter().enumerate() {
+                    if mask & (1 << bit) != 0 {
+                        touched[axis] += step[axis];
+                    }
+                }
+
+                cells.push(touched);
+            }
+        }
+
+        for &axis in &crossed {
+            cell[axis] += step[axis];
+            t_max[axis] += t_delta[axis];
+        }
+
+        cells.push(cell);
+    }
+
+    cells
+}
+
+/// Returns whether no cell the segment passes through is an obstacle.
+pub fn line_of_sight<G: GridProvider>(
+    from: Vector3<f32>,
+    to: Vector3<f32>,
+    grid_provider: &G,
+) -> bool {
+    supercover(from, to)
+        .into_iter()
+        .all(|cell| !grid_provider.is_obstacle(cell))
+}
+
+/// Returns whether every segment of the path keeps line of sight,
+/// so the whole polyline can be flown without clipping an obstacle.
+pub fn validate_path<G: GridProvider>(path: &[Vector3<f32>], grid_provider: &G) -> bool {
+    match path {
+        [point] => !grid_provider.is_obstacle(*point),
+        _ => path
+            .windows(2)
+            .all(|segment| line_of_sight(segment[0], segment[1], grid_provider)),
+    }
+}
+
+/// Merges segments shorter than `min_length` by dropping waypoints,
+/// as long as the merged segment keeps line of sight.
+/// A waypoint which can't be dropped without clipping an obstacle is kept.
+pub fn enforce_min_segment<G: GridProvider>(
+    path: &[Vector3<f32>],
+    min_length: f32,
+    grid_provider: &G,
+) -> Vec<Vector3<f32>> {
+    if path.len() < 3 {
+        return path.to_vec();
+    }
+
+    let mut merged = vec![path[0]];
+
+    for i in 1..path.len() - 1 {
+        let previous = merged[merged.len() - 1];
+        let next = path[i + 1];
+
+        let is_short = (path[i] - previous).magnitude() < min_length
+            || (next - path[i]).magnitude() < min_length;
+
+        if !(is_short && line_of_sight(previous, next, grid_provider)) {
+            merged.push(path[i]);
+        }
+    }
+
+    merged.push(path[path.len() - 1]);
+    merged
+}
+
+/// Ramer-Douglas-Peucker simplification which also keeps the path clear of obstacles.
+/// A run of waypoints is replaced by a single segment only if none of them
+/// is further than `epsilon` from it and the segment keeps line of sight.
+/// Larger `epsilon` gives fewer waypoints. The first and the last points are always preserved.
+pub fn simplify_path<G: GridProvider>(
+    path: &[Vector3<f32>],
+    epsilon: f32,
+    grid_provider: &G,
+) -> Vec<Vector3<f32>> {
+    if path.len() < 3 {
+        return path.to_vec();
+    }
+
+    let mut keep = vec![false; path.len()];
+    keep[0] = true;
+    keep[path.len() - 1] = true;
+
+    let mut ranges = vec![(0, path.len() - 1)];
+
+    while let Some((first, last)) = ranges.pop() {
+        if last - first < 2 {
+            continue;
+        }
+
+        let (farthest, deviation) = (first + 1..last)
+            .map(|i| (i, segment_distance(path[i], path[first], path[last])))
+            .fold(
+                (first + 1, -1.0),
+                |max, point| {
+                    if point.1 > max.1 {
+                        point
+                    } else {
+                        max
+                    }
+                },
+            );
+
+        if deviation <= epsilon && line_of_sight(path[first], path[last], grid_provider) {
+            continue;
+        }
+
+        keep[farthest] = true;
+        ranges.push((first, farthest));
+        ranges.push((farthest, last));
+    }
+
+    path.iter()
+        .zip(keep)
+        .filter(|(_, keep)| *keep)
+        .map(|(point, _)| *point)
+        .collect()
+}
+
+/// Distance from the point to the closest point of the segment.
+fn segment_distance(point: Vector3<f32>, from: Vector3<f32>, to: Vector3<f32>) -> f32 {
+    let segment = to - from;
+    let length_squared = segment.magnitude_squared();
+
+    if length_squared == 0.0 {
+        return (point - from).magnitude();
+    }
+
+    let t = ((point - from).dot(&segment) / length_squared).clamp(0.0, 1.0);
+    (point - (from + segment * t)).magnitude()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::path::{
+        compress_collinear, enforce_min_segment, resample_path, simplify_path, supercover,
+    };
+    use crate::provider::FnProvider;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_resample_straight_path() {
+        let path = vec![Vector3::new(0.0, 0.0, 0.0), Vector3::new(10.0, 0.0, 0.0)];
+
+        let resampled = resample_path(&path, 2.0);
+        assert_eq!(resampled.len(), 6);
+
+        for (i, vector) in resampled.iter().enumerate() {
+            assert_eq!(*vector, Vector3::new(2.0 * i as f32, 0.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn test_resample_corner_path() {
+        let path = vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(3.0, 0.0, 0.0),
+            Vector3::new(3.0, 4.0, 0.0),
+        ];
+
+        let resampled = resample_path(&path, 2.0);
+
+        assert_eq!(
+            resampled,
+            vec![
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(2.0, 0.0, 0.0),
+                Vector3::new(3.0, 1.0, 0.0),
+                Vector3::new(3.0, 3.0, 0.0),
+                Vector3::new(3.0, 4.0, 0.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compress_straight_path() {
+        let path: Vec<_> = (0..10).map(|x| Vector3::new(x as f32, 0.0, 0.0)).collect();
+
+        assert_eq!(
+            compress_collinear(&path),
+            vec![Vector3::new(0.0, 0.0, 0.0), Vector3::new(9.0, 0.0, 0.0)]
+        );
+    }
+
+    #[test]
+    fn test_compress_corner_path() {
+        let path = vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(3.0, 1.0, 0.0),
+            Vector3::new(4.0, 2.0, 0.0),
+            Vector3::new(4.0, 2.0, 1.0),
+        ];
+
+        assert_eq!(
+            compress_collinear(&path),
+            vec![
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(2.0, 0.0, 0.0),
+                Vector3::new(4.0, 2.0, 0.0),
+                Vector3::new(4.0, 2.0, 1.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_supercover() {
+        let cells = supercover(Vector3::new(0.0, 0.0, 0.0), Vector3::new(3.0, 0.0, 0.0));
+        assert_eq!(cells.len(), 4);
+
+        let cells = supercover(Vector3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 1.0, 0.0));
+
+        assert_eq!(
+            cells,
+            vec![
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(0.0, 1.0, 0.0),
+                Vector3::new(1.0, 1.0, 0.0),
+            ]
+        );
+
+        let cells = supercover(Vector3::new(0.0, 0.0, 0.0), Vector3::new(-4.0, 2.0, 1.0));
+        assert_eq!(cells.first(), Some(&Vector3::new(0.0, 0.0, 0.0)));
+        assert_eq!(cells.last(), Some(&Vector3::new(-4.0, 2.0, 1.0)));
+    }
+
+    #[test]
+    fn test_enforce_min_segment() {
+        let path = vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(0.3, 0.0, 0.0),
+            Vector3::new(0.6, 0.0, 0.0),
+            Vector3::new(5.0, 0.0, 0.0),
+        ];
+
+        let grid_provider = FnProvider::new(|_| false);
+
+        assert_eq!(
+            enforce_min_segment(&path, 1.0, &grid_provider),
+            vec![Vector3::new(0.0, 0.0, 0.0), Vector3::new(5.0, 0.0, 0.0)]
+        );
+    }
+
+    #[test]
+    fn test_enforce_min_segment_corner() {
+        let path = vec![
+            Vector3::new(0.0, 4.0, 0.0),
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(4.0, 0.0, 0.0),
+        ];
+
+        let grid_provider = FnProvider::new(|_| false);
+        assert_eq!(enforce_min_segment(&path, 5.0, &grid_provider).len(), 2);
+
+        let grid_provider = FnProvider::new(|vector| vector.x >= 1.0 && vector.y >= 1.0);
+        assert_eq!(enforce_min_segment(&path, 5.0, &grid_provider), path);
+    }
+
+    #[test]
+    fn test_simplify_path() {
+        // Zigzag along x with the amplitude growing every period.
+        let path: Vec<_> = (0..40)
+            .map(|x| {
+                let amplitude = (x / 4) as f32 * 0.5;
+                let y = if x % 2 == 0 { amplitude } else { -amplitude };
+
+                Vector3::new(x as f32, y, 0.0)
+            })
+            .collect();
+
+        let grid_provider = FnProvider::new(|_| false);
+        let mut previous = path.len();
+
+        for epsilon in &[0.0, 0.5, 1.5, 2.5, 3.5, 100.0] {
+            let simplified = simplify_path(&path, *epsilon, &grid_provider);
+
+            assert!(simplified.len() <= previous, "Epsilon {}", epsilon);
+            assert_eq!(simplified.first(), path.first());
+            assert_eq!(simplified.last(), path.last());
+
+            previous = simplified.len();
+        }
+
+        // Only the flat start, where the amplitude is still zero, is dropped exactly.
+        assert_eq!(
+            simplify_path(&path, 0.0, &grid_provider).len(),
+            path.len() - 2
+        );
+        assert_eq!(previous, 2);
+    }
+
+    #[test]
+    fn test_simplify_path_obstacle() {
+        let path = vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(2.0, 1.0, 0.0),
+            Vector3::new(4.0, 0.0, 0.0),
+        ];
+
+        let grid_provider = FnProvider::new(|_| false);
+        assert_eq!(simplify_path(&path, 1.5, &grid_provider).len(), 2);
+
+        // Close enough to drop, but the shortcut would cross the obstacle.
+        let grid_provider = FnProvider::new(|vector| vector == Vector3::new(2.0, 0.0, 0.0));
+        assert_eq!(simplify_path(&path, 1.5, &grid_provider), path);
+    }
+}
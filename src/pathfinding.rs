@@ -1,262 +1,2838 @@
-use crate::provider::GridProvider;
+use crate::path::line_of_sight;
+use crate::provider::{GridProvider, GridProvider64};
+use crate::transform::Wrapping;
 use indexmap::IndexSet;
 use nalgebra::Vector3;
 use ordered_float::NotNan;
-use std::cmp::Ordering;
-use std::collections::BinaryHeap;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
 use std::fmt::Debug;
 use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+/// Heuristic weights `find_path_anytime` searches with, one after another.
+const ANYTIME_WEIGHTS: [f32; 6] = [5.0, 3.0, 2.0, 1.5, 1.2, 1.0];
+
+/// Tolerance for comparing accumulated costs against the seeded bound.
+const BOUND_EPSILON: f32 = 1e-3;
+
+/// Tolerance under which costs summed in a different order count as equal.
+const TIE_EPSILON: f32 = 1e-4;
 
 #[derive(Debug, Clone)]
 struct Node {
     vector: Vector3<f32>,
+    /// Direction of the move into this node.
+    /// Zero when directions aren't tracked or for the start node.
+    direction: Vector3<f32>,
     cost: f32,
+    /// Distance estimate to the goal, inflated by the heuristic weight.
     estimated_cost: f32,
+    /// Turns taken so far, only counted for `SearchOptions::minimize_turns`.
+    turns: u32,
+    /// Lower bound of the turns left to the goal, zero unless counting turns.
+    estimated_turns: u32,
+    /// Turns taken so far, only counted for `SearchOptions::prefer_straight`
+    /// to break ties between equally cheap nodes.
+    bends: u32,
     previous_node_index: usize,
 }
 
+/// Neighbor offsets with z changing fastest.
+const Z_INNERMOST_OFFSETS: [(i32, i32, i32); 26] = [
+    (-1, -1, -1),
+    (-1, -1, 0),
+    (-1, -1, 1),
+    (-1, 0, -1),
+    (-1, 0, 0),
+    (-1, 0, 1),
+    (-1, 1, -1),
+    (-1, 1, 0),
+    (-1, 1, 1),
+    (0, -1, -1),
+    (0, -1, 0),
+    (0, -1, 1),
+    (0, 0, -1),
+    (0, 0, 1),
+    (0, 1, -1),
+    (0, 1, 0),
+    (0, 1, 1),
+    (1, -1, -1),
+    (1, -1, 0),
+    (1, -1, 1),
+    (1, 0, -1),
+    (1, 0, 0),
+    (1, 0, 1),
+    (1, 1, -1),
+    (1, 1, 0),
+    (1, 1, 1),
+];
+
+/// Neighbor offsets with x changing fastest, the memory order of `Grid`.
+const X_INNERMOST_OFFSETS: [(i32, i32, i32); 26] = [
+    (-1, -1, -1),
+    (0, -1, -1),
+    (1, -1, -1),
+    (-1, 0, -1),
+    (0, 0, -1),
+    (1, 0, -1),
+    (-1, 1, -1),
+    (0, 1, -1),
+    (1, 1, -1),
+    (-1, -1, 0),
+    (0, -1, 0),
+    (1, -1, 0),
+    (-1, 0, 0),
+    (1, 0, 0),
+    (-1, 1, 0),
+    (0, 1, 0),
+    (1, 1, 0),
+    (-1, -1, 1),
+    (0, -1, 1),
+    (1, -1, 1),
+    (-1, 0, 1),
+    (0, 0, 1),
+    (1, 0, 1),
+    (-1, 1, 1),
+    (0, 1, 1),
+    (1, 1, 1),
+];
+
+/// Neighbor offsets sorted by the Morton code of the 3x3x3 block, x in the lowest bit.
+const MORTON_OFFSETS: [(i32, i32, i32); 26] = [
+    (-1, -1, -1),
+    (0, -1, -1),
+    (-1, 0, -1),
+    (0, 0, -1),
+    (-1, -1, 0),
+    (0, -1, 0),
+    (-1, 0, 0),
+    (1, -1, -1),
+    (1, 0, -1),
+    (1, -1, 0),
+    (1, 0, 0),
+    (-1, 1, -1),
+    (0, 1, -1),
+    (-1, 1, 0),
+    (0, 1, 0),
+    (1, 1, -1),
+    (1, 1, 0),
+    (-1, -1, 1),
+    (0, -1, 1),
+    (-1, 0, 1),
+    (0, 0, 1),
+    (1, -1, 1),
+    (1, 0, 1),
+    (-1, 1, 1),
+    (0, 1, 1),
+    (1, 1, 1),
+];
+
+/// Order in which the neighbors of a node are queried from the provider.
+/// It doesn't change the path cost, only which of equally cheap paths is found
+/// and how well successive queries hit nearby memory of the provider.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NeighborOrder {
+    /// Nested x, y, z loops with z innermost.
+    #[default]
+    ZInnermost,
+    /// Nested z, y, x loops with x innermost, which follows the memory layout of `Grid`.
+    XInnermost,
+    /// Morton (Z-order) traversal of the neighborhood.
+    Morton,
+}
+
+impl NeighborOrder {
+    fn offsets(self) -> &'static [(i32, i32, i32); 26] {
+        match self {
+            NeighborOrder::ZInnermost => &Z_INNERMOST_OFFSETS,
+            NeighborOrder::XInnermost => &X_INNERMOST_OFFSETS,
+            NeighborOrder::Morton => &MORTON_OFFSETS,
+        }
+    }
+}
+
+/// Move the search may take from any node in place of the built-in 26 neighbors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Motion {
+    /// Integer offset to the destination cell. Only the destination is checked
+    /// against obstacles, cells jumped over are not. Has to be finite.
+    pub offset: Vector3<f32>,
+    /// Cost of the move, the provider extra cost of the destination is added to it.
+    pub cost: f32,
+}
+
+impl Motion {
+    pub fn new(offset: Vector3<f32>, cost: f32) -> Self {
+        Motion { offset, cost }
+    }
+
+    /// Motion costing its euclidean length, like the built-in moves.
+    pub fn euclidean(offset: Vector3<f32>) -> Self {
+        Motion::new(offset, offset.magnitude())
+    }
+}
+
+/// Costs per unit of altitude gained and lost, added to the length of every move.
+/// A negative descent cost is a discount which makes descending cheaper than
+/// level flight, e.g. for gliders trading altitude for distance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VerticalCosts {
+    pub climb: f32,
+    /// Has to be greater than -1, so every move keeps a positive cost.
+    pub descent: f32,
+}
+
+impl VerticalCosts {
+    pub fn new(climb: f32, descent: f32) -> Self {
+        VerticalCosts { climb, descent }
+    }
+
+    /// Cost of changing the altitude by the signed offset.
+    fn cost(&self, dz: f32) -> f32 {
+        if dz > 0.0 {
+            self.climb * dz
+        } else {
+            -self.descent * dz
+        }
+    }
+}
+
+/// Additional constraints for the search.
+#[derive(Debug, Clone, Default)]
+pub struct SearchOptions {
+    /// Maximum angle in radians between two consecutive moves.
+    /// When set, the search state includes the arrival direction
+    /// so sharp turns are replaced by wider arcs.
+    pub max_turn_angle: Option<f32>,
+    /// Inclusive axis-aligned box (min, max) the search never leaves.
+    /// Guarantees termination even if the provider is unbounded.
+    /// `GridProvider::bounds` is respected on top of it.
+    pub bounds: Option<(Vector3<f32>, Vector3<f32>)>,
+    /// Cost added to every move which changes the direction
+    /// of the previous move, nudging the search toward straighter routes.
+    pub turn_penalty: f32,
+    /// Paths costing more are not searched for. Once the cheapest node left
+    /// can't reach the goal within it the search gives up with `None`.
+    /// With a heuristic weight the search runs until the heap is empty
+    /// and reopens explored nodes, so it finds a path within it if there is one.
+    pub max_path_cost: Option<f32>,
+    /// Whether the path begins with the snapped start. By default the path
+    /// begins with the first move, like `find_path` returns it.
+    pub include_start: bool,
+    /// Whether obstacle answers are memoized per cell for the search, so an
+    /// expensive provider is asked about every cell at most once.
+    pub cache_obstacles: bool,
+    /// Order in which neighbors are queried from the provider.
+    pub neighbor_order: NeighborOrder,
+    /// Motions replacing the built-in 26 neighbors, see `find_path_with_motions`.
+    pub motions: Option<Vec<Motion>>,
+    /// Factor of at least one the distance estimate is inflated by. The search
+    /// expands fewer nodes and the path costs at most this many times the cheapest one.
+    pub heuristic_weight: Option<f32>,
+    /// Asymmetric costs of climbing and descending, see `VerticalCosts`.
+    pub vertical_costs: Option<VerticalCosts>,
+    /// Wraparound world, neighbors across the seam are adjacent and the path
+    /// stays within the world, so it jumps from one edge to the other at the seam.
+    /// The world size should be whole cells to keep wrapped vectors on the lattice.
+    pub wrapping: Option<Wrapping>,
+    /// When the start or the goal rounds into an obstacle, they snap to the nearest
+    /// open neighbor cell within this distance instead, see `snap_to_open`.
+    pub snap_tolerance: Option<f32>,
+    /// Whether the altitude only changes by pure vertical moves, so moves
+    /// combining a vertical and a horizontal component are never taken.
+    pub vertical_moves_axis_only: bool,
+    /// Whether the number of turns is minimized before the cost, so the path
+    /// is the cheapest among those with the fewest changes of direction.
+    /// `max_path_cost` still limits the cost. Straight runs away from the goal
+    /// may look as promising as any path with more turns, so the search only
+    /// ends reliably when bounded by `bounds` or the provider bounds.
+    pub minimize_turns: bool,
+    /// Whether ties between equally cheap ways into a cell go to the one with
+    /// fewer turns, so open areas give a straight run and a diagonal run instead
+    /// of a staircase. The path cost stays the same.
+    pub prefer_straight: bool,
+}
+
+impl SearchOptions {
+    /// Weight of the distance estimate, one for an optimal search.
+    fn weight(&self) -> f32 {
+        self.heuristic_weight.unwrap_or(1.0)
+    }
+
+    /// Factor keeping the euclidean distance a lower bound of the cost.
+    /// A move descends by at most its length, so with a descent discount
+    /// it costs at least its length scaled by one plus the discount.
+    fn distance_scale(&self) -> f32 {
+        match &self.vertical_costs {
+            Some(vertical_costs) => {
+                assert!(
+                    vertical_costs.descent > -1.0,
+                    "Descent cost {} must be greater than -1",
+                    vertical_costs.descent
+                );
+
+                1.0 + vertical_costs.descent.min(0.0)
+            }
+            None => 1.0,
+        }
+    }
+
+    /// Shortest offset between the vectors, across the seam of a wraparound world.
+    fn offset(&self, from: Vector3<f32>, to: Vector3<f32>) -> Vector3<f32> {
+        match &self.wrapping {
+            Some(wrapping) => wrapping.offset(from, to),
+            None => to - from,
+        }
+    }
+
+    /// Whether the search state has to include the arrival direction.
+    fn tracks_direction(&self) -> bool {
+        self.max_turn_angle.is_some() || self.turn_penalty != 0.0 || self.minimize_turns
+    }
+}
+
 /// Finds the path from one vector to another.
-/// Returns list of vectors to achieve the goal.
+/// Returns list of vectors to achieve the goal, beginning with the first move
+/// after the start, the start itself is not included. Use
+/// `SearchOptions::include_start` to get it as well.
+///
+/// The search moves over the integer lattice, so start and goal
+/// are snapped to the nearest integer coordinates first.
+/// Returns `None` right away if either of them is inside of an obstacle
+/// or isn't finite.
 pub fn find_path<G: GridProvider>(
     start: Vector3<f32>,
     goal: Vector3<f32>,
     grid_provider: G,
 ) -> Option<Vec<Vector3<f32>>> {
-    let mut reachable = BinaryHeap::new();
-    let mut explored = IndexSet::new();
+    find_path_streaming(start, goal, grid_provider, |_| true)
+}
+
+/// Returns the straight flight `[start, goal]` when no cell along the segment
+/// is an obstacle, `None` otherwise. Much cheaper than `find_path`, so it's
+/// worth trying first for queries which are mostly in open air. Unlike
+/// `find_path` the path begins with the start and the vectors aren't snapped.
+pub fn direct_path<G: GridProvider>(
+    start: Vector3<f32>,
+    goal: Vector3<f32>,
+    grid_provider: G,
+) -> Option<Vec<Vector3<f32>>> {
+    if line_of_sight(start, goal, &grid_provider) {
+        Some(vec![start, goal])
+    } else {
+        None
+    }
+}
+
+/// Cost of the cheapest path from every cell of the inclusive region (min, max)
+/// to the goal, like `find_path_with_cost` would return it, e.g. for agents
+/// which follow the gradient toward or away from the goal. Runs Dijkstra
+/// from the goal, so a single expansion covers the whole region.
+/// Cells which can't reach the goal within the region are absent,
+/// the map is empty when the goal is solid or outside of the region.
+pub fn distance_to_goal_field<G: GridProvider>(
+    goal: Vector3<f32>,
+    grid_provider: G,
+    region: (Vector3<f32>, Vector3<f32>),
+) -> HashMap<(i32, i32, i32), f32> {
+    let goal = snap_to_grid(goal);
+    let (min, max) = region;
+    let mut costs = HashMap::new();
 
-    let start_estimated_distance = (start - &goal).magnitude();
+    if !is_within_bounds(&goal, &min, &max)
+        || grid_provider.is_obstacle_cell(goal.x as i32, goal.y as i32, goal.z as i32)
+    {
+        return costs;
+    }
 
-    reachable.push(Node {
-        vector: start,
-        cost: 0.0,
-        estimated_cost: start_estimated_distance,
-        previous_node_index: 0,
-    });
+    let options = SearchOptions {
+        bounds: Some(region),
+        ..SearchOptions::default()
+    };
 
-    while let Some(current) = reachable.pop() {
-        if current.vector == goal {
-            let mut path = Vec::new();
-            reconstruct_path(&current, &explored, &mut path);
-            path.reverse();
+    let mut reachable = BinaryHeap::new();
+    reachable.push((Reverse(NotNan::new(0.0).unwrap()), cell_key(&goal)));
 
-            return Some(path);
+    while let Some((Reverse(cost), cell)) = reachable.pop() {
+        if costs.contains_key(&cell) {
+            continue;
         }
 
-        let (explored_node_index, _) = explored.insert_full(current.clone());
+        costs.insert(cell, cost.into_inner());
 
-        for (adjacent_vector, cost) in adjacent_vectors(&current.vector, &grid_provider) {
-            let estimated_distance = (adjacent_vector - &goal).magnitude();
+        let vector = Vector3::new(cell.0 as f32, cell.1 as f32, cell.2 as f32);
+        // Paths toward the goal enter this cell, so its extra cost is paid.
+        let extra_cost = grid_provider.extra_cost(vector);
 
-            let mut adjacent_node = Node {
-                vector: adjacent_vector,
-                cost,
-                estimated_cost: estimated_distance,
-                previous_node_index: explored_node_index,
-            };
+        for (adjacent_vector, _) in adjacent_vectors(&vector, &grid_provider, &options, None) {
+            let adjacent_cell = cell_key(&adjacent_vector);
 
-            if explored.contains(&adjacent_node) {
+            if costs.contains_key(&adjacent_cell) {
                 continue;
             }
 
-            let new_cost = current.cost + cost;
+            let adjacent_cost = cost + (vector - adjacent_vector).magnitude() + extra_cost;
+            reachable.push((Reverse(adjacent_cost), adjacent_cell));
+        }
+    }
 
-            if adjacent_node.cost > new_cost {
-                adjacent_node.cost = new_cost;
-            }
+    costs
+}
 
-            reachable.push(adjacent_node);
-        }
+fn cell_key(vector: &Vector3<f32>) -> (i32, i32, i32) {
+    (vector.x as i32, vector.y as i32, vector.z as i32)
+}
+
+/// Finds the path from one vector to another, see `find_path`, moving only
+/// through cells within `tube_radius` of the segment between start and goal.
+/// Bounds the search to the tube, which is much faster over long distances
+/// in mostly open space. Returns `None` if no path fits in the tube,
+/// a wider tube may find one then.
+pub fn find_path_corridor<G: GridProvider>(
+    start: Vector3<f32>,
+    goal: Vector3<f32>,
+    grid_provider: G,
+    tube_radius: f32,
+) -> Option<Vec<Vector3<f32>>> {
+    let corridor_provider = CorridorProvider {
+        inner: grid_provider,
+        start: snap_to_grid(start),
+        goal: snap_to_grid(goal),
+        tube_radius,
+    };
+
+    find_path(start, goal, corridor_provider)
+}
+
+/// Finds the path between `f64` vectors, see `find_path`.
+///
+/// The search runs in `f32` coordinates relative to the snapped start, so cells
+/// stay exact however far the start is from the world origin, as long as the
+/// path stays within about 16 million cells of the start.
+pub fn find_path_f64<G: GridProvider64>(
+    start: Vector3<f64>,
+    goal: Vector3<f64>,
+    grid_provider: G,
+) -> Option<Vec<Vector3<f64>>> {
+    let origin = start.map(|axis| axis.round());
+    let local_goal = (goal - origin).map(|axis| axis as f32);
+
+    let local_provider = LocalProvider {
+        inner: grid_provider,
+        origin,
+    };
+
+    let path = find_path(Vector3::zeros(), local_goal, local_provider)?;
+
+    Some(
+        path.iter()
+            .map(|vector| origin + vector.map(|axis| axis as f64))
+            .collect(),
+    )
+}
+
+/// Finds the path from one vector to another along with the search statistics.
+pub fn find_path_with_stats<G: GridProvider>(
+    start: Vector3<f32>,
+    goal: Vector3<f32>,
+    grid_provider: G,
+) -> (Option<Vec<Vector3<f32>>>, SearchStats) {
+    let mut pathfinder = Pathfinder::new();
+    let path = pathfinder.solve(start, goal, grid_provider);
+
+    (path, pathfinder.stats)
+}
+
+/// Finds the path from one vector to another along with its cost,
+/// the summed move lengths plus the provider extra costs of the entered cells.
+pub fn find_path_with_cost<G: GridProvider>(
+    start: Vector3<f32>,
+    goal: Vector3<f32>,
+    grid_provider: G,
+) -> Option<(Vec<Vector3<f32>>, f32)> {
+    let path = find_path(start, goal, &grid_provider)?;
+
+    let start = snap_to_grid(start);
+    let goal = snap_to_grid(goal);
+
+    // Reaching the start itself takes no move.
+    if start == goal {
+        return Some((path, 0.0));
     }
 
-    None
+    let cost = path_cost(&start, &goal, &path, &grid_provider)?;
+
+    Some((path, cost))
 }
 
-fn adjacent_vectors<G: GridProvider>(
-    vector: &Vector3<f32>,
+/// Finds the path through all waypoints in order.
+/// Like `find_path`, the first waypoint is not included in the path.
+/// Returns `None` if any leg can't be found.
+pub fn find_path_sequence<G: GridProvider>(
+    waypoints: &[Vector3<f32>],
     grid_provider: &G,
-) -> Vec<(Vector3<f32>, f32)> {
-    let mut adjacent = Vec::new();
-
-    for x in -1..=1 {
-        for y in -1..=1 {
-            for z in -1..=1 {
-                if x == 0 && y == 0 && z == 0 {
-                    continue;
-                }
+) -> Option<Vec<Vector3<f32>>> {
+    let mut path: Vec<Vector3<f32>> = Vec::new();
 
-                let adjacent_vector = Vector3::new(x as f32, y as f32, z as f32) + vector;
+    for leg in waypoints.windows(2) {
+        let leg_path = find_path(leg[0], leg[1], grid_provider)?;
 
-                if !grid_provider.is_obstacle(adjacent_vector) {
-                    adjacent.push((adjacent_vector, 1.0))
-                }
+        for vector in leg_path {
+            // Skips junction duplicates, e.g. when a waypoint repeats.
+            if path.last() != Some(&vector) {
+                path.push(vector);
             }
         }
     }
 
-    adjacent
+    Some(path)
 }
 
-fn reconstruct_path(node: &Node, explored: &IndexSet<Node>, path: &mut Vec<Vector3<f32>>) {
-    path.push(node.vector);
+/// Finds the path from one vector to another respecting the search options.
+pub fn find_path_with_options<G: GridProvider>(
+    start: Vector3<f32>,
+    goal: Vector3<f32>,
+    grid_provider: G,
+    options: &SearchOptions,
+) -> Option<Vec<Vector3<f32>>> {
+    Pathfinder::new().solve_with_options(start, goal, grid_provider, options)
+}
 
-    if node.previous_node_index != 0 {
-        let previous_node = explored.get_index(node.previous_node_index).unwrap();
-        reconstruct_path(&previous_node, explored, path);
-    }
+/// Finds the path from one vector to another moving only by the motions,
+/// which replace the built-in 26 neighbors, e.g. for vehicles jumping over cells.
+///
+/// The search estimates the remaining cost by the euclidean distance, so it's
+/// up to the caller to keep it admissible: every motion has to cost at least its
+/// euclidean length, otherwise the path may not be the cheapest one. Debug builds
+/// panic when the search takes such a motion. The goal has to be reachable by the motions from
+/// the start, else the search exhausts everything it can reach; see
+/// `SearchOptions::bounds` to keep that finite.
+pub fn find_path_with_motions<G: GridProvider>(
+    start: Vector3<f32>,
+    goal: Vector3<f32>,
+    grid_provider: G,
+    motions: Vec<Motion>,
+) -> Option<Vec<Vector3<f32>>> {
+    let options = SearchOptions {
+        motions: Some(motions),
+        ..SearchOptions::default()
+    };
+
+    find_path_with_options(start, goal, grid_provider, &options)
 }
 
-impl PartialOrd for Node {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        let total_cost1 = self.cost + self.estimated_cost;
-        let total_cost2 = other.cost + other.estimated_cost;
+/// Path found by `find_path_anytime`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnytimePath {
+    /// Path in the `find_path` format, without the start.
+    pub path: Vec<Vector3<f32>>,
+    pub cost: f32,
+    /// The path costs at most this many times the cheapest path, one when it's optimal.
+    pub suboptimality: f32,
+}
 
-        // This how we convert max binary heap to min binary heap.
-        total_cost2.partial_cmp(&total_cost1)
-    }
+/// Finds the path from one vector to another within the time budget.
+/// Searches repeatedly with a heuristic inflated by weights from 5 down to 1, see
+/// `SearchOptions::heuristic_weight`, so a path is found quickly and then refined,
+/// each search bounded by the cheapest path found before it.
+/// Returns the best path once the budget elapses or the path is proven optimal,
+/// or `None` if no path was found within the budget or there is none.
+///
+/// Every search starts over rather than reusing the previous one, so memory is
+/// bounded by a single search, which the weights keep small early on.
+pub fn find_path_anytime<G: GridProvider>(
+    start: Vector3<f32>,
+    goal: Vector3<f32>,
+    grid_provider: G,
+    time_budget: Duration,
+) -> Option<AnytimePath> {
+    let deadline = Instant::now() + time_budget;
+
+    search_anytime(start, goal, &grid_provider, deadline, |_| {})
 }
 
-impl Ord for Node {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap()
+/// Runs `find_path_anytime`, calling back with the best path after every finished search.
+fn search_anytime<G, F>(
+    start: Vector3<f32>,
+    goal: Vector3<f32>,
+    grid_provider: &G,
+    deadline: Instant,
+    mut on_search: F,
+) -> Option<AnytimePath>
+where
+    G: GridProvider,
+    F: FnMut(&AnytimePath),
+{
+    let start = snap_to_grid(start);
+    let mut pathfinder = Pathfinder::new();
+    let mut best: Option<AnytimePath> = None;
+
+    for &weight in ANYTIME_WEIGHTS.iter() {
+        let options = SearchOptions {
+            heuristic_weight: Some(weight),
+            ..SearchOptions::default()
+        };
+
+        let goal = pathfinder.reset(start, goal, grid_provider, &options);
+        pathfinder.incumbent = best.as_ref().map(|best| (best.cost, best.path.clone()));
+
+        // Out of time, or there is no path at all.
+        let path = match pathfinder.run(&goal, grid_provider, &options, &mut |_| {
+            Instant::now() < deadline
+        }) {
+            Some(path) => path,
+            None => break,
+        };
+
+        // Reaching the start itself takes no move.
+        let cost = if start == goal {
+            0.0
+        } else {
+            path_cost(&start, &goal, &path, grid_provider)?
+        };
+
+        let found = AnytimePath {
+            path,
+            cost,
+            suboptimality: weight,
+        };
+
+        on_search(&found);
+        best = Some(found);
     }
+
+    best
 }
 
-impl Eq for Node {}
+/// Finds the path from one vector to another, reusing a previous path as a hint.
+/// The hint is in the `find_path` format, without the start. When it still leads
+/// from start to goal through open cells, its cost bounds the search: nodes which
+/// can't beat it are discarded and the search stops once the hint is proven optimal.
+/// Otherwise this is a normal search.
+pub fn find_path_seeded<G: GridProvider>(
+    start: Vector3<f32>,
+    goal: Vector3<f32>,
+    grid_provider: G,
+    hint: &[Vector3<f32>],
+) -> Option<Vec<Vector3<f32>>> {
+    Pathfinder::new().solve_seeded(start, goal, grid_provider, hint)
+}
 
-impl PartialEq for Node {
-    fn eq(&self, other: &Self) -> bool {
-        self.vector.eq(&other.vector)
-    }
+/// Finds the path from one vector to another, invoking `on_frontier`
+/// with every node taken off the frontier for expansion.
+/// Returning `false` from the callback aborts the search with `None`.
+pub fn find_path_streaming<G, F>(
+    start: Vector3<f32>,
+    goal: Vector3<f32>,
+    grid_provider: G,
+    on_frontier: F,
+) -> Option<Vec<Vector3<f32>>>
+where
+    G: GridProvider,
+    F: FnMut(&Vector3<f32>) -> bool,
+{
+    Pathfinder::new().search(
+        start,
+        goal,
+        &grid_provider,
+        &SearchOptions::default(),
+        on_frontier,
+    )
 }
 
-impl Hash for Node {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        unsafe {
-            let x_non_nan = NotNan::unchecked_new(self.vector.x);
-            let y_non_nan = NotNan::unchecked_new(self.vector.y);
-            let z_non_nan = NotNan::unchecked_new(self.vector.z);
+/// Counters describing how hard the search worked.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchStats {
+    /// Nodes taken off the reachable heap and expanded.
+    pub expanded: usize,
+    /// Maximum size of the reachable heap.
+    pub max_reachable: usize,
+    /// Nodes pushed again because a cheaper way to them was found.
+    pub reopened: usize,
+    /// Whether the search was stopped by a limit before it finished.
+    pub limit_reached: bool,
+}
 
-            x_non_nan.hash(state);
-            y_non_nan.hash(state);
-            z_non_nan.hash(state);
+/// Reusable pathfinder which keeps its allocations between searches.
+/// Prefer it over `find_path` for many searches in a hot loop.
+#[derive(Default)]
+pub struct Pathfinder {
+    reachable: BinaryHeap<Node>,
+    explored: IndexSet<Node>,
+    /// Lowest (turns, cost, bends) a node was pushed to the reachable heap with.
+    best_cost: HashMap<Node, (u32, f32, u32)>,
+    /// Obstacle answers by cell, filled when `SearchOptions::cache_obstacles` is set.
+    obstacle_cache: HashMap<(i32, i32, i32), bool>,
+    /// Cost and path of a known solution, bounds the search when set.
+    incumbent: Option<(f32, Vec<Vector3<f32>>)>,
+    stats: SearchStats,
+    /// Whether obstacle answers outlive a search, only when the provider is
+    /// always the same, see `BoundPathfinder`.
+    keep_obstacle_cache: bool,
+}
+
+impl Pathfinder {
+    pub fn new() -> Self {
+        Pathfinder::default()
+    }
+
+    /// Creates the pathfinder with room for about `capacity_hint` nodes,
+    /// so large searches don't reallocate while they grow.
+    pub fn with_capacity(capacity_hint: usize) -> Self {
+        Pathfinder {
+            reachable: BinaryHeap::with_capacity(capacity_hint),
+            explored: IndexSet::with_capacity(capacity_hint),
+            best_cost: HashMap::with_capacity(capacity_hint),
+            ..Pathfinder::default()
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::pathfinding::find_path;
-    use crate::provider::GridProvider;
-    use nalgebra::Vector3;
+    /// Returns statistics of the last search.
+    pub fn stats(&self) -> &SearchStats {
+        &self.stats
+    }
 
-    struct SimpleGridProvider {}
+    /// Creates the pathfinder owning the provider, see `BoundPathfinder`.
+    pub fn with_provider<G: GridProvider>(grid_provider: G) -> BoundPathfinder<G> {
+        BoundPathfinder::new(grid_provider)
+    }
 
-    impl GridProvider for SimpleGridProvider {
-        fn is_obstacle(&self, _vector: Vector3<f32>) -> bool {
-            false
-        }
+    /// Finds the path from one vector to another, see `find_path`.
+    pub fn solve<G: GridProvider>(
+        &mut self,
+        start: Vector3<f32>,
+        goal: Vector3<f32>,
+        grid_provider: G,
+    ) -> Option<Vec<Vector3<f32>>> {
+        self.search(
+            start,
+            goal,
+            &grid_provider,
+            &SearchOptions::default(),
+            |_| true,
+        )
     }
 
-    #[test]
-    fn test_simple_straight_path() {
-        let start = Vector3::new(0.0, 0.0, 0.0);
-        let goal = Vector3::new(10.0, 0.0, 0.0);
+    /// Finds the path from one vector to another respecting the search options.
+    pub fn solve_with_options<G: GridProvider>(
+        &mut self,
+        start: Vector3<f32>,
+        goal: Vector3<f32>,
+        grid_provider: G,
+        options: &SearchOptions,
+    ) -> Option<Vec<Vector3<f32>>> {
+        self.search(start, goal, &grid_provider, options, |_| true)
+    }
 
-        let grid_provider = SimpleGridProvider {};
+    /// Finds the path from one vector to another bounded by the hint, see `find_path_seeded`.
+    pub fn solve_seeded<G: GridProvider>(
+        &mut self,
+        start: Vector3<f32>,
+        goal: Vector3<f32>,
+        grid_provider: G,
+        hint: &[Vector3<f32>],
+    ) -> Option<Vec<Vector3<f32>>> {
+        let snapped_start = snap_to_grid(start);
+        let goal = self.reset(start, goal, &grid_provider, &SearchOptions::default());
 
-        let path = find_path(start, goal, grid_provider).unwrap();
-        assert_eq!(path.len(), 10);
+        let hint: Vec<Vector3<f32>> = hint.iter().map(|vector| snap_to_grid(*vector)).collect();
 
-        for i in 1..11 {
-            let vec = path.get(i - 1).unwrap();
+        self.incumbent =
+            path_cost(&snapped_start, &goal, &hint, &grid_provider).map(|cost| (cost, hint));
 
-            assert_eq!(vec.x, 1.0 * i as f32);
-            assert_eq!(vec.y, 0.0);
-            assert_eq!(vec.z, 0.0);
+        self.run(
+            &goal,
+            &grid_provider,
+            &SearchOptions::default(),
+            &mut |_| true,
+        )
+    }
+
+    fn search<G, F>(
+        &mut self,
+        start: Vector3<f32>,
+        goal: Vector3<f32>,
+        grid_provider: &G,
+        options: &SearchOptions,
+        mut on_frontier: F,
+    ) -> Option<Vec<Vector3<f32>>>
+    where
+        G: GridProvider,
+        F: FnMut(&Vector3<f32>) -> bool,
+    {
+        #[cfg(feature = "tracing")]
+        let span = tracing::debug_span!(
+            "find_path",
+            start = ?(start.x, start.y, start.z),
+            goal = ?(goal.x, goal.y, goal.z),
+            expanded = tracing::field::Empty,
+            nodes = tracing::field::Empty,
+            elapsed_us = tracing::field::Empty,
+        )
+        .entered();
+        #[cfg(feature = "tracing")]
+        let started = Instant::now();
+
+        let goal = self.reset(start, goal, grid_provider, options);
+        let path = self.run(&goal, grid_provider, options, &mut on_frontier);
+
+        #[cfg(feature = "tracing")]
+        {
+            span.record("expanded", self.stats.expanded);
+            span.record("nodes", self.explored.len());
+            span.record("elapsed_us", started.elapsed().as_micros() as u64);
+
+            if path.is_none() {
+                tracing::debug!(
+                    expanded = self.stats.expanded,
+                    limit_reached = self.stats.limit_reached,
+                    "No path found"
+                );
+            }
         }
+
+        path
     }
 
-    #[test]
-    fn test_simple_horizontal_path() {
-        let start = Vector3::new(0.0, 0.0, 0.0);
-        let goal = Vector3::new(-10.0, -10.0, 0.0);
+    fn run<G, F>(
+        &mut self,
+        goal: &Vector3<f32>,
+        grid_provider: &G,
+        options: &SearchOptions,
+        on_frontier: &mut F,
+    ) -> Option<Vec<Vector3<f32>>>
+    where
+        G: GridProvider,
+        F: FnMut(&Vector3<f32>) -> bool,
+    {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("main_loop").entered();
 
-        let grid_provider = SimpleGridProvider {};
+        loop {
+            match self.step(goal, grid_provider, options, on_frontier) {
+                SearchState::InProgress => {}
+                SearchState::Found(path) => return Some(path),
+                SearchState::Unreachable => return None,
+            }
+        }
+    }
 
-        let path = find_path(start, goal, grid_provider).unwrap();
-        assert_eq!(path.len(), 10);
+    /// Prepares the state for a new search, returns the snapped goal.
+    fn reset<G: GridProvider>(
+        &mut self,
+        start: Vector3<f32>,
+        goal: Vector3<f32>,
+        grid_provider: &G,
+        options: &SearchOptions,
+    ) -> Vector3<f32> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("frontier_init").entered();
 
-        for i in 1..11 {
-            let vec = path.get(i - 1).unwrap();
+        let snap = |vector| match options.snap_tolerance {
+            Some(tolerance) => snap_to_open(vector, grid_provider, tolerance)
+                .unwrap_or_else(|| snap_to_grid(vector)),
+            None => snap_to_grid(vector),
+        };
 
-            assert_eq!(vec.x, -1.0 * i as f32);
-            assert_eq!(vec.y, -1.0 * i as f32);
-            assert_eq!(vec.z, 0.0);
+        let mut start = snap(start);
+        let mut goal = snap(goal);
+
+        if let Some(wrapping) = &options.wrapping {
+            start = wrapping.wrap(start);
+            goal = wrapping.wrap(goal);
+        }
+
+        if !self.keep_obstacle_cache {
+            self.obstacle_cache.clear();
+        }
+
+        // Clear instead of dropping to reuse the allocations.
+        self.reachable.clear();
+        self.explored.clear();
+        self.best_cost.clear();
+        self.incumbent = None;
+        self.stats = SearchStats::default();
+
+        // Nodes hash their coordinates as `NotNan`, so nothing non-finite may enter the heap.
+        let is_finite = |vector: &Vector3<f32>| vector.iter().all(|axis| axis.is_finite());
+
+        if !is_finite(&start) || !is_finite(&goal) {
+            return goal;
+        }
+
+        // Neither can be reached when solid, so the search ends on its first
+        // step instead of exhausting everything reachable from the start.
+        // The answers are kept for `SearchOptions::cache_obstacles`.
+        for vector in &[start, goal] {
+            let cell = (vector.x as i32, vector.y as i32, vector.z as i32);
+            let is_obstacle = *self
+                .obstacle_cache
+                .entry(cell)
+                .or_insert_with(|| grid_provider.is_obstacle_cell(cell.0, cell.1, cell.2));
+
+            if is_obstacle {
+                return goal;
+            }
         }
+
+        let start_estimated_distance =
+            options.offset(start, goal).magnitude() * options.distance_scale() * options.weight();
+
+        self.reachable.push(Node {
+            vector: start,
+            direction: Vector3::zeros(),
+            cost: 0.0,
+            estimated_cost: start_estimated_distance,
+            turns: 0,
+            estimated_turns: 0,
+            bends: 0,
+            previous_node_index: 0,
+        });
+        self.stats.max_reachable = 1;
+
+        goal
     }
 
-    struct WalledGridProvider {}
+    /// Pops nodes off the reachable heap until one is expanded or the search ends.
+    fn step<G, F>(
+        &mut self,
+        goal: &Vector3<f32>,
+        grid_provider: &G,
+        options: &SearchOptions,
+        on_frontier: &mut F,
+    ) -> SearchState
+    where
+        G: GridProvider,
+        F: FnMut(&Vector3<f32>) -> bool,
+    {
+        let weight = options.weight();
+        let distance_scale = options.distance_scale();
 
-    impl GridProvider for WalledGridProvider {
-        fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
-            if vector.x == 5.0 {
-                if 5.0 > vector.y.abs() {
-                    return true;
+        // An inflated estimate may explore a node before its cheapest way in is known.
+        // Only a cost limit needs that way, so explored nodes are then reopened.
+        let reopen = weight > 1.0 && options.max_path_cost.is_some();
+        let is_settled = |explored: &IndexSet<Node>, node: &Node| match explored.get(node) {
+            Some(explored) => !reopen || explored.cost <= node.cost,
+            None => false,
+        };
+
+        while let Some(current) = self.reachable.pop() {
+            // Lower bound of the cost of any path through the node.
+            let lower_bound = current.cost + current.estimated_cost / weight;
+
+            // The heuristic never overestimates, so no node left can do better.
+            if let Some(max_path_cost) = options.max_path_cost {
+                if lower_bound > max_path_cost {
+                    self.stats.limit_reached = true;
+
+                    // Ordered by turns first or by the inflated estimate,
+                    // cheaper nodes may be left.
+                    if options.minimize_turns || weight > 1.0 {
+                        continue;
+                    }
+
+                    return SearchState::Unreachable;
                 }
             }
 
-            if vector.z != 0.0 {
-                return true;
+            if current.vector == *goal {
+                #[cfg(feature = "tracing")]
+                let _span = tracing::debug_span!("reconstruct").entered();
+
+                let mut path = Vec::new();
+                reconstruct_path(&current, &self.explored, &mut path);
+
+                if options.include_start {
+                    // Nothing is explored when the start is the goal, the path is the start then.
+                    if let Some(start) = self.explored.get_index(0) {
+                        path.push(start.vector);
+                    }
+                }
+
+                path.reverse();
+
+                return SearchState::Found(path);
             }
 
-            false
-        }
-    }
+            // Nothing left on the heap can beat the known solution,
+            // or beat it by more than the weight when the estimate is inflated.
+            if let Some((bound, path)) = &self.incumbent {
+                if lower_bound >= bound - BOUND_EPSILON {
+                    return SearchState::Found(path.clone());
+                }
+            }
 
-    #[test]
-    fn test_walled_path() {
-        let start = Vector3::new(0.0, 0.0, 0.0);
-        let goal = Vector3::new(10.0, 0.0, 0.0);
+            // The node could be pushed several times before it was explored.
+            if is_settled(&self.explored, &current) {
+                continue;
+            }
 
-        let grid_provider = WalledGridProvider {};
+            if !on_frontier(&current.vector) {
+                self.stats.limit_reached = true;
+                return SearchState::Unreachable;
+            }
 
-        let path = find_path(start, goal, grid_provider).unwrap();
-        assert_eq!(path.len(), 13);
-
-        // #
-        // - #
-        // - - #
-        // - - - #
-        // - - - - #
-        // + + + + + #
-        // - # # # #
-        // #
-        // #
-        // #
-
-        for i in 1..4 {
-            let vec = path.get(i - 1).unwrap();
+            // Reopened nodes keep their index, so paths through them stay valid.
+            let (explored_node_index, _) = self.explored.replace_full(current.clone());
+            self.stats.expanded += 1;
 
-            assert_eq!(vec.x, 1.0 * i as f32);
-            assert_eq!(vec.y, 0.0);
-            assert_eq!(vec.z, 0.0);
-        }
+            let obstacle_cache = if options.cache_obstacles {
+                Some(&mut self.obstacle_cache)
+            } else {
+                None
+            };
 
-        for i in 1..5 {
-            let vec = path.get(2 + i).unwrap();
+            for (adjacent_vector, cost) in
+                adjacent_vectors(&current.vector, grid_provider, options, obstacle_cache)
+            {
+                let direction = options.offset(current.vector, adjacent_vector);
 
-            assert_eq!(vec.x, 4.0);
-            assert_eq!(vec.y, 1.0 * i as f32);
-            assert_eq!(vec.z, 0.0);
-        }
+                if let Some(max_turn_angle) = options.max_turn_angle {
+                    if exceeds_turn_angle(&current.direction, &direction, max_turn_angle) {
+                        continue;
+                    }
+                }
 
-        for i in 1..7 {
-            let vec = path.get(6 + i).unwrap();
+                let estimated_distance =
+                    options.offset(adjacent_vector, *goal).magnitude() * distance_scale;
 
-            assert_eq!(vec.x, 4.0 + 1.0 * i as f32);
-            assert_eq!(vec.y, 6.0 - 1.0 * i as f32);
-            assert_eq!(vec.z, 0.0);
+                // Without tracked directions the arrival is recovered from the parent,
+                // which is the node itself for the start.
+                let arrival = if options.prefer_straight && !options.tracks_direction() {
+                    let previous = self
+                        .explored
+                        .get_index(current.previous_node_index)
+                        .unwrap();
+                    options.offset(previous.vector, current.vector)
+                } else {
+                    current.direction
+                };
+
+                let is_turn = arrival != Vector3::zeros() && arrival != direction;
+                let turn_penalty = if is_turn { options.turn_penalty } else { 0.0 };
+
+                let (turns, estimated_turns) = if options.minimize_turns {
+                    (
+                        current.turns + is_turn as u32,
+                        turns_lower_bound(&direction, &options.offset(adjacent_vector, *goal)),
+                    )
+                } else {
+                    (0, 0)
+                };
+
+                // Consistency: the estimate may drop by at most the cost of the move,
+                // h(current) <= cost(current, adjacent) + h(adjacent). Together with
+                // the zero estimate at the goal it implies admissibility, which breaks
+                // e.g. with negative extra costs or turn penalties. The tolerance grows
+                // with the estimates, far goals carry larger rounding errors.
+                let tolerance = 1e-3
+                    + f32::EPSILON
+                        * 4.0
+                        * (current.estimated_cost / weight).max(estimated_distance);
+
+                debug_assert!(
+                    current.estimated_cost / weight
+                        <= cost + turn_penalty + estimated_distance + tolerance,
+                    "Inconsistent heuristic moving from {:?} to {:?}: estimate {} exceeds move cost {} plus estimate {}",
+                    current.vector,
+                    adjacent_vector,
+                    current.estimated_cost / weight,
+                    cost + turn_penalty,
+                    estimated_distance
+                );
+
+                let mut adjacent_node = Node {
+                    vector: adjacent_vector,
+                    direction: if options.tracks_direction() {
+                        direction
+                    } else {
+                        Vector3::zeros()
+                    },
+                    cost: current.cost + cost + turn_penalty,
+                    estimated_cost: estimated_distance * weight,
+                    turns,
+                    estimated_turns,
+                    bends: if options.prefer_straight {
+                        current.bends + is_turn as u32
+                    } else {
+                        0
+                    },
+                    previous_node_index: explored_node_index,
+                };
+
+                if is_settled(&self.explored, &adjacent_node) {
+                    continue;
+                }
+
+                if let Some((bound, _)) = &self.incumbent {
+                    if adjacent_node.cost + estimated_distance > bound + BOUND_EPSILON {
+                        continue;
+                    }
+                }
+
+                if let Some(&(turns, cost, bends)) = self.best_cost.get(&adjacent_node) {
+                    let is_straighter = turns == adjacent_node.turns
+                        && (cost - adjacent_node.cost).abs() <= TIE_EPSILON
+                        && adjacent_node.bends < bends;
+
+                    if is_straighter {
+                        // The exact same cost, so the straighter copy is popped first.
+                        adjacent_node.cost = cost;
+                    } else if (turns, cost) <= (adjacent_node.turns, adjacent_node.cost) {
+                        continue;
+                    }
+
+                    self.stats.reopened += 1;
+                }
+
+                self.best_cost.insert(
+                    adjacent_node.clone(),
+                    (adjacent_node.turns, adjacent_node.cost, adjacent_node.bends),
+                );
+                self.reachable.push(adjacent_node);
+                self.stats.max_reachable = self.stats.max_reachable.max(self.reachable.len());
+            }
+
+            return SearchState::InProgress;
+        }
+
+        SearchState::Unreachable
+    }
+}
+
+/// State of a search advanced by `StepwisePathfinder`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchState {
+    /// The search has nodes left to expand.
+    InProgress,
+    /// The path was found, the start is not included like in `find_path`.
+    Found(Vec<Vector3<f32>>),
+    /// Every reachable node was expanded without reaching the goal.
+    Unreachable,
+}
+
+/// Search which is advanced by a budget of expansions per call,
+/// e.g. to spread a long search over several frames of a game loop.
+pub struct StepwisePathfinder<G: GridProvider> {
+    pathfinder: Pathfinder,
+    goal: Vector3<f32>,
+    grid_provider: G,
+    options: SearchOptions,
+    /// Final state, kept so later steps return it again.
+    finished: Option<SearchState>,
+}
+
+impl<G: GridProvider> StepwisePathfinder<G> {
+    pub fn new(start: Vector3<f32>, goal: Vector3<f32>, grid_provider: G) -> Self {
+        Self::with_options(start, goal, grid_provider, SearchOptions::default())
+    }
+
+    pub fn with_options(
+        start: Vector3<f32>,
+        goal: Vector3<f32>,
+        grid_provider: G,
+        options: SearchOptions,
+    ) -> Self {
+        let mut pathfinder = Pathfinder::new();
+        let goal = pathfinder.reset(start, goal, &grid_provider, &options);
+
+        StepwisePathfinder {
+            pathfinder,
+            goal,
+            grid_provider,
+            options,
+            finished: None,
+        }
+    }
+
+    /// Expands at most `budget` nodes and returns the state of the search.
+    /// Once the search has finished, its final state is returned again.
+    pub fn step(&mut self, budget: usize) -> SearchState {
+        if let Some(state) = &self.finished {
+            return state.clone();
+        }
+
+        for _ in 0..budget {
+            let state =
+                self.pathfinder
+                    .step(&self.goal, &self.grid_provider, &self.options, &mut |_| {
+                        true
+                    });
+
+            if state != SearchState::InProgress {
+                self.finished = Some(state.clone());
+                return state;
+            }
+        }
+
+        SearchState::InProgress
+    }
+
+    /// Returns statistics of the search so far.
+    pub fn stats(&self) -> &SearchStats {
+        self.pathfinder.stats()
+    }
+}
+
+/// Pathfinder owning its provider, for many queries against the same world,
+/// e.g. on a server. Keeps the allocations like `Pathfinder` and also
+/// the obstacle answers of the provider, so queries near the earlier ones
+/// hardly ask the provider at all. The answers grow with every cell searched,
+/// see `with_cache_limit` and `clear_cache` to bound them.
+pub struct BoundPathfinder<G: GridProvider> {
+    pathfinder: Pathfinder,
+    grid_provider: G,
+    options: SearchOptions,
+    /// Kept obstacle answers past which they're dropped, see `with_cache_limit`.
+    cache_limit: Option<usize>,
+}
+
+impl<G: GridProvider> BoundPathfinder<G> {
+    pub fn new(grid_provider: G) -> Self {
+        BoundPathfinder {
+            pathfinder: Pathfinder {
+                keep_obstacle_cache: true,
+                ..Pathfinder::default()
+            },
+            grid_provider,
+            options: SearchOptions {
+                cache_obstacles: true,
+                ..SearchOptions::default()
+            },
+            cache_limit: None,
+        }
+    }
+
+    /// Sets the options of every search. Obstacle answers are only
+    /// kept with `SearchOptions::cache_obstacles`, which is set by default.
+    pub fn with_options(mut self, options: SearchOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Drops the kept obstacle answers before a search once there are more
+    /// than `limit` of them. A single search still keeps every answer it asks for.
+    pub fn with_cache_limit(mut self, limit: usize) -> Self {
+        self.cache_limit = Some(limit);
+        self
+    }
+
+    /// Finds the path from one vector to another, see `find_path`.
+    pub fn find(&mut self, start: Vector3<f32>, goal: Vector3<f32>) -> Option<Vec<Vector3<f32>>> {
+        if let Some(limit) = self.cache_limit {
+            if self.pathfinder.obstacle_cache.len() > limit {
+                self.clear_cache();
+            }
+        }
+
+        self.pathfinder
+            .search(start, goal, &self.grid_provider, &self.options, |_| true)
+    }
+
+    /// Drops the kept obstacle answers, e.g. to free memory between queries.
+    pub fn clear_cache(&mut self) {
+        self.pathfinder.obstacle_cache.clear();
+    }
+
+    pub fn provider(&self) -> &G {
+        &self.grid_provider
+    }
+
+    /// Gives access to the provider to change the world,
+    /// the kept obstacle answers are dropped.
+    pub fn provider_mut(&mut self) -> &mut G {
+        self.clear_cache();
+        &mut self.grid_provider
+    }
+
+    /// Returns statistics of the last search.
+    pub fn stats(&self) -> &SearchStats {
+        self.pathfinder.stats()
+    }
+}
+
+/// Adapts a `f64` provider to the `f32` search relative to the origin.
+struct LocalProvider<G: GridProvider64> {
+    inner: G,
+    origin: Vector3<f64>,
+}
+
+impl<G: GridProvider64> GridProvider for LocalProvider<G> {
+    fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+        self.inner
+            .is_obstacle(self.origin + vector.map(|axis| axis as f64))
+    }
+
+    fn extra_cost(&self, vector: Vector3<f32>) -> f32 {
+        self.inner
+            .extra_cost(self.origin + vector.map(|axis| axis as f64))
+    }
+}
+
+/// Solid outside of the tube around the segment, see `find_path_corridor`.
+struct CorridorProvider<G: GridProvider> {
+    inner: G,
+    start: Vector3<f32>,
+    goal: Vector3<f32>,
+    tube_radius: f32,
+}
+
+impl<G: GridProvider> CorridorProvider<G> {
+    fn is_outside(&self, vector: Vector3<f32>) -> bool {
+        let segment = self.goal - self.start;
+        let length_squared = segment.norm_squared();
+
+        let t = if length_squared > 0.0 {
+            ((vector - self.start).dot(&segment) / length_squared).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        (vector - (self.start + segment * t)).magnitude() > self.tube_radius
+    }
+}
+
+impl<G: GridProvider> GridProvider for CorridorProvider<G> {
+    fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+        self.is_outside(vector.map(|axis| axis.round())) || self.inner.is_obstacle(vector)
+    }
+
+    fn is_obstacle_cell(&self, x: i32, y: i32, z: i32) -> bool {
+        self.is_outside(Vector3::new(x as f32, y as f32, z as f32))
+            || self.inner.is_obstacle_cell(x, y, z)
+    }
+
+    fn extra_cost(&self, vector: Vector3<f32>) -> f32 {
+        self.inner.extra_cost(vector)
+    }
+
+    fn bounds(&self) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        self.inner.bounds()
+    }
+
+    fn prefetch(&self, min: Vector3<f32>, max: Vector3<f32>) {
+        self.inner.prefetch(min, max)
+    }
+}
+
+/// Returns the cost of moving along the path from the start, or `None` if the path
+/// doesn't end at the goal, skips a cell or passes through an obstacle.
+pub(crate) fn path_cost<G: GridProvider>(
+    start: &Vector3<f32>,
+    goal: &Vector3<f32>,
+    path: &[Vector3<f32>],
+    grid_provider: &G,
+) -> Option<f32> {
+    if path.last() != Some(goal) {
+        return None;
+    }
+
+    let mut cost = 0.0;
+    let mut previous = *start;
+
+    for vector in path {
+        let step = vector - previous;
+
+        if step == Vector3::zeros() || step.iter().any(|axis| axis.abs() > 1.0) {
+            return None;
+        }
+
+        if grid_provider.is_obstacle_cell(vector.x as i32, vector.y as i32, vector.z as i32) {
+            return None;
+        }
+
+        cost += step.magnitude() + grid_provider.extra_cost(*vector);
+        previous = *vector;
+    }
+
+    Some(cost)
+}
+
+/// Rounds the vector to the integer lattice the search moves over.
+pub fn snap_to_grid(vector: Vector3<f32>) -> Vector3<f32> {
+    vector.map(|axis| axis.round())
+}
+
+/// Rounds the vector to the nearest open cell among the rounded one and its
+/// 26 neighbors, as long as the cell is within `tolerance` of the vector.
+/// Guards the start and the goal against rounding into an adjacent wall
+/// when they sit on a cell boundary. `None` if there is no such cell.
+pub fn snap_to_open<G: GridProvider>(
+    vector: Vector3<f32>,
+    grid_provider: &G,
+    tolerance: f32,
+) -> Option<Vector3<f32>> {
+    let rounded = snap_to_grid(vector);
+
+    std::iter::once(&(0, 0, 0))
+        .chain(Z_INNERMOST_OFFSETS.iter())
+        .map(|&(x, y, z)| rounded + Vector3::new(x as f32, y as f32, z as f32))
+        .map(|cell| (cell, (cell - vector).magnitude()))
+        .filter(|(_, distance)| *distance <= tolerance)
+        .filter(|(cell, _)| {
+            !grid_provider.is_obstacle_cell(cell.x as i32, cell.y as i32, cell.z as i32)
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(cell, _)| cell)
+}
+
+fn adjacent_vectors<G: GridProvider>(
+    vector: &Vector3<f32>,
+    grid_provider: &G,
+    options: &SearchOptions,
+    mut obstacle_cache: Option<&mut HashMap<(i32, i32, i32), bool>>,
+) -> Vec<(Vector3<f32>, f32)> {
+    let mut adjacent = Vec::new();
+    let provider_bounds = grid_provider.bounds();
+
+    let mut visit = |offset: Vector3<f32>, length: f32| {
+        if options.vertical_moves_axis_only
+            && offset.z != 0.0
+            && (offset.x != 0.0 || offset.y != 0.0)
+        {
+            return;
+        }
+
+        let adjacent_vector = match &options.wrapping {
+            Some(wrapping) => wrapping.wrap(offset + vector),
+            None => offset + vector,
+        };
+
+        if let Some((min, max)) = &options.bounds {
+            if !is_within_bounds(&adjacent_vector, min, max) {
+                return;
+            }
+        }
+
+        if let Some((min, max)) = &provider_bounds {
+            if !is_within_bounds(&adjacent_vector, min, max) {
+                return;
+            }
+        }
+
+        let cell = (
+            adjacent_vector.x as i32,
+            adjacent_vector.y as i32,
+            adjacent_vector.z as i32,
+        );
+
+        let is_obstacle = match obstacle_cache.as_deref_mut() {
+            Some(obstacle_cache) => *obstacle_cache
+                .entry(cell)
+                .or_insert_with(|| grid_provider.is_obstacle_cell(cell.0, cell.1, cell.2)),
+            None => grid_provider.is_obstacle_cell(cell.0, cell.1, cell.2),
+        };
+
+        if !is_obstacle {
+            let vertical_cost = options
+                .vertical_costs
+                .map_or(0.0, |vertical_costs| vertical_costs.cost(offset.z));
+            let cost = length + vertical_cost + grid_provider.extra_cost(adjacent_vector);
+            adjacent.push((adjacent_vector, cost))
+        }
+    };
+
+    match &options.motions {
+        Some(motions) => {
+            for motion in motions {
+                visit(motion.offset, motion.cost);
+            }
+        }
+        None => {
+            for &(x, y, z) in options.neighbor_order.offsets() {
+                // Euclidean move length keeps the distance heuristic admissible.
+                let length = ((x * x + y * y + z * z) as f32).sqrt();
+                visit(Vector3::new(x as f32, y as f32, z as f32), length);
+            }
+        }
+    }
+
+    adjacent
+}
+
+/// Turns needed at least to reach the goal at the offset, arriving by the direction.
+/// Zero when the goal lies straight ahead, otherwise one.
+/// The start has no direction yet, its first move isn't a turn.
+fn turns_lower_bound(direction: &Vector3<f32>, offset: &Vector3<f32>) -> u32 {
+    if *direction == Vector3::zeros() || *offset == Vector3::zeros() {
+        return 0;
+    }
+
+    let is_ahead = direction.cross(offset).magnitude() < 1e-4 && direction.dot(offset) > 0.0;
+
+    if is_ahead {
+        0
+    } else {
+        1
+    }
+}
+
+fn is_within_bounds(vector: &Vector3<f32>, min: &Vector3<f32>, max: &Vector3<f32>) -> bool {
+    (0..3).all(|axis| vector[axis] >= min[axis] && vector[axis] <= max[axis])
+}
+
+fn exceeds_turn_angle(
+    previous_direction: &Vector3<f32>,
+    direction: &Vector3<f32>,
+    max_turn_angle: f32,
+) -> bool {
+    if *previous_direction == Vector3::zeros() {
+        return false;
+    }
+
+    // Small epsilon to keep exact 45 and 90 degree turns allowed.
+    previous_direction.angle(direction) > max_turn_angle + 1e-4
+}
+
+fn reconstruct_path(node: &Node, explored: &IndexSet<Node>, path: &mut Vec<Vector3<f32>>) {
+    path.push(node.vector);
+
+    if node.previous_node_index != 0 {
+        let previous_node = explored.get_index(node.previous_node_index).unwrap();
+        reconstruct_path(previous_node, explored, path);
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let total_turns1 = self.turns + self.estimated_turns;
+        let total_turns2 = other.turns + other.estimated_turns;
+
+        let total_cost1 = self.cost + self.estimated_cost;
+        let total_cost2 = other.cost + other.estimated_cost;
+
+        // Costs within the tie tolerance share a tier, where fewer bends go first.
+        // Bends are zero unless straight paths are preferred, which leaves the order by cost.
+        let tier = |cost: f32| (cost / TIE_EPSILON).round() as i64;
+
+        // This how we convert max binary heap to min binary heap.
+        // Turns come first, they are zero unless minimized.
+        total_turns2
+            .cmp(&total_turns1)
+            .then_with(|| tier(total_cost2).cmp(&tier(total_cost1)))
+            .then_with(|| other.bends.cmp(&self.bends))
+            .then_with(|| total_cost2.partial_cmp(&total_cost1).unwrap())
+    }
+}
+
+impl Eq for Node {}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.vector.eq(&other.vector) && self.direction.eq(&other.direction)
+    }
+}
+
+impl Hash for Node {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        unsafe {
+            let x_non_nan = NotNan::new_unchecked(self.vector.x);
+            let y_non_nan = NotNan::new_unchecked(self.vector.y);
+            let z_non_nan = NotNan::new_unchecked(self.vector.z);
+
+            x_non_nan.hash(state);
+            y_non_nan.hash(state);
+            z_non_nan.hash(state);
+
+            NotNan::new_unchecked(self.direction.x).hash(state);
+            NotNan::new_unchecked(self.direction.y).hash(state);
+            NotNan::new_unchecked(self.direction.z).hash(state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid::Grid;
+    use crate::pathfinding::{
+        direct_path, distance_to_goal_field, find_path, find_path_anytime, find_path_corridor,
+        find_path_f64, find_path_seeded, find_path_sequence, find_path_streaming,
+        find_path_with_cost, find_path_with_motions, find_path_with_options, find_path_with_stats,
+        path_cost, search_anytime, snap_to_open, Motion, NeighborOrder, Pathfinder, SearchOptions,
+        SearchState, StepwisePathfinder, VerticalCosts,
+    };
+    use crate::provider::{
+        ClearanceProvider, FnProvider, GridProvider, GridProvider64, RecordingProvider,
+        SingleGridProvider, WrappingProvider,
+    };
+    use crate::transform::{Topology, Wrapping};
+    use nalgebra::Vector3;
+    use std::time::{Duration, Instant};
+
+    struct SimpleGridProvider {}
+
+    impl GridProvider for SimpleGridProvider {
+        fn is_obstacle(&self, _vector: Vector3<f32>) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_simple_straight_path() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(10.0, 0.0, 0.0);
+
+        let grid_provider = SimpleGridProvider {};
+
+        let path = find_path(start, goal, grid_provider).unwrap();
+        assert_eq!(path.len(), 10);
+
+        for i in 1..11 {
+            let vec = path.get(i - 1).unwrap();
+
+            assert_eq!(vec.x, 1.0 * i as f32);
+            assert_eq!(vec.y, 0.0);
+            assert_eq!(vec.z, 0.0);
+        }
+    }
+
+    #[test]
+    fn test_include_start() {
+        let start = Vector3::new(0.2, 0.0, 0.0);
+        let goal = Vector3::new(3.0, 0.0, 0.0);
+
+        let path = find_path(start, goal, SimpleGridProvider {}).unwrap();
+        assert_eq!(path.first(), Some(&Vector3::new(1.0, 0.0, 0.0)));
+        assert_eq!(path.len(), 3);
+
+        let options = SearchOptions {
+            include_start: true,
+            ..SearchOptions::default()
+        };
+
+        let path = find_path_with_options(start, goal, SimpleGridProvider {}, &options).unwrap();
+        assert_eq!(path.first(), Some(&Vector3::new(0.0, 0.0, 0.0)));
+        assert_eq!(path.get(1), Some(&Vector3::new(1.0, 0.0, 0.0)));
+        assert_eq!(path.len(), 4);
+
+        let path = find_path_with_options(goal, goal, SimpleGridProvider {}, &options);
+        assert_eq!(path, Some(vec![goal]));
+    }
+
+    #[test]
+    fn test_fractional_path() {
+        let start = Vector3::new(0.2, -0.3, 0.0);
+        let goal = Vector3::new(9.7, 0.4, 0.0);
+
+        let grid_provider = SimpleGridProvider {};
+
+        let path = find_path(start, goal, grid_provider).unwrap();
+        assert_eq!(path.len(), 10);
+        assert_eq!(path.last(), Some(&Vector3::new(10.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_heuristic_weight() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(10.0, 0.0, 0.0);
+
+        let (optimal, optimal_stats) = find_path_with_stats(start, goal, WalledGridProvider {});
+        let optimal_cost =
+            path_cost(&start, &goal, &optimal.unwrap(), &WalledGridProvider {}).unwrap();
+
+        let options = SearchOptions {
+            heuristic_weight: Some(3.0),
+            ..SearchOptions::default()
+        };
+
+        let mut pathfinder = Pathfinder::new();
+        let path = pathfinder
+            .solve_with_options(start, goal, WalledGridProvider {}, &options)
+            .unwrap();
+        let cost = path_cost(&start, &goal, &path, &WalledGridProvider {}).unwrap();
+
+        assert!(cost >= optimal_cost - 1e-4);
+        assert!(cost <= optimal_cost * 3.0);
+        assert!(pathfinder.stats().expanded < optimal_stats.expanded);
+    }
+
+    #[test]
+    fn test_anytime_refines_path() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(10.0, 0.0, 0.0);
+
+        let optimal = find_path(start, goal, WalledGridProvider {}).unwrap();
+        let optimal_cost = path_cost(&start, &goal, &optimal, &WalledGridProvider {}).unwrap();
+
+        let mut found = Vec::new();
+        let deadline = Instant::now() + Duration::from_secs(60);
+
+        let best = search_anytime(start, goal, &WalledGridProvider {}, deadline, |path| {
+            found.push(path.clone())
+        })
+        .unwrap();
+
+        assert!(found.first().unwrap().cost > optimal_cost + 1e-4);
+
+        for pair in found.windows(2) {
+            assert!(pair[1].cost <= pair[0].cost);
+            assert!(pair[1].suboptimality < pair[0].suboptimality);
+        }
+
+        assert!((best.cost - optimal_cost).abs() < 1e-4);
+        assert_eq!(best.suboptimality, 1.0);
+
+        let best = find_path_anytime(start, goal, WalledGridProvider {}, Duration::from_secs(60));
+        assert_eq!(best.map(|best| best.suboptimality), Some(1.0));
+
+        let best = find_path_anytime(start, goal, WalledGridProvider {}, Duration::from_secs(0));
+        assert_eq!(best, None);
+    }
+
+    #[test]
+    fn test_vertical_costs() {
+        // Open slab from z = 0 to z = 2.
+        let grid_provider = FnProvider::new(|vector| vector.z < 0.0 || vector.z > 2.0);
+
+        let start = Vector3::new(0.0, 0.0, 1.0);
+        let goal = Vector3::new(10.0, 0.0, 1.0);
+
+        let path = find_path(start, goal, &grid_provider).unwrap();
+        assert!(path.iter().all(|vector| vector.z == 1.0));
+
+        // Descending is discounted enough to pay for climbing back.
+        let options = SearchOptions {
+            vertical_costs: Some(VerticalCosts::new(0.0, -0.9)),
+            ..SearchOptions::default()
+        };
+
+        let path = find_path_with_options(start, goal, &grid_provider, &options).unwrap();
+
+        assert_eq!(path.len(), 10);
+        assert!(path.iter().any(|vector| vector.z == 0.0));
+        assert_eq!(path.last(), Some(&goal));
+
+        // Climbing back costs more than the discount saves.
+        let options = SearchOptions {
+            vertical_costs: Some(VerticalCosts::new(0.5, -0.9)),
+            ..SearchOptions::default()
+        };
+
+        let path = find_path_with_options(start, goal, &grid_provider, &options).unwrap();
+        assert!(path.iter().all(|vector| vector.z == 1.0));
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_tracing_spans() {
+        use std::fmt::Debug;
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        /// Collects span names and "span.field=value" records, events count as spans.
+        #[derive(Clone, Default)]
+        struct Collector {
+            names: Arc<Mutex<Vec<&'static str>>>,
+            records: Arc<Mutex<Vec<String>>>,
+        }
+
+        struct Visitor<'a>(&'static str, &'a mut Vec<String>);
+
+        impl Visit for Visitor<'_> {
+            fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+                self.1
+                    .push(format!("{}.{}={:?}", self.0, field.name(), value));
+            }
+        }
+
+        impl Subscriber for Collector {
+            fn enabled(&self, _: &Metadata) -> bool {
+                true
+            }
+
+            fn new_span(&self, span: &Attributes) -> Id {
+                let mut names = self.names.lock().unwrap();
+                names.push(span.metadata().name());
+
+                span.record(&mut Visitor(
+                    span.metadata().name(),
+                    &mut self.records.lock().unwrap(),
+                ));
+
+                Id::from_u64(names.len() as u64)
+            }
+
+            fn record(&self, span: &Id, values: &Record) {
+                let name = self.names.lock().unwrap()[span.into_u64() as usize - 1];
+                values.record(&mut Visitor(name, &mut self.records.lock().unwrap()));
+            }
+
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+            fn event(&self, event: &Event) {
+                event.record(&mut Visitor("event", &mut self.records.lock().unwrap()));
+            }
+
+            fn enter(&self, _: &Id) {}
+
+            fn exit(&self, _: &Id) {}
+        }
+
+        let collector = Collector::default();
+        let grid_provider = FnProvider::new(|vector| vector.z != 0.0 || vector.x == 3.0);
+
+        tracing::subscriber::with_default(collector.clone(), || {
+            find_path(
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(2.0, 0.0, 0.0),
+                &grid_provider,
+            );
+
+            // Blocked by the wall once the search is bounded.
+            find_path_with_options(
+                Vector3::new(0.0, 0.0, 0.0),
+                Vector3::new(5.0, 0.0, 0.0),
+                &grid_provider,
+                &SearchOptions {
+                    bounds: Some((Vector3::new(0.0, 0.0, 0.0), Vector3::new(5.0, 5.0, 0.0))),
+                    ..SearchOptions::default()
+                },
+            );
+        });
+
+        let records = collector.records.lock().unwrap();
+        let names = collector.names.lock().unwrap();
+
+        assert!(records.contains(&"find_path.start=(0.0, 0.0, 0.0)".to_string()));
+        assert!(records.contains(&"find_path.goal=(2.0, 0.0, 0.0)".to_string()));
+        assert!(records.contains(&"find_path.nodes=2".to_string()));
+        assert!(records.contains(&"find_path.nodes=18".to_string()));
+        assert!(records
+            .iter()
+            .any(|record| record.starts_with("find_path.elapsed_us=")));
+        assert!(records.contains(&"event.message=No path found".to_string()));
+        assert_eq!(names.iter().filter(|name| **name == "find_path").count(), 2);
+        assert!(names.contains(&"frontier_init"));
+        assert!(names.contains(&"main_loop"));
+        assert!(names.contains(&"reconstruct"));
+    }
+
+    #[test]
+    fn test_wrapping() {
+        // Flat world 20 cells wide, solid outside of it unless queries wrap.
+        let flat =
+            || FnProvider::new(|vector| vector.z != 0.0 || vector.x < 0.0 || vector.x > 19.0);
+        let wrapping = Wrapping::new(Topology::WrapX, 20.0, 10.0);
+
+        let start = Vector3::new(1.0, 5.0, 0.0);
+        let goal = Vector3::new(18.0, 5.0, 0.0);
+
+        let options = SearchOptions {
+            bounds: Some((Vector3::new(0.0, 0.0, 0.0), Vector3::new(19.0, 9.0, 0.0))),
+            ..SearchOptions::default()
+        };
+
+        let bounded = find_path_with_options(start, goal, flat(), &options).unwrap();
+
+        let options = SearchOptions {
+            wrapping: Some(wrapping),
+            ..options
+        };
+
+        let wrapped = find_path_with_options(
+            start,
+            goal,
+            WrappingProvider::new(flat(), wrapping),
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(bounded.len(), 17);
+        assert_eq!(
+            wrapped,
+            vec![
+                Vector3::new(0.0, 5.0, 0.0),
+                Vector3::new(19.0, 5.0, 0.0),
+                Vector3::new(18.0, 5.0, 0.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_path_with_motions() {
+        let motions = vec![
+            Motion::euclidean(Vector3::new(2.0, 0.0, 0.0)),
+            Motion::euclidean(Vector3::new(-2.0, 0.0, 0.0)),
+            Motion::euclidean(Vector3::new(0.0, 2.0, 0.0)),
+            Motion::euclidean(Vector3::new(0.0, -2.0, 0.0)),
+        ];
+
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(10.0, 0.0, 0.0);
+
+        // Jumps over the wall at x = 5 instead of going around it.
+        let path = find_path_with_motions(start, goal, WalledGridProvider {}, motions).unwrap();
+
+        assert_eq!(
+            path,
+            (1..=5)
+                .map(|step| Vector3::new(step as f32 * 2.0, 0.0, 0.0))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_obstacle_goal() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let wall = Vector3::new(5.0, 0.0, 0.0);
+
+        let (path, stats) = find_path_with_stats(start, wall, WalledGridProvider {});
+        assert_eq!(path, None);
+        assert_eq!(stats.expanded, 0);
+
+        let (path, stats) = find_path_with_stats(wall, start, WalledGridProvider {});
+        assert_eq!(path, None);
+        assert_eq!(stats.expanded, 0);
+
+        assert_eq!(find_path(wall, wall, WalledGridProvider {}), None);
+    }
+
+    #[test]
+    fn test_path_with_cost() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+
+        let (path, cost) =
+            find_path_with_cost(start, Vector3::new(3.0, 4.0, 0.0), SimpleGridProvider {}).unwrap();
+
+        assert_eq!(path.len(), 4);
+        assert!((cost - (3.0 * 2f32.sqrt() + 1.0)).abs() < 1e-4);
+
+        let (path, cost) = find_path_with_cost(start, start, SimpleGridProvider {}).unwrap();
+
+        assert_eq!(path, vec![start]);
+        assert_eq!(cost, 0.0);
+    }
+
+    #[test]
+    fn test_sequence_path() {
+        let waypoints = vec![
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(3.0, 0.0, 0.0),
+            Vector3::new(3.0, 3.0, 0.0),
+            Vector3::new(3.0, 3.0, 0.0),
+            Vector3::new(3.0, 3.0, 2.0),
+        ];
+
+        let path = find_path_sequence(&waypoints, &SimpleGridProvider {}).unwrap();
+
+        assert_eq!(
+            path,
+            vec![
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(2.0, 0.0, 0.0),
+                Vector3::new(3.0, 0.0, 0.0),
+                Vector3::new(3.0, 1.0, 0.0),
+                Vector3::new(3.0, 2.0, 0.0),
+                Vector3::new(3.0, 3.0, 0.0),
+                Vector3::new(3.0, 3.0, 1.0),
+                Vector3::new(3.0, 3.0, 2.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stats() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(10.0, 0.0, 0.0);
+
+        let (path, stats) = find_path_with_stats(start, goal, SimpleGridProvider {});
+
+        assert_eq!(path.map(|path| path.len()), Some(10));
+        assert_eq!(stats.expanded, 10);
+        assert!(stats.max_reachable >= 26);
+        assert!(!stats.limit_reached);
+
+        let mut pathfinder = Pathfinder::new();
+        let mut expanded = 0;
+
+        let path = pathfinder.search(
+            start,
+            goal,
+            &SimpleGridProvider {},
+            &SearchOptions::default(),
+            |_| {
+                expanded += 1;
+                expanded < 3
+            },
+        );
+
+        assert_eq!(path, None);
+        assert_eq!(pathfinder.stats().expanded, 2);
+        assert!(pathfinder.stats().limit_reached);
+    }
+
+    #[test]
+    fn test_pathfinder_reuse() {
+        let mut pathfinder = Pathfinder::new();
+
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(10.0, 0.0, 0.0);
+
+        let expected = find_path(start, goal, WalledGridProvider {});
+
+        assert_eq!(
+            pathfinder.solve(start, goal, WalledGridProvider {}),
+            expected
+        );
+        assert_eq!(
+            pathfinder.solve(start, goal, WalledGridProvider {}),
+            expected
+        );
+
+        let goal = Vector3::new(-10.0, -10.0, 0.0);
+        let expected = find_path(start, goal, SimpleGridProvider {});
+
+        assert_eq!(
+            pathfinder.solve(start, goal, SimpleGridProvider {}),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_bound_pathfinder() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(10.0, 0.0, 0.0);
+        let expected = find_path(start, goal, WalledGridProvider {});
+
+        let mut pathfinder =
+            Pathfinder::with_provider(RecordingProvider::new(WalledGridProvider {}));
+
+        assert_eq!(pathfinder.find(start, goal), expected);
+        let queries = pathfinder.provider().queries().len();
+        assert!(queries > 0);
+
+        // Every answer is known from the first search.
+        assert_eq!(pathfinder.find(start, goal), expected);
+        assert_eq!(pathfinder.provider().queries().len(), queries);
+
+        // Nearby, only the cells around the new goal are asked.
+        let nearby = Vector3::new(10.0, 1.0, 0.0);
+        assert_eq!(
+            pathfinder.find(start, nearby),
+            find_path(start, nearby, WalledGridProvider {})
+        );
+        assert!(pathfinder.provider().queries().len() < queries * 2);
+
+        pathfinder.provider_mut();
+        assert_eq!(pathfinder.find(start, goal), expected);
+        assert!(pathfinder.provider().queries().len() > queries);
+    }
+
+    #[test]
+    fn test_bound_pathfinder_cache() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(10.0, 0.0, 0.0);
+        let expected = find_path(start, goal, WalledGridProvider {});
+
+        let mut pathfinder = Pathfinder::with_provider(WalledGridProvider {});
+        pathfinder.find(start, goal);
+
+        let answers = pathfinder.pathfinder.obstacle_cache.len();
+        assert!(answers > 0);
+
+        pathfinder.clear_cache();
+        assert!(pathfinder.pathfinder.obstacle_cache.is_empty());
+
+        // Under the limit the answers are kept, past it they're dropped first.
+        let mut pathfinder =
+            Pathfinder::with_provider(WalledGridProvider {}).with_cache_limit(answers);
+
+        assert_eq!(pathfinder.find(start, goal), expected);
+        assert_eq!(pathfinder.find(start, goal), expected);
+        assert_eq!(pathfinder.pathfinder.obstacle_cache.len(), answers);
+
+        let far = Vector3::new(-10.0, 0.0, 0.0);
+        pathfinder.find(start, far);
+        assert!(pathfinder.pathfinder.obstacle_cache.len() > answers);
+
+        assert_eq!(pathfinder.find(start, goal), expected);
+        assert_eq!(pathfinder.pathfinder.obstacle_cache.len(), answers);
+    }
+
+    struct NegativeCostGridProvider {}
+
+    impl GridProvider for NegativeCostGridProvider {
+        fn is_obstacle(&self, _vector: Vector3<f32>) -> bool {
+            false
+        }
+
+        fn extra_cost(&self, _vector: Vector3<f32>) -> f32 {
+            -2.0
+        }
+    }
+
+    #[test]
+    fn test_far_goal_bounded() {
+        // Estimates this large carry rounding errors well above any fixed tolerance.
+        let start = Vector3::new(2.0, 77.0, 0.0);
+        let goal = Vector3::new(300000.0, 200000.0, 0.0);
+        let grid_provider = FnProvider::new(|vector| vector.z != 0.0);
+
+        let options = SearchOptions {
+            bounds: Some((
+                Vector3::new(-8.0, 69.0, -1.0),
+                Vector3::new(12.0, 85.0, 1.0),
+            )),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            find_path_with_options(start, goal, &grid_provider, &options),
+            None
+        );
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Inconsistent heuristic")]
+    fn test_inconsistent_heuristic() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(10.0, 0.0, 0.0);
+
+        find_path(start, goal, NegativeCostGridProvider {});
+    }
+
+    #[test]
+    fn test_max_path_cost() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+
+        let options = SearchOptions {
+            max_path_cost: Some(10.5),
+            ..SearchOptions::default()
+        };
+
+        let within = Vector3::new(10.0, 0.0, 0.0);
+        let path = find_path_with_options(start, within, SimpleGridProvider {}, &options);
+        assert_eq!(path.map(|path| path.len()), Some(10));
+
+        let mut pathfinder = Pathfinder::new();
+        let beyond = Vector3::new(11.0, 0.0, 0.0);
+
+        assert_eq!(
+            pathfinder.solve_with_options(start, beyond, SimpleGridProvider {}, &options),
+            None
+        );
+        assert!(pathfinder.stats().limit_reached);
+        assert_eq!(pathfinder.stats().expanded, 0);
+    }
+
+    #[test]
+    fn test_max_path_cost_weighted() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(10.0, 0.0, 0.0);
+
+        let (_, optimal_cost) = find_path_with_cost(start, goal, WalledGridProvider {}).unwrap();
+
+        // The heap is ordered by the inflated estimate, nodes popped over the limit
+        // don't mean the rest of the heap is over it too.
+        let options = SearchOptions {
+            heuristic_weight: Some(3.0),
+            max_path_cost: Some(optimal_cost + 0.5),
+            ..SearchOptions::default()
+        };
+
+        let path = find_path_with_options(start, goal, WalledGridProvider {}, &options).unwrap();
+        let cost = path_cost(&start, &goal, &path, &WalledGridProvider {}).unwrap();
+        assert!(cost <= optimal_cost + 0.5);
+
+        let options = SearchOptions {
+            max_path_cost: Some(optimal_cost - 0.5),
+            ..options
+        };
+
+        assert_eq!(
+            find_path_with_options(start, goal, WalledGridProvider {}, &options),
+            None
+        );
+    }
+
+    #[test]
+    fn test_stepwise() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(10.0, 0.0, 0.0);
+
+        let expected = find_path(start, goal, WalledGridProvider {}).unwrap();
+
+        let mut pathfinder = StepwisePathfinder::new(start, goal, WalledGridProvider {});
+        let mut steps = 0;
+
+        let path = loop {
+            match pathfinder.step(1) {
+                SearchState::InProgress => steps += 1,
+                SearchState::Found(path) => break path,
+                SearchState::Unreachable => panic!("Path should be found"),
+            }
+        };
+
+        assert_eq!(path, expected);
+        assert_eq!(steps, pathfinder.stats().expanded);
+        assert_eq!(pathfinder.step(1), SearchState::Found(expected));
+    }
+
+    #[test]
+    fn test_stepwise_unreachable() {
+        let options = SearchOptions {
+            bounds: Some((Vector3::new(-2.0, -2.0, -2.0), Vector3::new(2.0, 2.0, 2.0))),
+            ..SearchOptions::default()
+        };
+
+        let mut pathfinder = StepwisePathfinder::with_options(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(10.0, 0.0, 0.0),
+            SimpleGridProvider {},
+            options,
+        );
+
+        assert_eq!(pathfinder.step(10), SearchState::InProgress);
+        assert_eq!(pathfinder.step(usize::MAX), SearchState::Unreachable);
+        assert_eq!(pathfinder.stats().expanded, 5 * 5 * 5);
+    }
+
+    #[test]
+    fn test_pathfinder_with_capacity() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(10.0, 0.0, 0.0);
+
+        let mut pathfinder = Pathfinder::with_capacity(1024);
+
+        assert!(pathfinder.explored.capacity() >= 1024);
+        assert!(pathfinder.reachable.capacity() >= 1024);
+        assert_eq!(
+            pathfinder.solve(start, goal, WalledGridProvider {}),
+            find_path(start, goal, WalledGridProvider {})
+        );
+    }
+
+    #[test]
+    fn test_simple_horizontal_path() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(-10.0, -10.0, 0.0);
+
+        let grid_provider = SimpleGridProvider {};
+
+        let path = find_path(start, goal, grid_provider).unwrap();
+        assert_eq!(path.len(), 10);
+
+        for i in 1..11 {
+            let vec = path.get(i - 1).unwrap();
+
+            assert_eq!(vec.x, -(i as f32));
+            assert_eq!(vec.y, -(i as f32));
+            assert_eq!(vec.z, 0.0);
+        }
+    }
+
+    struct WalledGridProvider {}
+
+    impl GridProvider for WalledGridProvider {
+        fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+            if vector.x == 5.0 && 5.0 > vector.y.abs() {
+                return true;
+            }
+
+            if vector.z != 0.0 {
+                return true;
+            }
+
+            false
+        }
+    }
+
+    #[test]
+    fn test_snap_tolerance() {
+        // Wall at x = 5, the start on the boundary rounds into it.
+        let grid_provider = FnProvider::new(|vector| vector.z != 0.0 || vector.x == 5.0);
+
+        let start = Vector3::new(4.5, 0.0, 0.0);
+        let goal = Vector3::new(0.0, 0.0, 0.0);
+
+        assert_eq!(find_path(start, goal, &grid_provider), None);
+
+        let options = SearchOptions {
+            snap_tolerance: Some(0.5),
+            include_start: true,
+            ..SearchOptions::default()
+        };
+
+        let path = find_path_with_options(start, goal, &grid_provider, &options).unwrap();
+
+        assert_eq!(path.first(), Some(&Vector3::new(4.0, 0.0, 0.0)));
+        assert_eq!(path.last(), Some(&goal));
+
+        assert_eq!(
+            snap_to_open(Vector3::new(5.2, 0.0, 0.0), &grid_provider, 0.5),
+            None
+        );
+        assert_eq!(
+            snap_to_open(Vector3::new(5.2, 0.0, 0.0), &grid_provider, 1.0),
+            Some(Vector3::new(6.0, 0.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn test_distance_to_goal_field() {
+        let goal = Vector3::new(2.0, 2.0, 0.0);
+        let region = (Vector3::new(0.0, 0.0, 0.0), Vector3::new(9.0, 9.0, 0.0));
+
+        let field = distance_to_goal_field(goal, WalledGridProvider {}, region);
+
+        assert_eq!(field.get(&(2, 2, 0)), Some(&0.0));
+        assert_eq!(field.get(&(3, 2, 0)), Some(&1.0));
+        assert_eq!(field.get(&(2, 1, 0)), Some(&1.0));
+        assert!((field[&(3, 3, 0)] - 2f32.sqrt()).abs() < 1e-6);
+        assert!(!field.contains_key(&(5, 2, 0)));
+
+        // Around the wall through y = 5, it blocks |y| < 5.
+        let target = Vector3::new(8.0, 2.0, 0.0);
+        let (_, cost) = find_path_with_cost(target, goal, WalledGridProvider {}).unwrap();
+        assert!((field[&(8, 2, 0)] - cost).abs() < 1e-4);
+
+        // The cell is closed off by walls within the region.
+        let walled_off = FnProvider::new(|vector| {
+            vector.z != 0.0
+                || ((vector.x - 7.0).abs() == 1.0 && (vector.y - 7.0).abs() <= 1.0)
+                || ((vector.y - 7.0).abs() == 1.0 && (vector.x - 7.0).abs() <= 1.0)
+        });
+
+        let field = distance_to_goal_field(goal, &walled_off, region);
+
+        assert!(!field.contains_key(&(7, 7, 0)));
+        assert_eq!(field.len(), 100 - 9);
+    }
+
+    #[test]
+    fn test_vertical_moves_axis_only() {
+        let grid_provider = SimpleGridProvider {};
+
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(6.0, 4.0, 3.0);
+
+        let options = SearchOptions {
+            vertical_moves_axis_only: true,
+            include_start: true,
+            ..SearchOptions::default()
+        };
+
+        let path = find_path_with_options(start, goal, grid_provider, &options).unwrap();
+
+        assert_eq!(path.last(), Some(&goal));
+        // 4 diagonal and 2 straight horizontal moves, 3 vertical ones.
+        assert_eq!(path.len(), 1 + 6 + 3);
+
+        for segment in path.windows(2) {
+            let step = segment[1] - segment[0];
+            assert!(
+                step.z == 0.0 || (step.x == 0.0 && step.y == 0.0),
+                "{:?}",
+                step
+            );
+        }
+    }
+
+    #[test]
+    fn test_prefer_straight() {
+        let grid_provider = FnProvider::new(|vector| vector.z != 0.0);
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(10.0, 4.0, 0.0);
+
+        let options = SearchOptions {
+            prefer_straight: true,
+            ..SearchOptions::default()
+        };
+
+        let path = find_path_with_options(start, goal, &grid_provider, &options).unwrap();
+        let (_, cost) = find_path_with_cost(start, goal, &grid_provider).unwrap();
+
+        // Six straight moves, then four diagonal ones instead of a staircase.
+        let mut expected: Vec<Vector3<f32>> =
+            (1..=6).map(|x| Vector3::new(x as f32, 0.0, 0.0)).collect();
+        expected.extend((1..=4).map(|i| Vector3::new(6.0 + i as f32, i as f32, 0.0)));
+
+        assert_eq!(path, expected);
+        assert!((path_cost(&start, &goal, &path, &grid_provider).unwrap() - cost).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_minimize_turns() {
+        let turns = |path: &[Vector3<f32>]| {
+            let steps: Vec<Vector3<f32>> = path.windows(2).map(|pair| pair[1] - pair[0]).collect();
+            steps.windows(2).filter(|pair| pair[0] != pair[1]).count()
+        };
+
+        let options = SearchOptions {
+            minimize_turns: true,
+            include_start: true,
+            ..SearchOptions::default()
+        };
+
+        // Flat: three diagonal moves, then four straight ones.
+        let grid_provider = FnProvider::new(|vector| vector.z != 0.0);
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(7.0, 3.0, 0.0);
+
+        let path = find_path_with_options(start, goal, &grid_provider, &options).unwrap();
+        let (_, cost) = find_path_with_cost(start, goal, &grid_provider).unwrap();
+
+        assert_eq!(turns(&path), 1);
+        assert_eq!(path.last(), Some(&goal));
+        assert!(
+            (path_cost(&start, &goal, &path[1..], &grid_provider).unwrap() - cost).abs() < 1e-4
+        );
+
+        // No two lattice directions reach this goal, three straight runs do.
+        let grid_provider = SingleGridProvider::new(Grid::new(12, 4));
+        let goal = Vector3::new(6.0, 3.0, 1.0);
+        let path = find_path_with_options(start, goal, &grid_provider, &options).unwrap();
+
+        assert_eq!(turns(&path), 2);
+        assert_eq!(path.last(), Some(&goal));
+
+        // Straight ahead needs no turn, the wall forces a detour around its end at y = 5.
+        let options = SearchOptions {
+            bounds: Some((Vector3::new(-2.0, -8.0, 0.0), Vector3::new(12.0, 8.0, 0.0))),
+            ..options
+        };
+
+        let path = find_path_with_options(
+            start,
+            Vector3::new(10.0, 0.0, 0.0),
+            WalledGridProvider {},
+            &options,
+        )
+        .unwrap();
+
+        assert_eq!(turns(&path), 1);
+        assert!(
+            path.contains(&Vector3::new(5.0, 5.0, 0.0))
+                || path.contains(&Vector3::new(5.0, -5.0, 0.0))
+        );
+    }
+
+    #[test]
+    fn test_corridor() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(10.0, 0.0, 0.0);
+
+        // The only gaps are around the ends of the wall at y = 5 and y = -5.
+        let path = find_path_corridor(start, goal, WalledGridProvider {}, 6.0).unwrap();
+
+        assert_eq!(path.last(), Some(&goal));
+        assert!(path.iter().all(|vector| vector.y.abs() <= 6.0));
+
+        assert_eq!(
+            find_path_corridor(start, goal, WalledGridProvider {}, 3.0),
+            None
+        );
+    }
+
+    #[test]
+    fn test_non_finite() {
+        let grid_provider = SimpleGridProvider {};
+        let vector = Vector3::new(1.0, 2.0, 3.0);
+
+        for axis in &[f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            let non_finite = Vector3::new(0.0, *axis, 0.0);
+
+            assert_eq!(find_path(non_finite, vector, &grid_provider), None);
+            assert_eq!(find_path(vector, non_finite, &grid_provider), None);
+
+            let (path, stats) = find_path_with_stats(non_finite, vector, &grid_provider);
+            assert_eq!(path, None);
+            assert_eq!(stats.expanded, 0);
+        }
+    }
+
+    #[test]
+    fn test_direct_path() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+
+        assert_eq!(
+            direct_path(start, Vector3::new(10.0, 3.5, 0.0), SimpleGridProvider {}),
+            Some(vec![start, Vector3::new(10.0, 3.5, 0.0)])
+        );
+        assert_eq!(
+            direct_path(start, Vector3::new(10.0, 0.0, 0.0), WalledGridProvider {}),
+            None
+        );
+        assert_eq!(
+            direct_path(start, Vector3::new(4.0, 3.0, 0.0), WalledGridProvider {}),
+            Some(vec![start, Vector3::new(4.0, 3.0, 0.0)])
+        );
+    }
+
+    #[test]
+    fn test_seeded() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(10.0, 0.0, 0.0);
+
+        let mut pathfinder = Pathfinder::new();
+        let hint = pathfinder
+            .solve(start, goal, WalledGridProvider {})
+            .unwrap();
+        let cold_stats = pathfinder.stats().clone();
+
+        let path = pathfinder.solve_seeded(start, goal, WalledGridProvider {}, &hint);
+
+        assert_eq!(path.as_ref(), Some(&hint));
+        assert!(pathfinder.stats().expanded < cold_stats.expanded);
+        assert!(pathfinder.stats().max_reachable <= cold_stats.max_reachable);
+
+        // Blocked hints fall back to a normal search.
+        let blocked = vec![
+            Vector3::new(1.0, 0.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(3.0, 0.0, 0.0),
+            Vector3::new(4.0, 0.0, 0.0),
+            Vector3::new(5.0, 0.0, 0.0),
+            Vector3::new(6.0, 0.0, 0.0),
+            Vector3::new(7.0, 0.0, 0.0),
+            Vector3::new(8.0, 0.0, 0.0),
+            Vector3::new(9.0, 0.0, 0.0),
+            Vector3::new(10.0, 0.0, 0.0),
+        ];
+
+        let path = pathfinder.solve_seeded(start, goal, WalledGridProvider {}, &blocked);
+
+        assert_eq!(path, Some(hint.clone()));
+        assert_eq!(pathfinder.stats(), &cold_stats);
+
+        assert_eq!(
+            find_path_seeded(start, goal, WalledGridProvider {}, &hint),
+            Some(hint)
+        );
+    }
+
+    /// `WalledGridProvider` moved far away from the world origin.
+    struct FarWalledGridProvider {
+        offset: Vector3<f64>,
+    }
+
+    impl GridProvider64 for FarWalledGridProvider {
+        fn is_obstacle(&self, vector: Vector3<f64>) -> bool {
+            let local = vector - self.offset;
+            (local.x == 5.0 && 5.0 > local.y.abs()) || local.z != 0.0
+        }
+    }
+
+    #[test]
+    fn test_f64_path() {
+        // Beyond 2^24 f32 can't tell neighboring cells apart.
+        let offset = Vector3::new(40_000_001.0, -70_000_003.0, 0.0);
+        let grid_provider = FarWalledGridProvider { offset };
+
+        let start = offset + Vector3::new(0.2, -0.3, 0.0);
+        let goal = offset + Vector3::new(10.0, 0.0, 0.0);
+
+        let path = find_path_f64(start, goal, &grid_provider).unwrap();
+        let expected = find_path(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(10.0, 0.0, 0.0),
+            WalledGridProvider {},
+        )
+        .unwrap();
+
+        assert_eq!(path.len(), expected.len());
+        assert_eq!(path.last(), Some(&goal));
+
+        for (vector, expected) in path.iter().zip(expected.iter()) {
+            assert_eq!(*vector - offset, expected.map(|axis| axis as f64));
+            assert!(!grid_provider.is_obstacle(*vector));
+        }
+    }
+
+    #[test]
+    fn test_neighbor_order() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(10.0, 0.0, 0.0);
+
+        let expected = find_path(start, goal, WalledGridProvider {}).unwrap();
+        let expected_cost = path_cost(&start, &goal, &expected, &WalledGridProvider {}).unwrap();
+
+        for &neighbor_order in &[
+            NeighborOrder::ZInnermost,
+            NeighborOrder::XInnermost,
+            NeighborOrder::Morton,
+        ] {
+            let options = SearchOptions {
+                neighbor_order,
+                ..SearchOptions::default()
+            };
+
+            let path =
+                find_path_with_options(start, goal, WalledGridProvider {}, &options).unwrap();
+            let cost = path_cost(&start, &goal, &path, &WalledGridProvider {}).unwrap();
+
+            assert!((cost - expected_cost).abs() < 1e-4, "{:?}", neighbor_order);
+
+            if neighbor_order == NeighborOrder::default() {
+                assert_eq!(path, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_cache_obstacles() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(10.0, 0.0, 0.0);
+
+        let max_queries_per_cell = |queries: Vec<Vector3<f32>>| {
+            let mut counts = std::collections::HashMap::new();
+
+            for vector in queries {
+                *counts
+                    .entry((vector.x as i32, vector.y as i32, vector.z as i32))
+                    .or_insert(0) += 1;
+            }
+
+            counts.values().copied().max().unwrap()
+        };
+
+        let grid_provider = RecordingProvider::new(WalledGridProvider {});
+        let expected = find_path(start, goal, &grid_provider);
+        assert!(max_queries_per_cell(grid_provider.into_queries()) > 1);
+
+        let options = SearchOptions {
+            cache_obstacles: true,
+            ..SearchOptions::default()
+        };
+
+        let grid_provider = RecordingProvider::new(WalledGridProvider {});
+        let path = find_path_with_options(start, goal, &grid_provider, &options);
+
+        assert_eq!(path, expected);
+        assert_eq!(max_queries_per_cell(grid_provider.into_queries()), 1);
+    }
+
+    #[test]
+    fn test_walled_path() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(10.0, 0.0, 0.0);
+
+        let grid_provider = WalledGridProvider {};
+
+        let path = find_path(start, goal, grid_provider).unwrap();
+        assert_eq!(path.len(), 10);
+
+        // The wall is symmetric, going around it above or below costs the same.
+        // Which side wins the tie depends on the expansion order, with every
+        // node expanded once it's the side below.
+        //
+        // Diagonally down to the wall corner, then diagonally up to the goal.
+        for i in 1..6 {
+            let vec = path.get(i - 1).unwrap();
+
+            assert_eq!(vec.x, 1.0 * i as f32);
+            assert_eq!(vec.y, -(i as f32));
+            assert_eq!(vec.z, 0.0);
+        }
+
+        for i in 1..6 {
+            let vec = path.get(4 + i).unwrap();
+
+            assert_eq!(vec.x, 5.0 + 1.0 * i as f32);
+            assert_eq!(vec.y, -5.0 + 1.0 * i as f32);
+            assert_eq!(vec.z, 0.0);
+        }
+    }
+
+    struct ThinWallGridProvider {}
+
+    impl GridProvider for ThinWallGridProvider {
+        fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+            if vector.y == 0.0 && 5.0 >= vector.x {
+                return true;
+            }
+
+            vector.z != 0.0
+        }
+    }
+
+    #[test]
+    fn test_max_turn_angle() {
+        let start = Vector3::new(3.0, -1.0, 0.0);
+        let goal = Vector3::new(3.0, 1.0, 0.0);
+
+        let path = find_path(start, goal, ThinWallGridProvider {}).unwrap();
+        assert!(max_turn(start, &path) > std::f32::consts::FRAC_PI_4 + 1e-4);
+
+        let options = SearchOptions {
+            max_turn_angle: Some(std::f32::consts::FRAC_PI_4),
+            ..Default::default()
+        };
+
+        let path = find_path_with_options(start, goal, ThinWallGridProvider {}, &options).unwrap();
+        assert_eq!(path.last(), Some(&goal));
+        assert!(max_turn(start, &path) <= std::f32::consts::FRAC_PI_4 + 1e-4);
+    }
+
+    #[test]
+    fn test_bounds() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(100.0, 0.0, 0.0);
+
+        let options = SearchOptions {
+            bounds: Some((Vector3::new(-3.0, -3.0, -3.0), Vector3::new(3.0, 3.0, 3.0))),
+            ..Default::default()
+        };
+
+        let path = find_path_with_options(start, goal, SimpleGridProvider {}, &options);
+        assert_eq!(path, None);
+
+        let goal = Vector3::new(3.0, 0.0, 0.0);
+
+        let path = find_path_with_options(start, goal, SimpleGridProvider {}, &options);
+        assert_eq!(path.map(|path| path.len()), Some(3));
+    }
+
+    #[test]
+    fn test_explored_once() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(100.0, 0.0, 0.0);
+
+        let options = SearchOptions {
+            bounds: Some((Vector3::new(-3.0, -3.0, -3.0), Vector3::new(3.0, 3.0, 3.0))),
+            ..Default::default()
+        };
+
+        // Cells are pushed again whenever a cheaper way to them is found,
+        // but each of the 7 x 7 x 7 cells is expanded only once.
+        let mut pathfinder = Pathfinder::new();
+        let path = pathfinder.solve_with_options(start, goal, SimpleGridProvider {}, &options);
+
+        assert_eq!(path, None);
+        assert!(pathfinder.stats().reopened > 0);
+        assert_eq!(pathfinder.stats().expanded, 7 * 7 * 7);
+    }
+
+    #[test]
+    fn test_turn_penalty() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(10.0, 4.0, 0.0);
+        let grid_provider = FnProvider::new(|vector| vector.z != 0.0);
+
+        let path = find_path(start, goal, &grid_provider).unwrap();
+        let options = SearchOptions::default();
+
+        assert_eq!(
+            find_path_with_options(start, goal, &grid_provider, &options),
+            Some(path.clone())
+        );
+
+        let options = SearchOptions {
+            turn_penalty: 1.0,
+            ..Default::default()
+        };
+
+        let straight_path = find_path_with_options(start, goal, &grid_provider, &options).unwrap();
+
+        assert_eq!(straight_path.last(), Some(&goal));
+        assert!(turns(start, &straight_path) < turns(start, &path));
+    }
+
+    fn turns(start: Vector3<f32>, path: &[Vector3<f32>]) -> usize {
+        let mut points = vec![start];
+        points.extend_from_slice(path);
+
+        points
+            .windows(3)
+            .filter(|window| window[1] - window[0] != window[2] - window[1])
+            .count()
+    }
+
+    fn max_turn(start: Vector3<f32>, path: &[Vector3<f32>]) -> f32 {
+        let mut points = vec![start];
+        points.extend_from_slice(path);
+
+        points
+            .windows(3)
+            .map(|window| (window[1] - window[0]).angle(&(window[2] - window[1])))
+            .fold(0.0, f32::max)
+    }
+
+    struct TestGridProvider<'a> {
+        grid: &'a Grid,
+    }
+
+    impl<'a> GridProvider for TestGridProvider<'a> {
+        fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+            let width = self.grid.width as f32;
+            let height = self.grid.height as f32;
+
+            if vector.x < 0.0 || vector.y < 0.0 || vector.z < 0.0 {
+                return true;
+            }
+
+            if vector.x >= width || vector.y >= width || vector.z >= height {
+                return true;
+            }
+
+            self.grid
+                .is_obstacle(vector.x as u32, vector.y as u32, vector.z as u32)
+        }
+    }
+
+    #[test]
+    fn test_clearance_path() {
+        let mut grid = Grid::new(12, 1);
+
+        for x in 0..12 {
+            grid.set_obstacle(x, 0, 0);
         }
+
+        let start = Vector3::new(1.0, 1.0, 0.0);
+        let goal = Vector3::new(10.0, 1.0, 0.0);
+
+        let path = find_path(start, goal, TestGridProvider { grid: &grid }).unwrap();
+        assert!(path.iter().all(|vector| vector.y == 1.0));
+
+        let distance_field = grid.distance_field();
+        let grid_provider =
+            ClearanceProvider::new(TestGridProvider { grid: &grid }, distance_field, 3.0, 1.0);
+
+        let path = find_path(start, goal, grid_provider).unwrap();
+        assert_eq!(path.last(), Some(&goal));
+        assert!(path.iter().any(|vector| vector.y >= 3.0));
+    }
+
+    #[test]
+    fn test_streaming_path() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(10.0, 0.0, 0.0);
+
+        let mut expanded = Vec::new();
+
+        let path = find_path_streaming(start, goal, SimpleGridProvider {}, |vector| {
+            expanded.push(*vector);
+            true
+        });
+
+        assert_eq!(path, find_path(start, goal, SimpleGridProvider {}));
+        assert_eq!(expanded.first(), Some(&start));
+        assert!(!expanded.contains(&goal));
+    }
+
+    #[test]
+    fn test_streaming_abort() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(10.0, 0.0, 0.0);
+
+        let mut expanded = 0;
+
+        let path = find_path_streaming(start, goal, SimpleGridProvider {}, |_| {
+            expanded += 1;
+            expanded < 3
+        });
+
+        assert_eq!(path, None);
+        assert_eq!(expanded, 3);
     }
 }
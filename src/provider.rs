@@ -1,9 +1,436 @@
-use crate::grid::Grid;
+use crate::distance::DistanceField;
+use crate::grid::{Grid, GridError};
+use crate::transform::{Rounding, WorldToCell, Wrapping};
 use nalgebra::Vector3;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::io::ErrorKind;
+use std::iter::FromIterator;
+use std::ops::Range;
 use std::path::Path;
 
 pub trait GridProvider {
     fn is_obstacle(&self, vector: Vector3<f32>) -> bool;
+
+    /// Checks the integer lattice cell, the search only ever asks for those.
+    /// Integer-native providers can override it to skip the float round-trip.
+    fn is_obstacle_cell(&self, x: i32, y: i32, z: i32) -> bool {
+        self.is_obstacle(Vector3::new(x as f32, y as f32, z as f32))
+    }
+
+    /// Additional cost of moving into the vector on top of the move itself.
+    fn extra_cost(&self, _vector: Vector3<f32>) -> f32 {
+        0.0
+    }
+
+    /// Inclusive box (min, max) of the cells the provider knows about, the search
+    /// never leaves it. `None` by default, the provider is unbounded then.
+    fn bounds(&self) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        None
+    }
+
+    /// Loads whatever the provider needs to answer queries within the box
+    /// up front, e.g. before a search bounded by it. Does nothing by default.
+    fn prefetch(&self, _min: Vector3<f32>, _max: Vector3<f32>) {}
+}
+
+impl<G: GridProvider + ?Sized> GridProvider for &G {
+    fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+        (**self).is_obstacle(vector)
+    }
+
+    fn is_obstacle_cell(&self, x: i32, y: i32, z: i32) -> bool {
+        (**self).is_obstacle_cell(x, y, z)
+    }
+
+    fn extra_cost(&self, vector: Vector3<f32>) -> f32 {
+        (**self).extra_cost(vector)
+    }
+
+    fn bounds(&self) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        (**self).bounds()
+    }
+
+    fn prefetch(&self, min: Vector3<f32>, max: Vector3<f32>) {
+        (**self).prefetch(min, max)
+    }
+}
+
+/// Provider which may not cover the whole world, used as a tier of `TieredProvider`.
+pub trait TierProvider {
+    /// Whether the vector is an obstacle, or `None` if the provider doesn't cover it.
+    fn lookup(&self, vector: Vector3<f32>) -> Option<bool>;
+}
+
+/// Provider queried with `f64` world coordinates by `find_path_f64`,
+/// for worlds too large for `f32` to represent every cell exactly.
+pub trait GridProvider64 {
+    fn is_obstacle(&self, vector: Vector3<f64>) -> bool;
+
+    /// Additional cost of moving into the vector on top of the move itself.
+    fn extra_cost(&self, _vector: Vector3<f64>) -> f32 {
+        0.0
+    }
+}
+
+impl<G: GridProvider64 + ?Sized> GridProvider64 for &G {
+    fn is_obstacle(&self, vector: Vector3<f64>) -> bool {
+        (**self).is_obstacle(vector)
+    }
+
+    fn extra_cost(&self, vector: Vector3<f64>) -> f32 {
+        (**self).extra_cost(vector)
+    }
+}
+
+/// Provider backed by a single grid, world coordinates are the grid cells.
+/// Cells outside of the grid are open air, though the search doesn't leave
+/// the grid as it's the bounds of the provider.
+pub struct SingleGridProvider {
+    grid: Grid,
+}
+
+impl SingleGridProvider {
+    pub fn new(grid: Grid) -> Self {
+        SingleGridProvider { grid }
+    }
+
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+}
+
+impl GridProvider for SingleGridProvider {
+    fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+        self.lookup(vector).unwrap_or(false)
+    }
+
+    fn is_obstacle_cell(&self, x: i32, y: i32, z: i32) -> bool {
+        self.grid.get_cell(x, y, z).unwrap_or(false)
+    }
+
+    fn bounds(&self) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        let last = (self.grid.width as f32 - 1.0, self.grid.height as f32 - 1.0);
+
+        Some((Vector3::zeros(), Vector3::new(last.0, last.0, last.1)))
+    }
+}
+
+impl TierProvider for SingleGridProvider {
+    fn lookup(&self, vector: Vector3<f32>) -> Option<bool> {
+        if !(vector.x.is_finite() && vector.y.is_finite() && vector.z.is_finite()) {
+            return None;
+        }
+
+        let cell = vector.map(|axis| axis.round() as i32);
+        self.grid.get_cell(cell.x, cell.y, cell.z)
+    }
+}
+
+/// Provider storing only the obstacle cells, world coordinates are the cells.
+/// Uses memory proportional to the obstacles rather than to the volume,
+/// so mostly empty maps take far less than a dense `Grid`.
+#[derive(Debug, Clone, Default)]
+pub struct SparseGridProvider {
+    obstacles: HashSet<(i32, i32, i32)>,
+}
+
+impl SparseGridProvider {
+    pub fn new() -> Self {
+        SparseGridProvider::default()
+    }
+
+    /// Collects the obstacles of the dense grid.
+    pub fn from_grid(grid: &Grid) -> Self {
+        grid.obstacles()
+            .map(|cell| (cell.x as i32, cell.y as i32, cell.z as i32))
+            .collect()
+    }
+
+    /// Marks the cell as an obstacle, returns whether it was open before.
+    pub fn insert(&mut self, x: i32, y: i32, z: i32) -> bool {
+        self.obstacles.insert((x, y, z))
+    }
+
+    /// Clears the obstacle, returns whether the cell was an obstacle.
+    pub fn remove(&mut self, x: i32, y: i32, z: i32) -> bool {
+        self.obstacles.remove(&(x, y, z))
+    }
+
+    /// Returns the number of obstacle cells.
+    pub fn len(&self) -> usize {
+        self.obstacles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.obstacles.is_empty()
+    }
+}
+
+impl FromIterator<(i32, i32, i32)> for SparseGridProvider {
+    fn from_iter<I: IntoIterator<Item = (i32, i32, i32)>>(iter: I) -> Self {
+        SparseGridProvider {
+            obstacles: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl GridProvider for SparseGridProvider {
+    fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+        if !(vector.x.is_finite() && vector.y.is_finite() && vector.z.is_finite()) {
+            return false;
+        }
+
+        let cell = vector.map(|axis| axis.round() as i32);
+        self.is_obstacle_cell(cell.x, cell.y, cell.z)
+    }
+
+    fn is_obstacle_cell(&self, x: i32, y: i32, z: i32) -> bool {
+        self.obstacles.contains(&(x, y, z))
+    }
+}
+
+/// Edits on top of a static base provider, e.g. for obstacles moving every tick.
+/// Added and removed cells override the base, which is never modified.
+pub struct DynamicGridProvider<P: GridProvider> {
+    base: P,
+    added: HashSet<(i32, i32, i32)>,
+    removed: HashSet<(i32, i32, i32)>,
+}
+
+impl<P: GridProvider> DynamicGridProvider<P> {
+    pub fn new(base: P) -> Self {
+        DynamicGridProvider {
+            base,
+            added: HashSet::new(),
+            removed: HashSet::new(),
+        }
+    }
+
+    pub fn base(&self) -> &P {
+        &self.base
+    }
+
+    /// Makes the cell an obstacle whatever the base says.
+    pub fn add_obstacle(&mut self, cell: (i32, i32, i32)) {
+        self.removed.remove(&cell);
+        self.added.insert(cell);
+    }
+
+    /// Makes the cell open whatever the base says.
+    pub fn remove_obstacle(&mut self, cell: (i32, i32, i32)) {
+        self.added.remove(&cell);
+        self.removed.insert(cell);
+    }
+
+    /// Drops all edits, the base shows through again.
+    pub fn clear(&mut self) {
+        self.added.clear();
+        self.removed.clear();
+    }
+
+    /// The edited answer for the cell, `None` if the cell wasn't edited.
+    fn edit(&self, cell: &(i32, i32, i32)) -> Option<bool> {
+        if self.added.contains(cell) {
+            Some(true)
+        } else if self.removed.contains(cell) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+}
+
+impl<P: GridProvider> GridProvider for DynamicGridProvider<P> {
+    fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+        if !(vector.x.is_finite() && vector.y.is_finite() && vector.z.is_finite()) {
+            return self.base.is_obstacle(vector);
+        }
+
+        let cell = vector.map(|axis| axis.round() as i32);
+
+        self.edit(&(cell.x, cell.y, cell.z))
+            .unwrap_or_else(|| self.base.is_obstacle(vector))
+    }
+
+    fn is_obstacle_cell(&self, x: i32, y: i32, z: i32) -> bool {
+        self.edit(&(x, y, z))
+            .unwrap_or_else(|| self.base.is_obstacle_cell(x, y, z))
+    }
+
+    fn extra_cost(&self, vector: Vector3<f32>) -> f32 {
+        self.base.extra_cost(vector)
+    }
+
+    fn bounds(&self) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        self.base.bounds()
+    }
+
+    fn prefetch(&self, min: Vector3<f32>, max: Vector3<f32>) {
+        self.base.prefetch(min, max)
+    }
+}
+
+/// Analytic obstacle shape of `GeometryProvider`, boundaries are solid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    Sphere {
+        center: Vector3<f32>,
+        radius: f32,
+    },
+    /// Axis-aligned box.
+    Aabb {
+        min: Vector3<f32>,
+        max: Vector3<f32>,
+    },
+}
+
+impl Shape {
+    pub fn contains(&self, point: Vector3<f32>) -> bool {
+        match self {
+            Shape::Sphere { center, radius } => (point - center).norm_squared() <= radius * radius,
+            Shape::Aabb { min, max } => {
+                (0..3).all(|axis| point[axis] >= min[axis] && point[axis] <= max[axis])
+            }
+        }
+    }
+}
+
+/// Obstacles kept as analytic shapes instead of rasterized cells. A cell is
+/// an obstacle when its center, the rounded vector, is inside of any shape.
+/// Shapes can be moved between searches by editing them through `shapes_mut`.
+#[derive(Debug, Clone, Default)]
+pub struct GeometryProvider {
+    shapes: Vec<Shape>,
+}
+
+impl GeometryProvider {
+    pub fn new(shapes: Vec<Shape>) -> Self {
+        GeometryProvider { shapes }
+    }
+
+    pub fn push(&mut self, shape: Shape) {
+        self.shapes.push(shape);
+    }
+
+    pub fn shapes(&self) -> &[Shape] {
+        &self.shapes
+    }
+
+    pub fn shapes_mut(&mut self) -> &mut Vec<Shape> {
+        &mut self.shapes
+    }
+}
+
+impl GridProvider for GeometryProvider {
+    fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+        let center = vector.map(|axis| axis.round());
+        self.shapes.iter().any(|shape| shape.contains(center))
+    }
+}
+
+/// Solid space below the floor and above the ceiling, stores no cells.
+pub struct PlaneProvider {
+    floor: f32,
+    ceiling: f32,
+}
+
+impl PlaneProvider {
+    pub fn new(floor: f32, ceiling: f32) -> Self {
+        PlaneProvider { floor, ceiling }
+    }
+
+    /// Provider with only the floor, there is no ceiling.
+    pub fn floor(floor: f32) -> Self {
+        PlaneProvider::new(floor, f32::INFINITY)
+    }
+}
+
+impl GridProvider for PlaneProvider {
+    fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+        vector.z < self.floor || vector.z > self.ceiling
+    }
+
+    fn is_obstacle_cell(&self, _x: i32, _y: i32, z: i32) -> bool {
+        (z as f32) < self.floor || (z as f32) > self.ceiling
+    }
+}
+
+/// Union of child providers: a vector is an obstacle if any child says so,
+/// extra costs of all children add up.
+pub struct CompositeGridProvider {
+    providers: Vec<Box<dyn GridProvider>>,
+}
+
+impl CompositeGridProvider {
+    pub fn new(providers: Vec<Box<dyn GridProvider>>) -> Self {
+        CompositeGridProvider { providers }
+    }
+}
+
+impl GridProvider for CompositeGridProvider {
+    fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+        self.providers
+            .iter()
+            .any(|provider| provider.is_obstacle(vector))
+    }
+
+    fn is_obstacle_cell(&self, x: i32, y: i32, z: i32) -> bool {
+        self.providers
+            .iter()
+            .any(|provider| provider.is_obstacle_cell(x, y, z))
+    }
+
+    fn extra_cost(&self, vector: Vector3<f32>) -> f32 {
+        self.providers
+            .iter()
+            .map(|provider| provider.extra_cost(vector))
+            .sum()
+    }
+
+    /// Union of the bounds of the children, unbounded if any of them is.
+    fn bounds(&self) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        union_bounds(self.providers.iter().map(|provider| provider.bounds()))
+    }
+
+    fn prefetch(&self, min: Vector3<f32>, max: Vector3<f32>) {
+        for provider in &self.providers {
+            provider.prefetch(min, max);
+        }
+    }
+}
+
+/// Smallest box containing all of the boxes, `None` if any of them
+/// is unbounded or there are none.
+fn union_bounds<I>(bounds: I) -> Option<(Vector3<f32>, Vector3<f32>)>
+where
+    I: IntoIterator<Item = Option<(Vector3<f32>, Vector3<f32>)>>,
+{
+    let mut bounds = bounds.into_iter();
+    let first = bounds.next()??;
+
+    bounds.try_fold(first, |(min, max), bounds| {
+        let (other_min, other_max) = bounds?;
+        Some((min.inf(&other_min), max.sup(&other_max)))
+    })
+}
+
+/// Provider backed by a closure, handy for analytic obstacles.
+/// `FnProvider::new(|vector| vector.z < 0.0)`
+pub struct FnProvider<F: Fn(Vector3<f32>) -> bool> {
+    is_obstacle_fn: F,
+}
+
+impl<F: Fn(Vector3<f32>) -> bool> FnProvider<F> {
+    pub fn new(is_obstacle_fn: F) -> Self {
+        FnProvider { is_obstacle_fn }
+    }
+}
+
+impl<F: Fn(Vector3<f32>) -> bool> GridProvider for FnProvider<F> {
+    fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+        (self.is_obstacle_fn)(vector)
+    }
 }
 
 pub struct FolderGridProvider<'a, ATG, GTA, GFN>
@@ -14,13 +441,19 @@ where
 {
     /// Folder where grid files located.
     grid_folder_path: &'a Path,
-    /// Function to convert axis to grid id.
-    axis_to_grid_id_fn: ATG,
-    /// Function to convert grid id to axis.
-    grid_id_to_axis_fn: GTA,
+    /// Converts world coordinates to grid cells.
+    world_to_cell: WorldToCell<ATG, GTA>,
     /// Function to format grid format name.
     /// 1, 1 => grid_1_1.dat
     grid_file_name_fn: GFN,
+    /// Grids loaded so far by their grid id, `None` if the grid file is missing.
+    grids: RefCell<HashMap<(u32, u32), Option<Grid>>>,
+    /// World box covered by the grid files, see `with_bounds`.
+    bounds: Option<(Vector3<f32>, Vector3<f32>)>,
+    /// Whether the ceiling reads the top layer, see `with_inclusive_top`.
+    inclusive_top: bool,
+    /// Whether cells without a tile are obstacles, see `with_unknown_is_solid`.
+    unknown_is_solid: bool,
 }
 
 impl<'a, ATG, GTA, GFN> FolderGridProvider<'a, ATG, GTA, GFN>
@@ -37,9 +470,81 @@ where
     ) -> Self {
         FolderGridProvider {
             grid_folder_path,
-            axis_to_grid_id_fn,
-            grid_id_to_axis_fn,
+            world_to_cell: WorldToCell::new(axis_to_grid_id_fn, grid_id_to_axis_fn),
             grid_file_name_fn,
+            grids: RefCell::new(HashMap::new()),
+            bounds: None,
+            inclusive_top: false,
+            unknown_is_solid: false,
+        }
+    }
+
+    /// Sets how world coordinates snap to cells, `Rounding::Round` by default.
+    pub fn with_rounding(mut self, rounding: Rounding) -> Self {
+        self.world_to_cell = self.world_to_cell.with_rounding(rounding);
+        self
+    }
+
+    /// Sets whether vectors rounding to the cell just above the top layer,
+    /// e.g. exactly at the grid height, read the top layer. By default
+    /// they are above the grid and open.
+    pub fn with_inclusive_top(mut self, inclusive_top: bool) -> Self {
+        self.inclusive_top = inclusive_top;
+        self
+    }
+
+    /// Sets whether cells of tiles without a grid file, or whose file can't be
+    /// loaded, are obstacles instead of open air. The safe choice when flying
+    /// into unmapped territory. Cells above or below a loaded grid stay open.
+    pub fn with_unknown_is_solid(mut self, unknown_is_solid: bool) -> Self {
+        self.unknown_is_solid = unknown_is_solid;
+        self
+    }
+
+    /// Sets the world box covered by the grid files. The folder isn't listed,
+    /// so without it the provider is unbounded.
+    pub fn with_bounds(mut self, min: Vector3<f32>, max: Vector3<f32>) -> Self {
+        self.bounds = Some((min, max));
+        self
+    }
+
+    /// Drops every loaded grid, e.g. after the grid files changed.
+    /// Grids are loaded again when they're needed.
+    pub fn clear_cache(&self) {
+        self.grids.borrow_mut().clear();
+    }
+
+    /// Drops the grid, so it's loaded again when it's needed.
+    pub fn evict(&self, grid_id: (u32, u32)) {
+        self.grids.borrow_mut().remove(&grid_id);
+    }
+
+    /// Loads the grid unless it was loaded before. Grids stay loaded until
+    /// evicted, so do missing ones. Grids which fail to load otherwise,
+    /// e.g. corrupted or unreadable files, are retried on the next load.
+    fn load(&self, grid_id: (u32, u32)) {
+        if self.grids.borrow().contains_key(&grid_id) {
+            return;
+        }
+
+        let grid_file_name = (self.grid_file_name_fn)(grid_id.0, grid_id.1);
+
+        let grid = match Grid::import(self.grid_folder_path.join(&grid_file_name)) {
+            Ok(grid) => Some(grid),
+            Err(GridError::Io(error)) if error.kind() == ErrorKind::NotFound => None,
+            Err(_) => return,
+        };
+
+        self.grids.borrow_mut().insert(grid_id, grid);
+    }
+
+    /// Whether the grid of the tile the vector falls into is loaded.
+    fn has_grid(&self, vector: Vector3<f32>) -> bool {
+        match self.world_to_cell.checked_cell(vector) {
+            Some((grid_x, grid_y, ..)) => {
+                matches!(self.grids.borrow().get(&(grid_x, grid_y)), Some(Some(_)))
+            }
+            None => false,
         }
     }
 }
@@ -51,47 +556,1341 @@ where
     GFN: Fn(u32, u32) -> String,
 {
     fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
-        let grid_x = (self.axis_to_grid_id_fn)(vector.x);
-        let grid_y = (self.axis_to_grid_id_fn)(vector.y);
-        let grid_file_name = (self.grid_file_name_fn)(grid_x, grid_y);
-        let grid_path = self.grid_folder_path.join(&grid_file_name);
+        self.lookup(vector)
+            .unwrap_or_else(|| self.unknown_is_solid && !self.has_grid(vector))
+    }
 
-        match Grid::import(grid_path) {
-            Ok(grid) if (vector.z >= 0.0 && vector.z <= grid.height as f32) => {
-                let grid_start_x = (self.grid_id_to_axis_fn)(grid_x);
-                let grid_start_y = (self.grid_id_to_axis_fn)(grid_y);
+    fn bounds(&self) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        self.bounds
+    }
 
-                let x = (grid_start_x - vector.x).round() as u32;
-                let y = (grid_start_y - vector.y).round() as u32;
-                let z = vector.z.round() as u32;
+    /// Loads every grid the box overlaps, one after another.
+    fn prefetch(&self, min: Vector3<f32>, max: Vector3<f32>) {
+        let (min_x, min_y) = self.world_to_cell.grid_id(min);
+        let (max_x, max_y) = self.world_to_cell.grid_id(max);
 
-                grid.is_obstacle(x, y, z)
+        // Grid ids may decrease along the world axes.
+        for grid_x in min_x.min(max_x)..=min_x.max(max_x) {
+            for grid_y in min_y.min(max_y)..=min_y.max(max_y) {
+                self.load((grid_x, grid_y));
             }
-            _ => false,
         }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::provider::{FolderGridProvider, GridProvider};
-    use nalgebra::Vector3;
-    use std::path::Path;
+impl<'a, ATG, GTA, GFN> TierProvider for FolderGridProvider<'a, ATG, GTA, GFN>
+where
+    ATG: Fn(f32) -> u32,
+    GTA: Fn(u32) -> f32,
+    GFN: Fn(u32, u32) -> String,
+{
+    fn lookup(&self, vector: Vector3<f32>) -> Option<bool> {
+        // Out of range transforms aren't covered, there is no grid to load.
+        let (grid_x, grid_y, x, y, z) = self.world_to_cell.checked_cell(vector)?;
 
-    #[test]
-    fn test_is_obstacle() {
-        let axis_to_grid_id_fn = |axis: f32| (32.0 - (axis / 533.33)).floor() as u32;
-        let grid_id_to_axis_fn = |grid_id| (32.0 - grid_id as f32) * 533.3;
-        let grid_file_name_fn = |x, y| format!("grid_{}_{}.dat", x, y);
-        let grid_folder_path = Path::new("test/map_1718");
+        self.load((grid_x, grid_y));
 
-        let grid_provider = FolderGridProvider::new(
-            grid_folder_path,
-            axis_to_grid_id_fn,
-            grid_id_to_axis_fn,
-            grid_file_name_fn,
-        );
+        match self.grids.borrow().get(&(grid_x, grid_y)) {
+            Some(Some(grid)) if vector.z >= 0.0 => {
+                let z = top_clamped(grid, x, y, z, self.inclusive_top)?;
+                Some(grid.is_obstacle(x, y, z))
+            }
+            _ => None,
+        }
+    }
+}
 
-        assert!(grid_provider.is_obstacle(Vector3::new(-1604.0, 1163.0, 111.0)));
+pub struct MapGridProvider<ATG, GTA>
+where
+    ATG: Fn(f32) -> u32,
+    GTA: Fn(u32) -> f32,
+{
+    /// Loaded grids by their grid id.
+    grids: HashMap<(u32, u32), Grid>,
+    /// Converts world coordinates to grid cells.
+    world_to_cell: WorldToCell<ATG, GTA>,
+    /// Whether the ceiling reads the top layer, see `with_inclusive_top`.
+    inclusive_top: bool,
+}
+
+impl<ATG, GTA> MapGridProvider<ATG, GTA>
+where
+    ATG: Fn(f32) -> u32,
+    GTA: Fn(u32) -> f32,
+{
+    pub fn new(
+        grids: HashMap<(u32, u32), Grid>,
+        axis_to_grid_id_fn: ATG,
+        grid_id_to_axis_fn: GTA,
+    ) -> Self {
+        MapGridProvider {
+            grids,
+            world_to_cell: WorldToCell::new(axis_to_grid_id_fn, grid_id_to_axis_fn),
+            inclusive_top: false,
+        }
+    }
+
+    /// Sets how world coordinates snap to cells, `Rounding::Round` by default.
+    pub fn with_rounding(mut self, rounding: Rounding) -> Self {
+        self.world_to_cell = self.world_to_cell.with_rounding(rounding);
+        self
+    }
+
+    /// Sets whether the ceiling reads the top layer, like
+    /// `FolderGridProvider::with_inclusive_top`.
+    pub fn with_inclusive_top(mut self, inclusive_top: bool) -> Self {
+        self.inclusive_top = inclusive_top;
+        self
+    }
+}
+
+impl<ATG, GTA> GridProvider for MapGridProvider<ATG, GTA>
+where
+    ATG: Fn(f32) -> u32,
+    GTA: Fn(u32) -> f32,
+{
+    fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+        self.lookup(vector).unwrap_or(false)
+    }
+}
+
+impl<ATG, GTA> TierProvider for MapGridProvider<ATG, GTA>
+where
+    ATG: Fn(f32) -> u32,
+    GTA: Fn(u32) -> f32,
+{
+    fn lookup(&self, vector: Vector3<f32>) -> Option<bool> {
+        let (grid_x, grid_y, x, y, z) = self.world_to_cell.checked_cell(vector)?;
+
+        match self.grids.get(&(grid_x, grid_y)) {
+            Some(grid) if vector.z >= 0.0 => {
+                let z = top_clamped(grid, x, y, z, self.inclusive_top)?;
+                Some(grid.is_obstacle(x, y, z))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Altitude of the cell within the grid, `None` outside of it. The cell
+/// just above the top layer is clamped into it when the top is inclusive.
+fn top_clamped(grid: &Grid, x: u32, y: u32, z: u32, inclusive_top: bool) -> Option<u32> {
+    if x >= grid.width || y >= grid.width {
+        return None;
+    }
+
+    if z < grid.height {
+        Some(z)
+    } else if inclusive_top && z == grid.height && grid.height > 0 {
+        Some(z - 1)
+    } else {
+        None
+    }
+}
+
+/// Penalizes cells closer to obstacles than the desired clearance,
+/// so paths prefer to stay away from walls when there is room.
+pub struct ClearanceProvider<P: GridProvider> {
+    inner: P,
+    /// Distance field indexed by rounded vector coordinates.
+    distance_field: DistanceField,
+    /// Desired distance to the nearest obstacle.
+    clearance: f32,
+    /// Cost per unit of missing clearance.
+    weight: f32,
+}
+
+impl<P: GridProvider> ClearanceProvider<P> {
+    pub fn new(inner: P, distance_field: DistanceField, clearance: f32, weight: f32) -> Self {
+        ClearanceProvider {
+            inner,
+            distance_field,
+            clearance,
+            weight,
+        }
+    }
+
+    fn distance(&self, vector: Vector3<f32>) -> f32 {
+        let (x, y, z) = (vector.x.round(), vector.y.round(), vector.z.round());
+
+        if x < 0.0 || y < 0.0 || z < 0.0 {
+            return f32::INFINITY;
+        }
+
+        let width = self.distance_field.width as f32;
+        let height = self.distance_field.height as f32;
+
+        if x >= width || y >= width || z >= height {
+            return f32::INFINITY;
+        }
+
+        self.distance_field
+            .distance_at(x as u32, y as u32, z as u32)
+    }
+}
+
+impl<P: GridProvider> GridProvider for ClearanceProvider<P> {
+    fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+        self.inner.is_obstacle(vector)
+    }
+
+    fn is_obstacle_cell(&self, x: i32, y: i32, z: i32) -> bool {
+        self.inner.is_obstacle_cell(x, y, z)
+    }
+
+    fn extra_cost(&self, vector: Vector3<f32>) -> f32 {
+        let missing_clearance = (self.clearance - self.distance(vector)).max(0.0);
+        self.inner.extra_cost(vector) + self.weight * missing_clearance
+    }
+
+    /// Bounds of the inner provider within the distance field,
+    /// unbounded if the inner provider is.
+    fn bounds(&self) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        let (min, max) = self.inner.bounds()?;
+
+        let width = self.distance_field.width as f32 - 1.0;
+        let height = self.distance_field.height as f32 - 1.0;
+
+        Some((
+            min.sup(&Vector3::zeros()),
+            max.inf(&Vector3::new(width, width, height)),
+        ))
+    }
+
+    fn prefetch(&self, min: Vector3<f32>, max: Vector3<f32>) {
+        self.inner.prefetch(min, max)
+    }
+}
+
+/// Places the inner provider at the offset in the world.
+pub struct TranslatedProvider<P: GridProvider> {
+    inner: P,
+    offset: Vector3<f32>,
+}
+
+impl<P: GridProvider> TranslatedProvider<P> {
+    pub fn new(inner: P, offset: Vector3<f32>) -> Self {
+        TranslatedProvider { inner, offset }
+    }
+}
+
+impl<P: GridProvider> GridProvider for TranslatedProvider<P> {
+    fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+        self.inner.is_obstacle(vector - self.offset)
+    }
+
+    fn extra_cost(&self, vector: Vector3<f32>) -> f32 {
+        self.inner.extra_cost(vector - self.offset)
+    }
+
+    fn bounds(&self) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        self.inner
+            .bounds()
+            .map(|(min, max)| (min + self.offset, max + self.offset))
+    }
+
+    fn prefetch(&self, min: Vector3<f32>, max: Vector3<f32>) {
+        self.inner.prefetch(min - self.offset, max - self.offset)
+    }
+}
+
+/// Makes the inner provider repeat along the wrapping axes,
+/// every query is wrapped into the world before delegating.
+pub struct WrappingProvider<P: GridProvider> {
+    inner: P,
+    wrapping: Wrapping,
+}
+
+impl<P: GridProvider> WrappingProvider<P> {
+    pub fn new(inner: P, wrapping: Wrapping) -> Self {
+        WrappingProvider { inner, wrapping }
+    }
+}
+
+impl<P: GridProvider> GridProvider for WrappingProvider<P> {
+    fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+        self.inner.is_obstacle(self.wrapping.wrap(vector))
+    }
+
+    fn extra_cost(&self, vector: Vector3<f32>) -> f32 {
+        self.inner.extra_cost(self.wrapping.wrap(vector))
+    }
+
+    /// Bounds of the inner provider, unbounded along the wrapping axes.
+    fn bounds(&self) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        let (mut min, mut max) = self.inner.bounds()?;
+
+        if self.wrapping.topology.wraps_x() {
+            min.x = f32::NEG_INFINITY;
+            max.x = f32::INFINITY;
+        }
+
+        if self.wrapping.topology.wraps_y() {
+            min.y = f32::NEG_INFINITY;
+            max.y = f32::INFINITY;
+        }
+
+        Some((min, max))
+    }
+}
+
+/// Multiplies query coordinates by the scale before delegating,
+/// e.g. a scale of 0.25 queries a grid with 4 meter cells.
+pub struct ScalingProvider<P: GridProvider> {
+    inner: P,
+    scale: f32,
+}
+
+impl<P: GridProvider> ScalingProvider<P> {
+    pub fn new(inner: P, scale: f32) -> Self {
+        ScalingProvider { inner, scale }
+    }
+}
+
+impl<P: GridProvider> GridProvider for ScalingProvider<P> {
+    fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+        self.inner.is_obstacle(vector * self.scale)
+    }
+
+    fn extra_cost(&self, vector: Vector3<f32>) -> f32 {
+        self.inner.extra_cost(vector * self.scale)
+    }
+
+    fn bounds(&self) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        let (min, max) = self.inner.bounds()?;
+        let (min, max) = (min / self.scale, max / self.scale);
+
+        // A negative scale mirrors the box.
+        Some((min.inf(&max), min.sup(&max)))
+    }
+
+    fn prefetch(&self, min: Vector3<f32>, max: Vector3<f32>) {
+        let (min, max) = (min * self.scale, max * self.scale);
+        self.inner.prefetch(min.inf(&max), min.sup(&max))
+    }
+}
+
+/// Records every obstacle query before forwarding it to the inner provider,
+/// useful to inspect the access pattern of a search.
+pub struct RecordingProvider<P: GridProvider> {
+    inner: P,
+    queries: RefCell<Vec<Vector3<f32>>>,
+}
+
+impl<P: GridProvider> RecordingProvider<P> {
+    pub fn new(inner: P) -> Self {
+        RecordingProvider {
+            inner,
+            queries: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Returns a copy of the queried vectors in the order they were asked.
+    pub fn queries(&self) -> Vec<Vector3<f32>> {
+        self.queries.borrow().clone()
+    }
+
+    /// Consumes the provider and returns the queried vectors.
+    pub fn into_queries(self) -> Vec<Vector3<f32>> {
+        self.queries.into_inner()
+    }
+}
+
+impl<P: GridProvider> GridProvider for RecordingProvider<P> {
+    fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+        self.queries.borrow_mut().push(vector);
+        self.inner.is_obstacle(vector)
+    }
+
+    fn is_obstacle_cell(&self, x: i32, y: i32, z: i32) -> bool {
+        self.queries
+            .borrow_mut()
+            .push(Vector3::new(x as f32, y as f32, z as f32));
+        self.inner.is_obstacle_cell(x, y, z)
+    }
+
+    fn extra_cost(&self, vector: Vector3<f32>) -> f32 {
+        self.inner.extra_cost(vector)
+    }
+
+    fn bounds(&self) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        self.inner.bounds()
+    }
+
+    fn prefetch(&self, min: Vector3<f32>, max: Vector3<f32>) {
+        self.inner.prefetch(min, max)
+    }
+}
+
+/// Adds the restriction provider on top of the base one while the current time
+/// is inside one of the scheduled windows, e.g. temporary no-fly zones.
+/// Paths aren't time-parameterized, so the time is set once per search.
+pub struct TimedProvider<P: GridProvider, R: GridProvider> {
+    base: P,
+    restriction: R,
+    /// Windows the restriction is active in.
+    schedule: Vec<Range<f32>>,
+    time: f32,
+}
+
+impl<P: GridProvider, R: GridProvider> TimedProvider<P, R> {
+    pub fn new(base: P, restriction: R, schedule: Vec<Range<f32>>, time: f32) -> Self {
+        TimedProvider {
+            base,
+            restriction,
+            schedule,
+            time,
+        }
+    }
+
+    pub fn set_time(&mut self, time: f32) {
+        self.time = time;
+    }
+
+    /// Whether the restriction is active at the current time.
+    pub fn is_active(&self) -> bool {
+        self.schedule
+            .iter()
+            .any(|window| window.contains(&self.time))
+    }
+}
+
+impl<P: GridProvider, R: GridProvider> GridProvider for TimedProvider<P, R> {
+    fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+        self.base.is_obstacle(vector) || (self.is_active() && self.restriction.is_obstacle(vector))
+    }
+
+    fn is_obstacle_cell(&self, x: i32, y: i32, z: i32) -> bool {
+        self.base.is_obstacle_cell(x, y, z)
+            || (self.is_active() && self.restriction.is_obstacle_cell(x, y, z))
+    }
+
+    fn extra_cost(&self, vector: Vector3<f32>) -> f32 {
+        if self.is_active() {
+            self.base.extra_cost(vector) + self.restriction.extra_cost(vector)
+        } else {
+            self.base.extra_cost(vector)
+        }
+    }
+
+    /// Bounds of the base provider, the restriction only adds obstacles.
+    fn bounds(&self) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        self.base.bounds()
+    }
+
+    fn prefetch(&self, min: Vector3<f32>, max: Vector3<f32>) {
+        self.base.prefetch(min, max);
+
+        if self.is_active() {
+            self.restriction.prefetch(min, max);
+        }
+    }
+}
+
+/// Consults the tiers in order and answers with the first one covering
+/// the vector, e.g. a fast in-memory tier in front of a slow disk tier.
+/// Vectors no tier covers are open air.
+pub struct TieredProvider {
+    tiers: Vec<Box<dyn TierProvider>>,
+    /// Whether answers of the tiers are cached.
+    promotion: bool,
+    /// Cached answers of the tiers for lattice cells, consulted before any tier.
+    promoted: RefCell<HashMap<(i32, i32, i32), bool>>,
+}
+
+impl TieredProvider {
+    pub fn new(tiers: Vec<Box<dyn TierProvider>>) -> Self {
+        TieredProvider {
+            tiers,
+            promotion: false,
+            promoted: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Caches answers of the tiers for lattice cells in memory, so every cell
+    /// hits the tiers once. Other vectors always go to the tiers.
+    pub fn with_promotion(mut self) -> Self {
+        self.promotion = true;
+        self
+    }
+}
+
+impl GridProvider for TieredProvider {
+    fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+        // Only lattice vectors are promoted, tiers may resolve other vectors
+        // to cells in their own way.
+        let cell = vector.map(|axis| axis as i32);
+
+        if cell.map(|axis| axis as f32) == vector {
+            self.is_obstacle_cell(cell.x, cell.y, cell.z)
+        } else {
+            self.tiers
+                .iter()
+                .find_map(|tier| tier.lookup(vector))
+                .unwrap_or(false)
+        }
+    }
+
+    fn is_obstacle_cell(&self, x: i32, y: i32, z: i32) -> bool {
+        let key = (x, y, z);
+
+        if let Some(is_obstacle) = self.promoted.borrow().get(&key) {
+            return *is_obstacle;
+        }
+
+        let vector = Vector3::new(x as f32, y as f32, z as f32);
+        let answer = self.tiers.iter().find_map(|tier| tier.lookup(vector));
+
+        if self.promotion {
+            if let Some(is_obstacle) = answer {
+                self.promoted.borrow_mut().insert(key, is_obstacle);
+            }
+        }
+
+        answer.unwrap_or(false)
+    }
+}
+
+/// Selects among child providers by altitude band.
+/// Altitudes not covered by any band are open air.
+///
+/// The search always moves in world units and estimates the remaining
+/// distance in world units, so the heuristic stays consistent even when
+/// layers have different cell sizes. A coarse layer (see `ScalingProvider`)
+/// just answers the same value for several neighboring world cells.
+pub struct LayeredProvider {
+    layers: Vec<(Range<f32>, Box<dyn GridProvider>)>,
+}
+
+impl LayeredProvider {
+    pub fn new(layers: Vec<(Range<f32>, Box<dyn GridProvider>)>) -> Self {
+        LayeredProvider { layers }
+    }
+
+    fn layer(&self, vector: &Vector3<f32>) -> Option<&dyn GridProvider> {
+        self.layers
+            .iter()
+            .find(|(band, _)| band.contains(&vector.z))
+            .map(|(_, provider)| provider.as_ref())
+    }
+}
+
+impl GridProvider for LayeredProvider {
+    fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+        match self.layer(&vector) {
+            Some(provider) => provider.is_obstacle(vector),
+            None => false,
+        }
+    }
+
+    fn extra_cost(&self, vector: Vector3<f32>) -> f32 {
+        match self.layer(&vector) {
+            Some(provider) => provider.extra_cost(vector),
+            None => 0.0,
+        }
+    }
+
+    /// Union of the bounds of the layers, unbounded if any of them is.
+    fn bounds(&self) -> Option<(Vector3<f32>, Vector3<f32>)> {
+        union_bounds(self.layers.iter().map(|(_, provider)| provider.bounds()))
+    }
+
+    /// Prefetches the layers whose band overlaps the box.
+    fn prefetch(&self, min: Vector3<f32>, max: Vector3<f32>) {
+        for (band, provider) in &self.layers {
+            if band.start <= max.z && min.z < band.end {
+                provider.prefetch(min, max);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::distance::DistanceField;
+    use crate::grid::Grid;
+    use crate::pathfinding::{
+        find_path, find_path_with_cost, find_path_with_options, SearchOptions,
+    };
+    use crate::provider::{
+        ClearanceProvider, CompositeGridProvider, DynamicGridProvider, FnProvider,
+        FolderGridProvider, GeometryProvider, GridProvider, LayeredProvider, MapGridProvider,
+        PlaneProvider, RecordingProvider, ScalingProvider, Shape, SingleGridProvider,
+        SparseGridProvider, TierProvider, TieredProvider, TimedProvider, TranslatedProvider,
+    };
+    use crate::transform::{cell_to_world, world_to_cell, Rounding};
+    use nalgebra::Vector3;
+    use std::cell::Cell;
+    use std::collections::HashMap;
+    use std::path::Path;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_sparse_grid_provider() {
+        let mut grid = Grid::new(12, 4);
+
+        for y in 0..10 {
+            grid.set_obstacle(5, y, 0);
+        }
+
+        grid.set_obstacle(0, 0, 3);
+        grid.set_obstacle(11, 11, 1);
+
+        let sparse = SparseGridProvider::from_grid(&grid);
+        let dense = SingleGridProvider::new(grid);
+
+        assert_eq!(sparse.len(), 12);
+
+        for z in -1..5 {
+            for y in -1..13 {
+                for x in -1..13 {
+                    assert_eq!(
+                        sparse.is_obstacle_cell(x, y, z),
+                        dense.is_obstacle_cell(x, y, z),
+                        "Providers differ at x: {}, y: {}, z: {}",
+                        x,
+                        y,
+                        z
+                    );
+
+                    let vector = Vector3::new(x as f32 + 0.3, y as f32 - 0.2, z as f32);
+                    assert_eq!(sparse.is_obstacle(vector), dense.is_obstacle(vector));
+                }
+            }
+        }
+
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(10.0, 0.0, 0.0);
+
+        // The sparse provider is unbounded, the dense one stays within the grid.
+        let options = SearchOptions {
+            bounds: dense.bounds(),
+            ..SearchOptions::default()
+        };
+
+        assert_eq!(
+            find_path_with_options(start, goal, &sparse, &options),
+            find_path(start, goal, &dense)
+        );
+    }
+
+    #[test]
+    fn test_geometry_provider() {
+        let center = Vector3::new(0.0, 0.0, 0.0);
+        let radius = 1.5;
+        let grid_provider = GeometryProvider::new(vec![Shape::Sphere { center, radius }]);
+
+        let mut blocked = 0;
+
+        for z in -4..=4 {
+            for y in -4..=4 {
+                for x in -4..=4 {
+                    let cell = Vector3::new(x as f32, y as f32, z as f32);
+                    let inside = (cell - center).magnitude() <= radius;
+
+                    assert_eq!(grid_provider.is_obstacle(cell), inside, "Cell {:?}", cell);
+                    // Any point of the cell answers like its center.
+                    assert_eq!(
+                        grid_provider.is_obstacle(cell + Vector3::new(0.4, -0.4, 0.3)),
+                        inside
+                    );
+
+                    blocked += inside as usize;
+                }
+            }
+        }
+
+        // The center, 6 face and 12 edge neighbors, corners are sqrt(3) away.
+        assert_eq!(blocked, 19);
+
+        let mut grid_provider = GeometryProvider::new(vec![Shape::Aabb {
+            min: Vector3::new(3.0, -3.0, -3.0),
+            max: Vector3::new(3.0, 3.0, 3.0),
+        }]);
+
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(6.0, 0.0, 0.0);
+
+        let path = find_path(start, goal, &grid_provider).unwrap();
+        assert!(path
+            .iter()
+            .all(|vector| vector.x != 3.0 || vector.y.abs() > 3.0 || vector.z.abs() > 3.0));
+
+        // Moving the wall out of the way clears the straight line.
+        grid_provider.shapes_mut()[0] = Shape::Aabb {
+            min: Vector3::new(3.0, 10.0, -3.0),
+            max: Vector3::new(3.0, 16.0, 3.0),
+        };
+
+        assert_eq!(find_path(start, goal, &grid_provider).unwrap().len(), 6);
+    }
+
+    #[test]
+    fn test_dynamic_grid_provider() {
+        let mut grid_provider = DynamicGridProvider::new(FnProvider::new(|vector| {
+            vector.z != 0.0 || vector.x.abs() > 10.0 || vector.y.abs() > 10.0
+        }));
+
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(6.0, 0.0, 0.0);
+
+        let (path, cost) = find_path_with_cost(start, goal, &grid_provider).unwrap();
+        assert_eq!(cost, 6.0);
+        assert!(path.contains(&Vector3::new(3.0, 0.0, 0.0)));
+
+        for y in -1..=1 {
+            grid_provider.add_obstacle((3, y, 0));
+        }
+
+        let (detour, detour_cost) = find_path_with_cost(start, goal, &grid_provider).unwrap();
+        assert!(detour_cost > cost);
+        assert!(detour
+            .iter()
+            .all(|vector| !grid_provider.is_obstacle(*vector)));
+
+        // Opening a cell of the base works the same way.
+        assert!(grid_provider.is_obstacle(Vector3::new(11.0, 0.0, 0.0)));
+        grid_provider.remove_obstacle((11, 0, 0));
+        assert!(!grid_provider.is_obstacle(Vector3::new(11.0, 0.0, 0.0)));
+
+        grid_provider.clear();
+        assert_eq!(find_path(start, goal, &grid_provider), Some(path));
+    }
+
+    #[test]
+    fn test_provider_bounds() {
+        let mut grid = Grid::new(8, 3);
+
+        // Wall across the whole grid, open air around it outside of the grid.
+        for z in 0..3 {
+            for y in 0..8 {
+                grid.set_obstacle(4, y, z);
+            }
+        }
+
+        let grid_provider = SingleGridProvider::new(grid);
+
+        assert_eq!(
+            grid_provider.bounds(),
+            Some((Vector3::zeros(), Vector3::new(7.0, 7.0, 2.0)))
+        );
+
+        let start = Vector3::new(1.0, 1.0, 1.0);
+
+        assert_eq!(
+            find_path(start, Vector3::new(6.0, 1.0, 1.0), &grid_provider),
+            None
+        );
+        assert_eq!(
+            find_path(start, Vector3::new(20.0, 1.0, 1.0), &grid_provider),
+            None
+        );
+    }
+
+    /// 8 x 8 x 8 grid with the goal (4, 4, 4) sealed in a solid shell.
+    fn sealed_goal() -> SingleGridProvider {
+        let mut grid = Grid::new(8, 8);
+
+        for z in 3..6 {
+            for y in 3..6 {
+                for x in 3..6 {
+                    grid.set_obstacle(x, y, z);
+                }
+            }
+        }
+
+        grid.set_cell(4, 4, 4, false);
+
+        SingleGridProvider::new(grid)
+    }
+
+    #[test]
+    fn test_wrapper_bounds() {
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(4.0, 4.0, 4.0);
+        let bounds = Some((Vector3::zeros(), Vector3::new(7.0, 7.0, 7.0)));
+
+        // Without the bounds of the grid the searches never end.
+        let composite = CompositeGridProvider::new(vec![
+            Box::new(sealed_goal()),
+            Box::new(TranslatedProvider::new(
+                sealed_goal(),
+                Vector3::new(2.0, 0.0, 0.0),
+            )),
+        ]);
+
+        assert_eq!(
+            composite.bounds(),
+            Some((Vector3::zeros(), Vector3::new(9.0, 7.0, 7.0)))
+        );
+        assert_eq!(find_path(start, goal, &composite), None);
+
+        let layered = LayeredProvider::new(vec![
+            (0.0..4.0, Box::new(sealed_goal()) as Box<dyn GridProvider>),
+            (4.0..8.0, Box::new(sealed_goal())),
+        ]);
+
+        assert_eq!(layered.bounds(), bounds);
+        assert_eq!(find_path(start, goal, &layered), None);
+
+        let timed = TimedProvider::new(sealed_goal(), FnProvider::new(|_| false), vec![], 0.0);
+        assert_eq!(timed.bounds(), bounds);
+        assert_eq!(find_path(start, goal, &timed), None);
+
+        let scaled = ScalingProvider::new(sealed_goal(), 0.5);
+        assert_eq!(
+            scaled.bounds(),
+            Some((Vector3::zeros(), Vector3::new(14.0, 14.0, 14.0)))
+        );
+
+        // Clamped to the distance field.
+        let distance_field = DistanceField::new(&Grid::new(6, 6));
+        let clearance = ClearanceProvider::new(sealed_goal(), distance_field, 2.0, 1.0);
+
+        assert_eq!(
+            clearance.bounds(),
+            Some((Vector3::zeros(), Vector3::new(5.0, 5.0, 5.0)))
+        );
+
+        // Any unbounded child leaves the union unbounded.
+        let unbounded = CompositeGridProvider::new(vec![
+            Box::new(sealed_goal()),
+            Box::new(FnProvider::new(|_| false)),
+        ]);
+
+        assert_eq!(unbounded.bounds(), None);
+    }
+
+    #[test]
+    fn test_is_obstacle() {
+        let axis_to_grid_id_fn = |axis: f32| (32.0 - (axis / 533.33)).floor() as u32;
+        let grid_id_to_axis_fn = |grid_id| (32.0 - grid_id as f32) * 533.3;
+        let grid_file_name_fn = |x, y| format!("grid_{}_{}.dat", x, y);
+        let grid_folder_path = Path::new("test/map_1718");
+
+        let grid_provider = FolderGridProvider::new(
+            grid_folder_path,
+            axis_to_grid_id_fn,
+            grid_id_to_axis_fn,
+            grid_file_name_fn,
+        );
+
+        assert!(grid_provider.is_obstacle(Vector3::new(-1604.0, 1163.0, 111.0)));
+    }
+
+    #[test]
+    fn test_out_of_range_is_obstacle() {
+        let axis_to_grid_id_fn = |axis: f32| (32.0 - (axis / 533.33)).floor() as u32;
+        let grid_id_to_axis_fn = |grid_id| (32.0 - grid_id as f32) * 533.3;
+        let grid_file_names = Cell::new(0);
+        let grid_file_name_fn = |x, y| {
+            grid_file_names.set(grid_file_names.get() + 1);
+            format!("grid_{}_{}.dat", x, y)
+        };
+        let grid_folder_path = Path::new("test/map_1718");
+
+        let grid_provider = FolderGridProvider::new(
+            grid_folder_path,
+            axis_to_grid_id_fn,
+            grid_id_to_axis_fn,
+            grid_file_name_fn,
+        );
+
+        assert!(!grid_provider.is_obstacle(Vector3::new(100000.0, 1163.0, 111.0)));
+        assert_eq!(grid_file_names.get(), 0);
+
+        // Valid transform, but there is no such grid file.
+        assert!(!grid_provider.is_obstacle(Vector3::new(-2140.0, 1163.0, 111.0)));
+        assert_eq!(grid_file_names.get(), 1);
+    }
+
+    #[test]
+    fn test_folder_world_to_cell() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let axis_to_grid_id_fn = |axis: f32| (32.0 - (axis / 533.33)).floor() as u32;
+        let grid_id_to_axis_fn = |grid_id| (32.0 - grid_id as f32) * 533.3;
+
+        // On a boundary between cells along every axis.
+        let vector = Vector3::new(-1602.4, 1597.4, 2.5);
+        let origin = Vector3::new(grid_id_to_axis_fn(35), grid_id_to_axis_fn(29), 0.0);
+        let (x, y, z) = world_to_cell(vector, origin, 1.0);
+
+        let mut grid = Grid::new(8, 8);
+        grid.set_obstacle(x, y, z);
+        grid.export(temp_dir.path().join("grid_35_29.dat")).unwrap();
+
+        let grid_provider = FolderGridProvider::new(
+            temp_dir.path(),
+            axis_to_grid_id_fn,
+            grid_id_to_axis_fn,
+            |x, y| format!("grid_{}_{}.dat", x, y),
+        );
+
+        assert!(grid_provider.is_obstacle(vector));
+        assert!(grid_provider.is_obstacle(cell_to_world((x, y, z), origin, 1.0)));
+        assert!(!grid_provider.is_obstacle(vector + Vector3::new(0.2, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_prefetch() {
+        let axis_to_grid_id_fn = |axis: f32| (32.0 - (axis / 533.33)).floor() as u32;
+        let grid_id_to_axis_fn = |grid_id| (32.0 - grid_id as f32) * 533.3;
+        let grid_loads = Cell::new(0);
+        let grid_file_name_fn = |x, y| {
+            grid_loads.set(grid_loads.get() + 1);
+            format!("grid_{}_{}.dat", x, y)
+        };
+        let grid_folder_path = Path::new("test/map_1718");
+
+        let grid_provider = FolderGridProvider::new(
+            grid_folder_path,
+            axis_to_grid_id_fn,
+            grid_id_to_axis_fn,
+            grid_file_name_fn,
+        );
+
+        // Overlaps grid 35_29 and the missing grid 34_29.
+        let min = Vector3::new(-1700.0, 1100.0, 100.0);
+        let max = Vector3::new(-1500.0, 1150.0, 120.0);
+
+        grid_provider.prefetch(min, max);
+        assert_eq!(grid_loads.get(), 2);
+
+        let options = SearchOptions {
+            bounds: Some((min, max)),
+            ..SearchOptions::default()
+        };
+
+        let path = find_path_with_options(
+            Vector3::new(-1660.0, 1110.0, 110.0),
+            Vector3::new(-1680.0, 1130.0, 110.0),
+            &grid_provider,
+            &options,
+        );
+
+        assert!(path.is_some());
+        assert!(!grid_provider.is_obstacle(Vector3::new(-1550.0, 1120.0, 110.0)));
+        assert_eq!(grid_loads.get(), 2);
+    }
+
+    #[test]
+    fn test_map_is_obstacle() {
+        let axis_to_grid_id_fn = |axis: f32| (32.0 - (axis / 533.33)).floor() as u32;
+        let grid_id_to_axis_fn = |grid_id| (32.0 - grid_id as f32) * 533.3;
+
+        let mut grids = HashMap::new();
+        let grid = Grid::import(Path::new("test/map_1718/grid_35_29.dat")).unwrap();
+        grids.insert((35, 29), grid);
+
+        let grid_provider = MapGridProvider::new(grids, axis_to_grid_id_fn, grid_id_to_axis_fn);
+
+        assert!(grid_provider.is_obstacle(Vector3::new(-1604.0, 1163.0, 111.0)));
+        assert!(!grid_provider.is_obstacle(Vector3::new(1604.0, 1163.0, 111.0)));
+    }
+
+    #[test]
+    fn test_top_layer() {
+        let grids = || {
+            let mut grid = Grid::new(3, 2);
+            grid.set_obstacle(1, 1, 1);
+
+            let mut grids = HashMap::new();
+            grids.insert((0, 0), grid);
+            grids
+        };
+
+        // Exactly at the grid height and rounding up to it, then the cell above.
+        let ceiling = Vector3::new(2.0, 2.0, 2.0);
+        let below = Vector3::new(2.0, 2.0, 1.7);
+        let above = Vector3::new(2.0, 2.0, 2.6);
+
+        let grid_provider = MapGridProvider::new(grids(), |_| 0, |_| 3.0);
+        assert!(grid_provider.is_obstacle(Vector3::new(2.0, 2.0, 1.0)));
+        assert!(!grid_provider.is_obstacle(ceiling));
+        assert!(!grid_provider.is_obstacle(below));
+        assert!(!grid_provider.is_obstacle(above));
+
+        let grid_provider = MapGridProvider::new(grids(), |_| 0, |_| 3.0).with_inclusive_top(true);
+        assert!(grid_provider.is_obstacle(ceiling));
+        assert!(grid_provider.is_obstacle(below));
+        assert!(!grid_provider.is_obstacle(above));
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        grids()
+            .remove(&(0, 0))
+            .unwrap()
+            .export(temp_dir.path().join("grid_0_0.dat"))
+            .unwrap();
+
+        let grid_provider = FolderGridProvider::new(
+            temp_dir.path(),
+            |_| 0,
+            |_| 3.0,
+            |x, y| format!("grid_{}_{}.dat", x, y),
+        );
+        assert!(!grid_provider.is_obstacle(ceiling));
+
+        let grid_provider = grid_provider.with_inclusive_top(true);
+        assert!(grid_provider.is_obstacle(ceiling));
+    }
+
+    #[test]
+    fn test_unknown_is_solid() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let mut grid = Grid::new(3, 2);
+        grid.set_obstacle(1, 1, 1);
+        grid.export(temp_dir.path().join("grid_0_0.dat")).unwrap();
+
+        // Tile (0, 0) has a file, tile (1, 0) past x = 100 doesn't.
+        let grid_provider = |unknown_is_solid| {
+            FolderGridProvider::new(
+                temp_dir.path(),
+                |axis| (axis > 100.0) as u32,
+                |grid_id| 3.0 + 100.0 * grid_id as f32,
+                |x, y| format!("grid_{}_{}.dat", x, y),
+            )
+            .with_unknown_is_solid(unknown_is_solid)
+        };
+
+        let blocked = Vector3::new(2.0, 2.0, 1.0);
+        let clear = Vector3::new(3.0, 2.0, 1.0);
+        let above = Vector3::new(2.0, 2.0, 5.0);
+        let missing = Vector3::new(102.0, 2.0, 1.0);
+
+        let open = grid_provider(false);
+        assert!(open.is_obstacle(blocked));
+        assert!(!open.is_obstacle(clear));
+        assert!(!open.is_obstacle(above));
+        assert!(!open.is_obstacle(missing));
+
+        let solid = grid_provider(true);
+        assert!(solid.is_obstacle(blocked));
+        assert!(!solid.is_obstacle(clear));
+        assert!(!solid.is_obstacle(above));
+        assert!(solid.is_obstacle(missing));
+    }
+
+    #[test]
+    fn test_folder_cache() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let mut grid = Grid::new(3, 2);
+        grid.set_obstacle(1, 1, 1);
+        grid.export(temp_dir.path().join("grid_0_0.dat")).unwrap();
+        std::fs::write(temp_dir.path().join("grid_2_0.dat"), [0, 0]).unwrap();
+
+        // Tile (0, 0) has a file, tile (1, 0) past x = 100 doesn't
+        // and tile (2, 0) past x = 200 has a corrupted one.
+        let grid_loads = Cell::new(0);
+        let grid_provider = FolderGridProvider::new(
+            temp_dir.path(),
+            |axis| (axis > 100.0) as u32 + (axis > 200.0) as u32,
+            |grid_id| 3.0 + 100.0 * grid_id as f32,
+            |x, y| {
+                grid_loads.set(grid_loads.get() + 1);
+                format!("grid_{}_{}.dat", x, y)
+            },
+        );
+
+        let blocked = Vector3::new(2.0, 2.0, 1.0);
+        let missing = Vector3::new(102.0, 2.0, 1.0);
+        let corrupted = Vector3::new(202.0, 2.0, 1.0);
+
+        for _ in 0..2 {
+            assert!(grid_provider.is_obstacle(blocked));
+            assert!(!grid_provider.is_obstacle(missing));
+        }
+
+        assert_eq!(grid_loads.get(), 2);
+
+        // Failed loads aren't cached.
+        assert!(!grid_provider.is_obstacle(corrupted));
+        assert!(!grid_provider.is_obstacle(corrupted));
+        assert_eq!(grid_loads.get(), 4);
+
+        let tile = temp_dir.path().join("grid_0_0.dat");
+        std::fs::copy(&tile, temp_dir.path().join("grid_1_0.dat")).unwrap();
+        std::fs::copy(&tile, temp_dir.path().join("grid_2_0.dat")).unwrap();
+
+        assert!(!grid_provider.is_obstacle(missing));
+        assert!(grid_provider.is_obstacle(corrupted));
+
+        grid_provider.evict((1, 0));
+        assert!(grid_provider.is_obstacle(missing));
+
+        Grid::new(3, 2).export(&tile).unwrap();
+
+        assert!(grid_provider.is_obstacle(blocked));
+
+        grid_provider.clear_cache();
+        assert!(!grid_provider.is_obstacle(blocked));
+    }
+
+    #[test]
+    fn test_map_rounding() {
+        let grids = || {
+            let mut grid = Grid::new(3, 3);
+            grid.set_obstacle(1, 1, 1);
+
+            let mut grids = HashMap::new();
+            grids.insert((0, 0), grid);
+            grids
+        };
+
+        // 3.0 - 1.5 = 1.5 cells, floor lands on the obstacle but round doesn't.
+        let vector = Vector3::new(1.5, 1.5, 1.5);
+
+        let grid_provider = MapGridProvider::new(grids(), |_| 0, |_| 3.0);
+        assert!(!grid_provider.is_obstacle(vector));
+
+        let grid_provider =
+            MapGridProvider::new(grids(), |_| 0, |_| 3.0).with_rounding(Rounding::Floor);
+        assert!(grid_provider.is_obstacle(vector));
+    }
+
+    struct SingleObstacleProvider {}
+
+    impl GridProvider for SingleObstacleProvider {
+        fn is_obstacle(&self, vector: Vector3<f32>) -> bool {
+            vector == Vector3::new(1.0, 1.0, 1.0)
+        }
+    }
+
+    #[test]
+    fn test_translated_is_obstacle() {
+        let offset = Vector3::new(100.0, -50.0, 10.0);
+        let grid_provider = TranslatedProvider::new(SingleObstacleProvider {}, offset);
+
+        assert!(grid_provider.is_obstacle(offset + Vector3::new(1.0, 1.0, 1.0)));
+        assert!(!grid_provider.is_obstacle(Vector3::new(1.0, 1.0, 1.0)));
+    }
+
+    #[test]
+    fn test_scaling_is_obstacle() {
+        let grid_provider = ScalingProvider::new(SingleObstacleProvider {}, 0.25);
+
+        assert!(grid_provider.is_obstacle(Vector3::new(4.0, 4.0, 4.0)));
+        assert!(!grid_provider.is_obstacle(Vector3::new(1.0, 1.0, 1.0)));
+    }
+
+    #[test]
+    fn test_layered_is_obstacle() {
+        let grid_provider = LayeredProvider::new(vec![
+            (0.0..2.0, Box::new(SingleObstacleProvider {})),
+            (
+                2.0..10.0,
+                Box::new(ScalingProvider::new(SingleObstacleProvider {}, 0.25)),
+            ),
+        ]);
+
+        assert!(grid_provider.is_obstacle(Vector3::new(1.0, 1.0, 1.0)));
+        assert!(grid_provider.is_obstacle(Vector3::new(4.0, 4.0, 4.0)));
+        assert!(!grid_provider.is_obstacle(Vector3::new(1.0, 1.0, 4.0)));
+        assert!(!grid_provider.is_obstacle(Vector3::new(4.0, 4.0, 12.0)));
+    }
+
+    #[test]
+    fn test_timed_routes() {
+        let base = FnProvider::new(|vector| vector.z != 0.0);
+        let restriction = FnProvider::new(|vector| vector.x == 5.0 && vector.y.abs() < 5.0);
+
+        let mut grid_provider = TimedProvider::new(base, restriction, vec![10.0..30.0], 5.0);
+
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(10.0, 0.0, 0.0);
+
+        assert!(!grid_provider.is_active());
+        let outside = find_path(start, goal, &grid_provider).unwrap();
+        assert_eq!(outside.len(), 10);
+        assert!(outside.contains(&Vector3::new(5.0, 0.0, 0.0)));
+
+        grid_provider.set_time(20.0);
+
+        assert!(grid_provider.is_active());
+        let inside = find_path(start, goal, &grid_provider).unwrap();
+        assert_ne!(inside, outside);
+        assert!(inside
+            .iter()
+            .all(|vector| vector.x != 5.0 || vector.y.abs() >= 5.0));
+
+        grid_provider.set_time(30.0);
+        assert!(!grid_provider.is_active());
+    }
+
+    #[test]
+    fn test_plane_is_obstacle() {
+        let grid_provider = PlaneProvider::floor(0.0);
+
+        assert!(grid_provider.is_obstacle(Vector3::new(0.0, 0.0, -1.0)));
+        assert!(grid_provider.is_obstacle(Vector3::new(1000.0, -1000.0, -0.5)));
+        assert!(grid_provider.is_obstacle_cell(-5, 7, -1));
+        assert!(!grid_provider.is_obstacle(Vector3::new(0.0, 0.0, 0.0)));
+        assert!(!grid_provider.is_obstacle(Vector3::new(1000.0, -1000.0, 250.0)));
+        assert!(!grid_provider.is_obstacle_cell(-5, 7, 1));
+
+        let grid_provider = PlaneProvider::new(0.0, 10.0);
+
+        assert!(grid_provider.is_obstacle(Vector3::new(0.0, 0.0, 11.0)));
+        assert!(!grid_provider.is_obstacle(Vector3::new(0.0, 0.0, 10.0)));
+    }
+
+    #[test]
+    fn test_composite_is_obstacle() {
+        let mut grid = Grid::new(3, 3);
+        grid.set_obstacle(1, 1, 1);
+
+        let grid_provider = CompositeGridProvider::new(vec![
+            Box::new(PlaneProvider::floor(0.0)),
+            Box::new(SingleGridProvider::new(grid)),
+        ]);
+
+        assert!(grid_provider.is_obstacle(Vector3::new(5.0, 5.0, -1.0)));
+        assert!(grid_provider.is_obstacle(Vector3::new(1.0, 1.0, 1.0)));
+        assert!(grid_provider.is_obstacle_cell(1, 1, 1));
+        assert!(!grid_provider.is_obstacle(Vector3::new(2.0, 1.0, 1.0)));
+    }
+
+    /// Tier covering non-negative x only, counts its lookups.
+    struct CountingTier {
+        is_obstacle: bool,
+        lookups: Rc<Cell<u32>>,
+    }
+
+    impl TierProvider for CountingTier {
+        fn lookup(&self, vector: Vector3<f32>) -> Option<bool> {
+            self.lookups.set(self.lookups.get() + 1);
+
+            if vector.x >= 0.0 {
+                Some(self.is_obstacle)
+            } else {
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn test_tiered_is_obstacle() {
+        let mut grid = Grid::new(3, 3);
+        grid.set_obstacle(1, 1, 1);
+
+        let disk_lookups = Rc::new(Cell::new(0));
+
+        let grid_provider = TieredProvider::new(vec![
+            Box::new(SingleGridProvider::new(grid)),
+            Box::new(CountingTier {
+                is_obstacle: true,
+                lookups: disk_lookups.clone(),
+            }),
+        ]);
+
+        // Covered by the cheap tier.
+        assert!(grid_provider.is_obstacle(Vector3::new(1.0, 1.0, 1.0)));
+        assert!(!grid_provider.is_obstacle(Vector3::new(0.0, 1.0, 1.0)));
+        assert_eq!(disk_lookups.get(), 0);
+
+        // Missed by the cheap tier.
+        assert!(grid_provider.is_obstacle(Vector3::new(10.0, 1.0, 1.0)));
+        assert_eq!(disk_lookups.get(), 1);
+
+        // Covered by no tier.
+        assert!(!grid_provider.is_obstacle(Vector3::new(-10.0, 1.0, 1.0)));
+        assert_eq!(disk_lookups.get(), 2);
+    }
+
+    #[test]
+    fn test_tiered_promotion() {
+        let disk_lookups = Rc::new(Cell::new(0));
+
+        let grid_provider = TieredProvider::new(vec![Box::new(CountingTier {
+            is_obstacle: true,
+            lookups: disk_lookups.clone(),
+        })])
+        .with_promotion();
+
+        for _ in 0..3 {
+            assert!(grid_provider.is_obstacle(Vector3::new(10.0, 1.0, 1.0)));
+        }
+
+        assert_eq!(disk_lookups.get(), 1);
+
+        // Uncovered answers aren't promoted.
+        for _ in 0..3 {
+            assert!(!grid_provider.is_obstacle(Vector3::new(-10.0, 1.0, 1.0)));
+        }
+
+        assert_eq!(disk_lookups.get(), 4);
+
+        // Vectors off the lattice aren't promoted, nor answer from the cells
+        // they round to.
+        assert!(grid_provider.is_obstacle(Vector3::new(0.4, 1.0, 1.0)));
+        assert!(!grid_provider.is_obstacle(Vector3::new(-0.4, 1.0, 1.0)));
+        assert!(grid_provider.is_obstacle(Vector3::new(0.4, 1.0, 1.0)));
+        assert_eq!(disk_lookups.get(), 7);
+
+        for _ in 0..2 {
+            assert!(grid_provider.is_obstacle(Vector3::new(f32::INFINITY, 1.0, 1.0)));
+            assert!(!grid_provider.is_obstacle(Vector3::new(f32::NAN, 1.0, 1.0)));
+        }
+
+        assert_eq!(disk_lookups.get(), 11);
+    }
+
+    #[test]
+    fn test_fn_is_obstacle() {
+        let grid_provider = FnProvider::new(|vector| vector.z < 0.0);
+
+        assert!(grid_provider.is_obstacle(Vector3::new(0.0, 0.0, -1.0)));
+        assert!(!grid_provider.is_obstacle(Vector3::new(0.0, 0.0, 1.0)));
+
+        let center = Vector3::new(5.0, 5.0, 5.0);
+        let grid_provider = FnProvider::new(|vector| (vector - center).magnitude() <= 2.0);
+
+        assert!(grid_provider.is_obstacle(Vector3::new(5.0, 6.0, 6.0)));
+        assert!(!grid_provider.is_obstacle(Vector3::new(5.0, 8.0, 5.0)));
+    }
+
+    #[test]
+    fn test_single_is_obstacle() {
+        let mut grid = Grid::new(3, 3);
+        grid.set_obstacle(1, 2, 0);
+
+        let grid_provider = SingleGridProvider::new(grid);
+
+        assert!(grid_provider.is_obstacle_cell(1, 2, 0));
+        assert!(grid_provider.is_obstacle(Vector3::new(1.2, 1.9, -0.3)));
+        assert!(!grid_provider.is_obstacle_cell(2, 1, 0));
+        assert!(!grid_provider.is_obstacle_cell(-1, 2, 0));
+        assert!(!grid_provider.is_obstacle(Vector3::new(f32::NAN, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_default_is_obstacle_cell() {
+        let grid_provider = FnProvider::new(|vector| vector == Vector3::new(-1.0, 2.0, 3.0));
+
+        assert!(grid_provider.is_obstacle_cell(-1, 2, 3));
+        assert!(!grid_provider.is_obstacle_cell(1, 2, 3));
+    }
+
+    #[test]
+    fn test_recording_provider() {
+        let grid_provider = RecordingProvider::new(FnProvider::new(|_| false));
+
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let goal = Vector3::new(1.0, 0.0, 0.0);
+
+        let path = find_path(start, goal, &grid_provider).unwrap();
+        assert_eq!(path, vec![goal]);
+
+        // Start and goal are checked up front, then only the start
+        // was expanded before the goal was popped.
+        let queries = grid_provider.into_queries();
+        assert_eq!(queries.len(), 2 + 26);
+        assert_eq!(queries[..2], [start, goal]);
+        assert_eq!(queries[2], Vector3::new(-1.0, -1.0, -1.0));
+        assert_eq!(queries.last(), Some(&Vector3::new(1.0, 1.0, 1.0)));
+        assert!(queries[2..].contains(&goal));
+        assert!(!queries[2..].contains(&start));
     }
 }
@@ -0,0 +1,153 @@
+use crate::grid::Grid;
+
+/// Open cells of the grid labeled by the connected air pocket they belong to.
+#[derive(Debug)]
+pub struct LabeledGrid {
+    pub width: u32,
+    pub height: u32,
+    /// Zero for obstacles, region label otherwise.
+    labels: Vec<u32>,
+    regions: u32,
+}
+
+impl LabeledGrid {
+    /// Flood fills open cells of the grid using 26-connectivity.
+    pub fn new(grid: &Grid) -> Self {
+        let width = grid.width;
+        let height = grid.height;
+
+        let mut labels = vec![0; width as usize * width as usize * height as usize];
+        let mut regions = 0;
+        let mut stack = Vec::new();
+
+        for z in 0..height {
+            for y in 0..width {
+                for x in 0..width {
+                    let index = Grid::index(x, y, z, width, height);
+
+                    if labels[index] != 0 || grid.is_obstacle(x, y, z) {
+                        continue;
+                    }
+
+                    regions += 1;
+                    labels[index] = regions;
+                    stack.push((x, y, z));
+
+                    while let Some((x, y, z)) = stack.pop() {
+                        for (ax, ay, az) in adjacent_cells(x, y, z, width, height) {
+                            let adjacent_index = Grid::index(ax, ay, az, width, height);
+
+                            if labels[adjacent_index] != 0 || grid.is_obstacle(ax, ay, az) {
+                                continue;
+                            }
+
+                            labels[adjacent_index] = regions;
+                            stack.push((ax, ay, az));
+                        }
+                    }
+                }
+            }
+        }
+
+        LabeledGrid {
+            width,
+            height,
+            labels,
+            regions,
+        }
+    }
+
+    /// Returns the region label of the cell, or `None` for obstacles.
+    pub fn label(&self, x: u32, y: u32, z: u32) -> Option<u32> {
+        match self.labels[Grid::index(x, y, z, self.width, self.height)] {
+            0 => None,
+            label => Some(label),
+        }
+    }
+
+    /// Returns whether both cells are open and in the same region.
+    pub fn same_region(&self, a: (u32, u32, u32), b: (u32, u32, u32)) -> bool {
+        match (self.label(a.0, a.1, a.2), self.label(b.0, b.1, b.2)) {
+            (Some(label_a), Some(label_b)) => label_a == label_b,
+            _ => false,
+        }
+    }
+
+    /// Returns the number of regions.
+    pub fn regions(&self) -> u32 {
+        self.regions
+    }
+}
+
+fn adjacent_cells(x: u32, y: u32, z: u32, width: u32, height: u32) -> Vec<(u32, u32, u32)> {
+    let mut adjacent = Vec::new();
+
+    for dx in -1i64..=1 {
+        for dy in -1i64..=1 {
+            for dz in -1i64..=1 {
+                if dx == 0 && dy == 0 && dz == 0 {
+                    continue;
+                }
+
+                let ax = x as i64 + dx;
+                let ay = y as i64 + dy;
+                let az = z as i64 + dz;
+
+                if ax < 0 || ay < 0 || az < 0 {
+                    continue;
+                }
+
+                if ax >= width as i64 || ay >= width as i64 || az >= height as i64 {
+                    continue;
+                }
+
+                adjacent.push((ax as u32, ay as u32, az as u32));
+            }
+        }
+    }
+
+    adjacent
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid::Grid;
+
+    #[test]
+    fn test_wall_separates_regions() {
+        let mut grid = Grid::new(5, 5);
+
+        for y in 0..5 {
+            for z in 0..5 {
+                grid.set_obstacle(2, y, z);
+            }
+        }
+
+        let labeled = grid.connected_components();
+
+        assert_eq!(labeled.regions(), 2);
+        assert_eq!(labeled.label(2, 2, 2), None);
+        assert!(labeled.same_region((0, 0, 0), (1, 4, 4)));
+        assert!(labeled.same_region((3, 0, 0), (4, 4, 4)));
+        assert!(!labeled.same_region((0, 0, 0), (4, 4, 4)));
+        assert!(!labeled.same_region((0, 0, 0), (2, 0, 0)));
+    }
+
+    #[test]
+    fn test_diagonal_gap_connects_regions() {
+        let mut grid = Grid::new(3, 3);
+
+        for y in 0..3 {
+            for z in 0..3 {
+                if y != 2 || z != 2 {
+                    grid.set_obstacle(1, y, z);
+                }
+            }
+        }
+
+        let labeled = grid.connected_components();
+
+        assert_eq!(labeled.regions(), 1);
+        assert!(labeled.same_region((0, 0, 0), (2, 0, 0)));
+    }
+}
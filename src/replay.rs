@@ -0,0 +1,389 @@
+use crate::grid::{Grid, GridError};
+use crate::pathfinding::{
+    find_path_with_options, Motion, NeighborOrder, SearchOptions, VerticalCosts,
+};
+use crate::provider::SingleGridProvider;
+use crate::transform::{Topology, Wrapping};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use nalgebra::Vector3;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Read, Write};
+use std::path::Path;
+
+/// Magic bytes which start the query file.
+const MAGIC: [u8; 4] = *b"FPRQ";
+/// Current version of the query file format.
+///
+/// Layout: magic, version, start, goal, options, grid in the grid file format.
+/// Optional values are a presence byte followed by the value if present.
+/// Version 2 adds the neighbor order after the options of version 1,
+/// version 3 the optional motions after it, as a count and (offset, cost) pairs,
+/// version 4 the optional heuristic weight after the motions,
+/// version 5 the optional climb and descent costs after the weight,
+/// version 6 the optional wrapping topology, width and depth after them,
+/// version 7 the optional snap tolerance after the wrapping,
+/// version 8 the vertical moves axis only byte after the tolerance,
+/// version 9 the minimize turns byte after it,
+/// version 10 the prefer straight byte after that.
+const VERSION: u32 = 10;
+
+/// Search inputs which can be dumped along with the grid to reproduce a path.
+#[derive(Debug, Clone)]
+pub struct PathQuery {
+    pub start: Vector3<f32>,
+    pub goal: Vector3<f32>,
+    pub options: SearchOptions,
+}
+
+impl PathQuery {
+    pub fn new(start: Vector3<f32>, goal: Vector3<f32>, options: SearchOptions) -> Self {
+        PathQuery {
+            start,
+            goal,
+            options,
+        }
+    }
+
+    /// Writes the query and the provider grid into the file.
+    pub fn dump<P: AsRef<Path>>(
+        &self,
+        grid_provider: &SingleGridProvider,
+        path: P,
+    ) -> Result<(), Error> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+
+        let mut writer = BufWriter::new(file);
+
+        writer.write_all(&MAGIC)?;
+        writer.write_u32::<BigEndian>(VERSION)?;
+        write_vector(&mut writer, &self.start)?;
+        write_vector(&mut writer, &self.goal)?;
+
+        let options = &self.options;
+
+        write_optional(
+            &mut writer,
+            options.max_turn_angle.as_ref(),
+            |writer, angle| writer.write_f32::<BigEndian>(*angle),
+        )?;
+        write_optional(
+            &mut writer,
+            options.bounds.as_ref(),
+            |writer, (min, max)| {
+                write_vector(writer, min)?;
+                write_vector(writer, max)
+            },
+        )?;
+        writer.write_f32::<BigEndian>(options.turn_penalty)?;
+        write_optional(
+            &mut writer,
+            options.max_path_cost.as_ref(),
+            |writer, cost| writer.write_f32::<BigEndian>(*cost),
+        )?;
+        writer.write_u8(options.include_start as u8)?;
+        writer.write_u8(match options.neighbor_order {
+            NeighborOrder::ZInnermost => 0,
+            NeighborOrder::XInnermost => 1,
+            NeighborOrder::Morton => 2,
+        })?;
+        write_optional(&mut writer, options.motions.as_ref(), |writer, motions| {
+            writer.write_u32::<BigEndian>(motions.len() as u32)?;
+
+            for motion in motions {
+                write_vector(writer, &motion.offset)?;
+                writer.write_f32::<BigEndian>(motion.cost)?;
+            }
+
+            Ok(())
+        })?;
+        write_optional(
+            &mut writer,
+            options.heuristic_weight.as_ref(),
+            |writer, weight| writer.write_f32::<BigEndian>(*weight),
+        )?;
+        write_optional(
+            &mut writer,
+            options.vertical_costs.as_ref(),
+            |writer, vertical_costs| {
+                writer.write_f32::<BigEndian>(vertical_costs.climb)?;
+                writer.write_f32::<BigEndian>(vertical_costs.descent)
+            },
+        )?;
+        write_optional(
+            &mut writer,
+            options.wrapping.as_ref(),
+            |writer, wrapping| {
+                writer.write_u8(match wrapping.topology {
+                    Topology::Bounded => 0,
+                    Topology::WrapX => 1,
+                    Topology::WrapY => 2,
+                    Topology::WrapXY => 3,
+                })?;
+                writer.write_f32::<BigEndian>(wrapping.width)?;
+                writer.write_f32::<BigEndian>(wrapping.depth)
+            },
+        )?;
+        write_optional(
+            &mut writer,
+            options.snap_tolerance.as_ref(),
+            |writer, tolerance| writer.write_f32::<BigEndian>(*tolerance),
+        )?;
+        writer.write_u8(options.vertical_moves_axis_only as u8)?;
+        writer.write_u8(options.minimize_turns as u8)?;
+        writer.write_u8(options.prefer_straight as u8)?;
+
+        grid_provider.grid().write_to(&mut writer)?;
+        writer.flush()
+    }
+
+    /// Reads the query and the provider dumped into the file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<(PathQuery, SingleGridProvider), GridError> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+
+        if magic != MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "Not a path query file").into());
+        }
+
+        let version = reader.read_u32::<BigEndian>()?;
+
+        if version == 0 || version > VERSION {
+            return Err(GridError::UnsupportedVersion(version));
+        }
+
+        let start = read_vector(&mut reader)?;
+        let goal = read_vector(&mut reader)?;
+
+        let options = SearchOptions {
+            max_turn_angle: read_optional(&mut reader, |reader| reader.read_f32::<BigEndian>())?,
+            bounds: read_optional(&mut reader, |reader| {
+                Ok((read_vector(reader)?, read_vector(reader)?))
+            })?,
+            turn_penalty: reader.read_f32::<BigEndian>()?,
+            max_path_cost: read_optional(&mut reader, |reader| reader.read_f32::<BigEndian>())?,
+            include_start: reader.read_u8()? != 0,
+            neighbor_order: match version {
+                1 => NeighborOrder::default(),
+                _ => read_neighbor_order(&mut reader)?,
+            },
+            motions: match version {
+                1 | 2 => None,
+                _ => read_optional(&mut reader, |reader| {
+                    let count = reader.read_u32::<BigEndian>()?;
+
+                    (0..count)
+                        .map(|_| {
+                            Ok(Motion::new(
+                                read_vector(reader)?,
+                                reader.read_f32::<BigEndian>()?,
+                            ))
+                        })
+                        .collect()
+                })?,
+            },
+            heuristic_weight: match version {
+                1..=3 => None,
+                _ => read_optional(&mut reader, |reader| reader.read_f32::<BigEndian>())?,
+            },
+            vertical_costs: match version {
+                1..=4 => None,
+                _ => read_optional(&mut reader, |reader| {
+                    Ok(VerticalCosts::new(
+                        reader.read_f32::<BigEndian>()?,
+                        reader.read_f32::<BigEndian>()?,
+                    ))
+                })?,
+            },
+            wrapping: match version {
+                1..=5 => None,
+                _ => read_optional(&mut reader, |reader| {
+                    Ok(Wrapping::new(
+                        read_topology(reader)?,
+                        reader.read_f32::<BigEndian>()?,
+                        reader.read_f32::<BigEndian>()?,
+                    ))
+                })?,
+            },
+            snap_tolerance: match version {
+                1..=6 => None,
+                _ => read_optional(&mut reader, |reader| reader.read_f32::<BigEndian>())?,
+            },
+            vertical_moves_axis_only: match version {
+                1..=7 => false,
+                _ => reader.read_u8()? != 0,
+            },
+            minimize_turns: match version {
+                1..=8 => false,
+                _ => reader.read_u8()? != 0,
+            },
+            prefer_straight: match version {
+                1..=9 => false,
+                _ => reader.read_u8()? != 0,
+            },
+            // The cache doesn't change the found path, so it isn't dumped.
+            ..SearchOptions::default()
+        };
+
+        let grid = Grid::read_from(&mut reader, false)?;
+
+        Ok((
+            PathQuery::new(start, goal, options),
+            SingleGridProvider::new(grid),
+        ))
+    }
+}
+
+/// Loads the dumped query and runs the search again.
+pub fn replay<P: AsRef<Path>>(path: P) -> Result<Option<Vec<Vector3<f32>>>, GridError> {
+    let (query, grid_provider) = PathQuery::load(path)?;
+
+    Ok(find_path_with_options(
+        query.start,
+        query.goal,
+        grid_provider,
+        &query.options,
+    ))
+}
+
+fn write_vector<W: Write>(writer: &mut W, vector: &Vector3<f32>) -> Result<(), Error> {
+    for axis in vector.iter() {
+        writer.write_f32::<BigEndian>(*axis)?;
+    }
+
+    Ok(())
+}
+
+fn read_vector<R: Read>(reader: &mut R) -> Result<Vector3<f32>, Error> {
+    Ok(Vector3::new(
+        reader.read_f32::<BigEndian>()?,
+        reader.read_f32::<BigEndian>()?,
+        reader.read_f32::<BigEndian>()?,
+    ))
+}
+
+fn read_neighbor_order<R: Read>(reader: &mut R) -> Result<NeighborOrder, Error> {
+    match reader.read_u8()? {
+        0 => Ok(NeighborOrder::ZInnermost),
+        1 => Ok(NeighborOrder::XInnermost),
+        2 => Ok(NeighborOrder::Morton),
+        _ => Err(Error::new(ErrorKind::InvalidData, "Unknown neighbor order")),
+    }
+}
+
+fn read_topology<R: Read>(reader: &mut R) -> Result<Topology, Error> {
+    match reader.read_u8()? {
+        0 => Ok(Topology::Bounded),
+        1 => Ok(Topology::WrapX),
+        2 => Ok(Topology::WrapY),
+        3 => Ok(Topology::WrapXY),
+        _ => Err(Error::new(ErrorKind::InvalidData, "Unknown topology")),
+    }
+}
+
+fn write_optional<W, T, F>(writer: &mut W, value: Option<&T>, write_fn: F) -> Result<(), Error>
+where
+    W: Write,
+    F: FnOnce(&mut W, &T) -> Result<(), Error>,
+{
+    match value {
+        Some(value) => {
+            writer.write_u8(1)?;
+            write_fn(writer, value)
+        }
+        None => writer.write_u8(0),
+    }
+}
+
+fn read_optional<R, T, F>(reader: &mut R, read_fn: F) -> Result<Option<T>, Error>
+where
+    R: Read,
+    F: FnOnce(&mut R) -> Result<T, Error>,
+{
+    match reader.read_u8()? {
+        0 => Ok(None),
+        _ => read_fn(reader).map(Some),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid::Grid;
+    use crate::pathfinding::{
+        find_path_with_options, Motion, NeighborOrder, SearchOptions, VerticalCosts,
+    };
+    use crate::provider::SingleGridProvider;
+    use crate::replay::{replay, PathQuery};
+    use crate::transform::{Topology, Wrapping};
+    use nalgebra::Vector3;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_dump_replay() {
+        let temp_file = NamedTempFile::new().unwrap();
+
+        let mut grid = Grid::new(12, 2);
+
+        for y in 0..10 {
+            grid.set_obstacle(5, y, 0);
+        }
+
+        let grid_provider = SingleGridProvider::new(grid);
+
+        let options = SearchOptions {
+            bounds: Some((Vector3::new(0.0, 0.0, 0.0), Vector3::new(11.0, 11.0, 0.0))),
+            turn_penalty: 0.5,
+            include_start: true,
+            neighbor_order: NeighborOrder::Morton,
+            motions: Some(vec![
+                Motion::euclidean(Vector3::new(2.0, 0.0, 0.0)),
+                Motion::euclidean(Vector3::new(0.0, 1.0, 0.0)),
+                Motion::new(Vector3::new(0.0, -1.0, 0.0), 1.5),
+            ]),
+            heuristic_weight: Some(1.5),
+            vertical_costs: Some(VerticalCosts::new(0.5, -0.25)),
+            wrapping: Some(Wrapping::new(Topology::WrapY, 12.0, 12.0)),
+            snap_tolerance: Some(0.75),
+            vertical_moves_axis_only: true,
+            minimize_turns: true,
+            prefer_straight: true,
+            ..SearchOptions::default()
+        };
+
+        let query = PathQuery::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(10.0, 0.0, 0.0),
+            options,
+        );
+
+        let expected =
+            find_path_with_options(query.start, query.goal, &grid_provider, &query.options);
+        assert!(expected.is_some());
+
+        query.dump(&grid_provider, temp_file.path()).unwrap();
+
+        let (loaded, loaded_provider) = PathQuery::load(temp_file.path()).unwrap();
+
+        assert_eq!(loaded.start, query.start);
+        assert_eq!(loaded.goal, query.goal);
+        assert_eq!(loaded.options.bounds, query.options.bounds);
+        assert_eq!(loaded.options.max_turn_angle, None);
+        assert_eq!(loaded.options.neighbor_order, NeighborOrder::Morton);
+        assert_eq!(loaded.options.motions, query.options.motions);
+        assert_eq!(loaded.options.heuristic_weight, Some(1.5));
+        assert_eq!(loaded.options.vertical_costs, query.options.vertical_costs);
+        assert_eq!(loaded.options.wrapping, query.options.wrapping);
+        assert_eq!(loaded.options.snap_tolerance, Some(0.75));
+        assert!(loaded.options.vertical_moves_axis_only);
+        assert!(loaded.options.minimize_turns);
+        assert!(loaded.options.prefer_straight);
+        assert_eq!(loaded_provider.grid(), grid_provider.grid());
+
+        assert_eq!(replay(temp_file.path()).unwrap(), expected);
+    }
+}
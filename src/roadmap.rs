@@ -0,0 +1,273 @@
+use crate::path::line_of_sight;
+use crate::provider::GridProvider;
+use crate::rng::Rng;
+use nalgebra::Vector3;
+use ordered_float::NotNan;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+/// Nearest visible nodes every node and every query end are connected to.
+const NEIGHBORS: usize = 8;
+
+/// Probabilistic roadmap, random open cells connected by line of sight,
+/// for answering many queries in the same static region. A query only
+/// searches the small graph instead of the grid, so it's much faster than
+/// `find_path`, but the path is not the cheapest one and may be missed
+/// in narrow passages the samples don't cover.
+pub struct Roadmap<G: GridProvider> {
+    grid_provider: G,
+    nodes: Vec<Vector3<f32>>,
+    /// Neighbors of every node with the distance to them.
+    edges: Vec<Vec<(usize, f32)>>,
+}
+
+impl<G: GridProvider> Roadmap<G> {
+    /// Samples up to `samples` open lattice cells within the inclusive bounds (min, max)
+    /// and connects each to its nearest visible nodes. Solid and repeated samples
+    /// are dropped. The same seed always gives the same roadmap.
+    pub fn build(
+        grid_provider: G,
+        bounds: (Vector3<f32>, Vector3<f32>),
+        samples: usize,
+        seed: u64,
+    ) -> Self {
+        let (min, max) = bounds;
+        let (min, max) = (min.map(f32::ceil), max.map(f32::floor));
+
+        let mut rng = Rng::new(seed);
+        let mut sampled = HashSet::new();
+        let mut nodes = Vec::new();
+
+        let is_finite = |vector: &Vector3<f32>| vector.iter().all(|axis| axis.is_finite());
+
+        if is_finite(&min)
+            && is_finite(&max)
+            && min.iter().zip(max.iter()).all(|(min, max)| min <= max)
+        {
+            for _ in 0..samples {
+                let vector = min.zip_map(&max, |min, max| {
+                    min + rng.below((max - min) as usize + 1) as f32
+                });
+
+                let cell = (vector.x as i32, vector.y as i32, vector.z as i32);
+
+                if sampled.insert(cell) && !grid_provider.is_obstacle(vector) {
+                    nodes.push(vector);
+                }
+            }
+        }
+
+        let mut roadmap = Roadmap {
+            grid_provider,
+            edges: vec![Vec::new(); nodes.len()],
+            nodes,
+        };
+
+        for index in 0..roadmap.nodes.len() {
+            for (neighbor, distance) in roadmap.nearest_visible(roadmap.nodes[index]) {
+                if neighbor == index || roadmap.edges[index].iter().any(|edge| edge.0 == neighbor) {
+                    continue;
+                }
+
+                roadmap.edges[index].push((neighbor, distance));
+                roadmap.edges[neighbor].push((index, distance));
+            }
+        }
+
+        roadmap
+    }
+
+    /// Sampled open cells.
+    pub fn nodes(&self) -> &[Vector3<f32>] {
+        &self.nodes
+    }
+
+    /// Returns the polyline from the start to the goal through the roadmap,
+    /// `[start, goal]` when the goal is in sight. Like `direct_path` it begins
+    /// with the start and the ends aren't snapped. `None` when either end is
+    /// solid, not finite or they can't be connected through the roadmap.
+    pub fn query(&self, start: Vector3<f32>, goal: Vector3<f32>) -> Option<Vec<Vector3<f32>>> {
+        // Costs are ordered as `NotNan`, so nothing non-finite may enter the heap.
+        let is_finite = |vector: &Vector3<f32>| vector.iter().all(|axis| axis.is_finite());
+
+        if !is_finite(&start) || !is_finite(&goal) {
+            return None;
+        }
+
+        if self.grid_provider.is_obstacle(start) || self.grid_provider.is_obstacle(goal) {
+            return None;
+        }
+
+        if line_of_sight(start, goal, &self.grid_provider) {
+            return Some(vec![start, goal]);
+        }
+
+        // The goal is one more node past the sampled ones.
+        let goal_index = self.nodes.len();
+        let goal_links: HashMap<usize, f32> = self.nearest_visible(goal).into_iter().collect();
+
+        let mut costs = vec![f32::INFINITY; goal_index + 1];
+        let mut previous: Vec<Option<usize>> = vec![None; goal_index + 1];
+        let mut reachable = BinaryHeap::new();
+
+        let estimate = |index: usize| {
+            if index == goal_index {
+                0.0
+            } else {
+                (goal - self.nodes[index]).magnitude()
+            }
+        };
+
+        let mut relax = |reachable: &mut BinaryHeap<_>,
+                         costs: &mut Vec<f32>,
+                         from: Option<usize>,
+                         index: usize,
+                         cost: f32| {
+            if cost < costs[index] {
+                costs[index] = cost;
+                previous[index] = from;
+
+                let total = NotNan::new(cost + estimate(index)).unwrap();
+                reachable.push((Reverse(total), index));
+            }
+        };
+
+        for (index, distance) in self.nearest_visible(start) {
+            relax(&mut reachable, &mut costs, None, index, distance);
+        }
+
+        let mut explored = vec![false; goal_index + 1];
+
+        while let Some((_, index)) = reachable.pop() {
+            if index == goal_index {
+                break;
+            }
+
+            if explored[index] {
+                continue;
+            }
+
+            explored[index] = true;
+            let cost = costs[index];
+
+            for &(neighbor, distance) in &self.edges[index] {
+                relax(
+                    &mut reachable,
+                    &mut costs,
+                    Some(index),
+                    neighbor,
+                    cost + distance,
+                );
+            }
+
+            if let Some(distance) = goal_links.get(&index) {
+                relax(
+                    &mut reachable,
+                    &mut costs,
+                    Some(index),
+                    goal_index,
+                    cost + distance,
+                );
+            }
+        }
+
+        let mut node = previous[goal_index]?;
+        let mut path = vec![goal, self.nodes[node]];
+
+        while let Some(previous_node) = previous[node] {
+            node = previous_node;
+            path.push(self.nodes[node]);
+        }
+
+        path.push(start);
+        path.reverse();
+
+        Some(path)
+    }
+
+    /// Up to `NEIGHBORS` nodes in sight of the vector, nearest first.
+    fn nearest_visible(&self, vector: Vector3<f32>) -> Vec<(usize, f32)> {
+        let mut nearest: Vec<(usize, f32)> = self
+            .nodes
+            .iter()
+            .map(|node| (vector - node).magnitude())
+            .enumerate()
+            .collect();
+
+        nearest.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+        nearest
+            .into_iter()
+            .filter(|&(index, _)| line_of_sight(vector, self.nodes[index], &self.grid_provider))
+            .take(NEIGHBORS)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::path::validate_path;
+    use crate::provider::{FnProvider, GridProvider};
+    use crate::roadmap::Roadmap;
+    use nalgebra::Vector3;
+
+    /// Wall at x = 5 with a gap for |y| > 4, flat at z = 0.
+    fn walled(vector: Vector3<f32>) -> bool {
+        vector.z.round() != 0.0 || (vector.x.round() == 5.0 && vector.y.round().abs() <= 4.0)
+    }
+
+    #[test]
+    fn test_roadmap() {
+        let bounds = (Vector3::new(0.0, -10.0, 0.0), Vector3::new(10.0, 10.0, 0.0));
+        let roadmap = Roadmap::build(FnProvider::new(walled), bounds, 100, 3);
+
+        assert!(!roadmap.nodes().is_empty());
+        assert!(roadmap.nodes().iter().all(|node| !walled(*node)));
+
+        let start = Vector3::new(1.0, 0.0, 0.0);
+        let goal = Vector3::new(9.0, 0.0, 0.0);
+        let path = roadmap.query(start, goal).unwrap();
+
+        assert!(path.len() > 2);
+        assert_eq!(path.first(), Some(&start));
+        assert_eq!(path.last(), Some(&goal));
+        assert!(validate_path(&path, &FnProvider::new(walled)));
+
+        // In sight of each other, and solid.
+        assert_eq!(
+            roadmap.query(start, Vector3::new(3.0, 2.0, 0.0)),
+            Some(vec![start, Vector3::new(3.0, 2.0, 0.0)])
+        );
+        assert_eq!(roadmap.query(start, Vector3::new(5.0, 0.0, 0.0)), None);
+
+        // Not finite.
+        assert_eq!(roadmap.query(start, Vector3::new(f32::NAN, 0.0, 0.0)), None);
+        assert_eq!(
+            roadmap.query(Vector3::new(0.0, f32::INFINITY, 0.0), goal),
+            None
+        );
+    }
+
+    #[test]
+    fn test_roadmap_deterministic() {
+        let bounds = (Vector3::new(-4.0, -4.0, -4.0), Vector3::new(4.0, 4.0, 4.0));
+        let open = FnProvider::new(|_| false);
+
+        let first = Roadmap::build(&open, bounds, 50, 11);
+        let second = Roadmap::build(&open, bounds, 50, 11);
+
+        assert_eq!(first.nodes(), second.nodes());
+        assert!(first.nodes().len() <= 50);
+        assert!(first
+            .nodes()
+            .iter()
+            .all(|node| node.iter().all(|axis| axis.abs() <= 4.0)));
+        assert!(!open.is_obstacle(first.nodes()[0]));
+
+        let unbounded = (
+            Vector3::new(0.0, 0.0, f32::NEG_INFINITY),
+            Vector3::new(4.0, 4.0, 4.0),
+        );
+        assert!(Roadmap::build(&open, unbounded, 50, 11).nodes().is_empty());
+    }
+}
@@ -0,0 +1,124 @@
+use crate::grid::Grid;
+use crate::rng::Rng;
+
+/// Perfect maze in the x-y plane, its walls span the whole height so the search
+/// can't fly over them. Corridors are the cells with odd x and y, e.g. (1, 1, z)
+/// to (width - 2, width - 2, z) for an odd width, all of them are connected.
+/// The same seed always gives the same maze.
+pub fn generate_maze(width: u32, height: u32, seed: u64) -> Grid {
+    let mut grid = Grid::new(width, height);
+    grid.invert();
+
+    let rooms = (width / 2) as i32;
+    let mut rng = Rng::new(seed);
+
+    if rooms == 0 {
+        return grid;
+    }
+
+    let mut carve = |x: i32, y: i32| {
+        for z in 0..height as i32 {
+            grid.set_cell(x, y, z, false);
+        }
+    };
+
+    // Randomized depth-first search over the rooms.
+    let mut visited = vec![false; (rooms * rooms) as usize];
+    let mut stack = vec![(0, 0)];
+
+    visited[0] = true;
+    carve(1, 1);
+
+    while let Some(&(x, y)) = stack.last() {
+        let unvisited: Vec<(i32, i32)> = [(1, 0), (-1, 0), (0, 1), (0, -1)]
+            .iter()
+            .map(|(dx, dy)| (x + dx, y + dy))
+            .filter(|&(nx, ny)| nx >= 0 && ny >= 0 && nx < rooms && ny < rooms)
+            .filter(|&(nx, ny)| !visited[(nx + ny * rooms) as usize])
+            .collect();
+
+        if unvisited.is_empty() {
+            stack.pop();
+            continue;
+        }
+
+        let (nx, ny) = unvisited[rng.below(unvisited.len())];
+        visited[(nx + ny * rooms) as usize] = true;
+
+        // The wall between the rooms, then the room itself.
+        carve(x + nx + 1, y + ny + 1);
+        carve(2 * nx + 1, 2 * ny + 1);
+
+        stack.push((nx, ny));
+    }
+
+    grid
+}
+
+/// Every cell is an obstacle with the probability `density`, independently
+/// of the others. The same seed always gives the same grid.
+pub fn generate_random(width: u32, height: u32, density: f32, seed: u64) -> Grid {
+    assert!(
+        (0.0..=1.0).contains(&density),
+        "Density {} is not within [0, 1]",
+        density
+    );
+
+    let mut grid = Grid::new(width, height);
+    let mut rng = Rng::new(seed);
+
+    for z in 0..height {
+        for y in 0..width {
+            for x in 0..width {
+                if rng.next_f32() < density {
+                    grid.set_obstacle(x, y, z);
+                }
+            }
+        }
+    }
+
+    grid
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pathfinding::find_path;
+    use crate::provider::SingleGridProvider;
+    use crate::testutil::{generate_maze, generate_random};
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_generate_maze() {
+        let maze = generate_maze(21, 2, 7);
+
+        assert!(maze == generate_maze(21, 2, 7));
+        assert!(maze != generate_maze(21, 2, 8));
+
+        // Odd rooms are open, even ones are walls, 10 x 10 rooms and 99 passages.
+        assert!(!maze.is_obstacle(1, 1, 1));
+        assert!(maze.is_obstacle(2, 2, 0));
+        assert_eq!(maze.count_obstacles(), 21 * 21 * 2 - (100 + 99) * 2);
+
+        let path = find_path(
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(19.0, 19.0, 1.0),
+            SingleGridProvider::new(maze),
+        );
+
+        assert!(path.is_some());
+    }
+
+    #[test]
+    fn test_generate_random() {
+        let grid = generate_random(32, 8, 0.25, 42);
+
+        assert!(grid == generate_random(32, 8, 0.25, 42));
+        assert!(grid != generate_random(32, 8, 0.25, 43));
+
+        let density = grid.count_obstacles() as f32 / (32 * 32 * 8) as f32;
+        assert!((density - 0.25).abs() < 0.02, "Density {}", density);
+
+        assert_eq!(generate_random(8, 2, 0.0, 1).count_obstacles(), 0);
+        assert_eq!(generate_random(8, 2, 1.0, 1).count_obstacles(), 8 * 8 * 2);
+    }
+}
@@ -0,0 +1,255 @@
+use crate::pathfinding::find_path_with_cost;
+use crate::provider::GridProvider;
+use nalgebra::Vector3;
+
+/// Largest number of waypoints ordered exactly, more waypoints are ordered
+/// by a heuristic. Exact ordering takes `2^n * n^2` steps over the leg costs.
+pub const EXACT_WAYPOINT_LIMIT: usize = 10;
+
+type Leg = Option<(Vec<Vector3<f32>>, f32)>;
+
+/// Finds the route from the start through all waypoints, visiting them in
+/// the order which makes the route cheapest. Like `find_path`, the start is
+/// not included. Returns `None` if any waypoint can't be reached.
+///
+/// Every waypoint is searched for from the start and from every other waypoint,
+/// so the provider extra costs may make legs asymmetric. `n` waypoints take
+/// `n^2` searches. Up to `EXACT_WAYPOINT_LIMIT` waypoints the order is optimal.
+/// Above it the order is built by always visiting the nearest waypoint left,
+/// then improved by 2-opt segment reversals, which is usually close to optimal
+/// but not guaranteed to be.
+pub fn find_path_visiting<G: GridProvider>(
+    start: Vector3<f32>,
+    waypoints: &[Vector3<f32>],
+    grid_provider: G,
+) -> Option<Vec<Vector3<f32>>> {
+    // Point zero is the start, point `i + 1` is the waypoint `i`.
+    let points: Vec<Vector3<f32>> = std::iter::once(start)
+        .chain(waypoints.iter().copied())
+        .collect();
+
+    // The route never returns to the start nor stays at a point,
+    // so those legs aren't searched.
+    let legs: Vec<Vec<Leg>> = points
+        .iter()
+        .enumerate()
+        .map(|(from_index, from)| {
+            points
+                .iter()
+                .enumerate()
+                .map(|(to_index, to)| {
+                    if to_index == 0 || to_index == from_index {
+                        None
+                    } else {
+                        find_path_with_cost(*from, *to, &grid_provider)
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    let costs: Vec<Vec<f32>> = legs
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|leg| leg.as_ref().map_or(f32::INFINITY, |(_, cost)| *cost))
+                .collect()
+        })
+        .collect();
+
+    let order = if waypoints.len() <= EXACT_WAYPOINT_LIMIT {
+        exact_order(&costs)?
+    } else {
+        heuristic_order(&costs)
+    };
+
+    if !route_cost(&order, &costs).is_finite() {
+        return None;
+    }
+
+    let mut path: Vec<Vector3<f32>> = Vec::new();
+    let mut previous = 0;
+
+    for point in order {
+        let (leg_path, _) = legs[previous][point].as_ref()?;
+
+        for vector in leg_path {
+            // Skips junction duplicates, like `find_path_sequence`.
+            if path.last() != Some(vector) {
+                path.push(*vector);
+            }
+        }
+
+        previous = point;
+    }
+
+    Some(path)
+}
+
+/// Cost of visiting the points in the order, starting at point zero.
+fn route_cost(order: &[usize], costs: &[Vec<f32>]) -> f32 {
+    let mut previous = 0;
+
+    order
+        .iter()
+        .map(|&point| {
+            let cost = costs[previous][point];
+            previous = point;
+            cost
+        })
+        .sum()
+}
+
+/// Optimal order by Held-Karp dynamic programming over subsets of waypoints,
+/// `None` if no order visits all of them.
+fn exact_order(costs: &[Vec<f32>]) -> Option<Vec<usize>> {
+    let count = costs.len() - 1;
+    let subsets = 1 << count;
+
+    // Cheapest cost of visiting the subset and ending at the waypoint,
+    // along with the waypoint visited before it.
+    let mut best = vec![vec![(f32::INFINITY, None); count]; subsets];
+
+    for last in 0..count {
+        best[1 << last][last] = (costs[0][last + 1], None);
+    }
+
+    for subset in 1..subsets {
+        for last in 0..count {
+            let (cost, _) = best[subset][last];
+
+            if subset & (1 << last) == 0 || !cost.is_finite() {
+                continue;
+            }
+
+            for next in (0..count).filter(|next| subset & (1 << next) == 0) {
+                let next_subset = subset | (1 << next);
+                let next_cost = cost + costs[last + 1][next + 1];
+
+                if next_cost < best[next_subset][next].0 {
+                    best[next_subset][next] = (next_cost, Some(last));
+                }
+            }
+        }
+    }
+
+    let full = subsets - 1;
+    let mut last = (0..count)
+        .filter(|last| best[full][*last].0.is_finite())
+        .min_by(|a, b| best[full][*a].0.total_cmp(&best[full][*b].0));
+
+    if count > 0 && last.is_none() {
+        return None;
+    }
+
+    let mut subset = full;
+    let mut order = Vec::with_capacity(count);
+
+    while let Some(waypoint) = last {
+        order.push(waypoint + 1);
+        last = best[subset][waypoint].1;
+        subset &= !(1 << waypoint);
+    }
+
+    order.reverse();
+    Some(order)
+}
+
+/// Nearest neighbor order improved by 2-opt until no reversal helps.
+fn heuristic_order(costs: &[Vec<f32>]) -> Vec<usize> {
+    let mut left: Vec<usize> = (1..costs.len()).collect();
+    let mut order = Vec::with_capacity(left.len());
+    let mut previous = 0;
+
+    while !left.is_empty() {
+        let (index, _) = left
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| costs[previous][**a].total_cmp(&costs[previous][**b]))
+            .unwrap();
+
+        previous = left.swap_remove(index);
+        order.push(previous);
+    }
+
+    let mut cost = route_cost(&order, costs);
+    let mut improved = true;
+
+    while improved {
+        improved = false;
+
+        for i in 0..order.len() {
+            for j in i + 1..order.len() {
+                order[i..=j].reverse();
+
+                let candidate_cost = route_cost(&order, costs);
+
+                if candidate_cost < cost {
+                    cost = candidate_cost;
+                    improved = true;
+                } else {
+                    order[i..=j].reverse();
+                }
+            }
+        }
+    }
+
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::provider::FnProvider;
+    use crate::tour::{find_path_visiting, EXACT_WAYPOINT_LIMIT};
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_visiting_optimal_order() {
+        let grid_provider = FnProvider::new(|vector| vector.z != 0.0);
+
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let waypoints = [
+            Vector3::new(10.0, 0.0, 0.0),
+            Vector3::new(2.0, 0.0, 0.0),
+            Vector3::new(5.0, 0.0, 0.0),
+        ];
+
+        let path = find_path_visiting(start, &waypoints, &grid_provider).unwrap();
+
+        let position = |waypoint: &Vector3<f32>| path.iter().position(|vector| vector == waypoint);
+
+        assert_eq!(path.len(), 10);
+        assert_eq!(path.last(), Some(&waypoints[0]));
+        assert!(position(&waypoints[1]) < position(&waypoints[2]));
+    }
+
+    #[test]
+    fn test_visiting_heuristic_order() {
+        let grid_provider = FnProvider::new(|vector| vector.z != 0.0);
+
+        let start = Vector3::new(0.0, 0.0, 0.0);
+        let waypoints: Vec<Vector3<f32>> = (1..=EXACT_WAYPOINT_LIMIT + 2)
+            .rev()
+            .map(|x| Vector3::new(x as f32 * 2.0, 0.0, 0.0))
+            .collect();
+
+        let path = find_path_visiting(start, &waypoints, &grid_provider).unwrap();
+
+        assert_eq!(path.len(), 2 * (EXACT_WAYPOINT_LIMIT + 2));
+        assert!(waypoints.iter().all(|waypoint| path.contains(waypoint)));
+    }
+
+    #[test]
+    fn test_visiting_unreachable() {
+        // Closed room split by a wall at x = 3.
+        let grid_provider = FnProvider::new(|vector| {
+            vector.z != 0.0 || vector.x == 3.0 || vector.x.abs() > 6.0 || vector.y.abs() > 6.0
+        });
+
+        let waypoints = [Vector3::new(1.0, 0.0, 0.0), Vector3::new(5.0, 0.0, 0.0)];
+
+        let path = find_path_visiting(Vector3::new(0.0, 0.0, 0.0), &waypoints, &grid_provider);
+
+        assert_eq!(path, None);
+    }
+}
@@ -0,0 +1,430 @@
+use nalgebra::Vector3;
+
+/// How world coordinates snap to grid cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Rounding {
+    /// Nearest cell, halves round away from zero.
+    #[default]
+    Round,
+    /// Cell below, for data authored with floor semantics.
+    Floor,
+    /// Cell above.
+    Ceil,
+    /// Nearest cell, halves round to the even cell.
+    Nearest,
+}
+
+impl Rounding {
+    pub fn apply(self, axis: f32) -> f32 {
+        match self {
+            Rounding::Round => axis.round(),
+            Rounding::Floor => axis.floor(),
+            Rounding::Ceil => axis.ceil(),
+            Rounding::Nearest => {
+                let rounded = axis.round();
+
+                // Exact halves rounded away from zero land on an odd cell, step back.
+                if (axis - axis.trunc()).abs() == 0.5 && rounded % 2.0 != 0.0 {
+                    rounded - axis.signum()
+                } else {
+                    rounded
+                }
+            }
+        }
+    }
+}
+
+/// Converts world coordinates into the cell units the search moves in,
+/// for a lattice of `cell_size` cubes with the cell zero at the origin.
+/// The result isn't rounded, the search snaps it to the nearest cell.
+pub fn world_to_grid(vector: Vector3<f32>, origin: Vector3<f32>, cell_size: f32) -> Vector3<f32> {
+    (vector - origin) / cell_size
+}
+
+/// Converts cell units back into world coordinates, the inverse of `world_to_grid`.
+pub fn grid_to_world(vector: Vector3<f32>, origin: Vector3<f32>, cell_size: f32) -> Vector3<f32> {
+    origin + vector * cell_size
+}
+
+/// Converts the path found in cell units into world coordinates.
+pub fn transform_path(
+    path: &[Vector3<f32>],
+    origin: Vector3<f32>,
+    cell_size: f32,
+) -> Vec<Vector3<f32>> {
+    path.iter()
+        .map(|vector| grid_to_world(*vector, origin, cell_size))
+        .collect()
+}
+
+/// Horizontal axes along which the world wraps around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Topology {
+    /// The world doesn't wrap.
+    #[default]
+    Bounded,
+    WrapX,
+    WrapY,
+    /// Both horizontal axes wrap, the world is a torus.
+    WrapXY,
+}
+
+impl Topology {
+    pub(crate) fn wraps_x(self) -> bool {
+        matches!(self, Topology::WrapX | Topology::WrapXY)
+    }
+
+    pub(crate) fn wraps_y(self) -> bool {
+        matches!(self, Topology::WrapY | Topology::WrapXY)
+    }
+}
+
+/// Wraparound world spanning `[0, width)` along x and `[0, depth)` along y,
+/// leaving it on one side enters it on the other side.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wrapping {
+    pub topology: Topology,
+    pub width: f32,
+    pub depth: f32,
+}
+
+impl Wrapping {
+    pub fn new(topology: Topology, width: f32, depth: f32) -> Self {
+        Wrapping {
+            topology,
+            width,
+            depth,
+        }
+    }
+
+    /// Moves the vector into the world along the wrapping axes.
+    pub fn wrap(&self, vector: Vector3<f32>) -> Vector3<f32> {
+        let mut wrapped = vector;
+
+        if self.topology.wraps_x() {
+            wrapped.x = vector.x.rem_euclid(self.width);
+        }
+
+        if self.topology.wraps_y() {
+            wrapped.y = vector.y.rem_euclid(self.depth);
+        }
+
+        wrapped
+    }
+
+    /// Shortest offset from one vector to another, possibly across the seam.
+    pub fn offset(&self, from: Vector3<f32>, to: Vector3<f32>) -> Vector3<f32> {
+        let mut offset = to - from;
+
+        if self.topology.wraps_x() {
+            offset.x -= self.width * (offset.x / self.width).round();
+        }
+
+        if self.topology.wraps_y() {
+            offset.y -= self.depth * (offset.y / self.depth).round();
+        }
+
+        offset
+    }
+}
+
+/// Converts world coordinates into grid ids and grid cell coordinates.
+pub struct WorldToCell<ATG, GTA>
+where
+    ATG: Fn(f32) -> u32,
+    GTA: Fn(u32) -> f32,
+{
+    /// Function to convert axis to grid id.
+    axis_to_grid_id_fn: ATG,
+    /// Function to convert grid id to axis.
+    grid_id_to_axis_fn: GTA,
+    /// How cell coordinates are snapped.
+    rounding: Rounding,
+}
+
+impl<ATG, GTA> WorldToCell<ATG, GTA>
+where
+    ATG: Fn(f32) -> u32,
+    GTA: Fn(u32) -> f32,
+{
+    pub fn new(axis_to_grid_id_fn: ATG, grid_id_to_axis_fn: GTA) -> Self {
+        WorldToCell {
+            axis_to_grid_id_fn,
+            grid_id_to_axis_fn,
+            rounding: Rounding::default(),
+        }
+    }
+
+    /// Sets how cell coordinates are snapped, `Rounding::Round` by default.
+    pub fn with_rounding(mut self, rounding: Rounding) -> Self {
+        self.rounding = rounding;
+        self
+    }
+
+    /// Returns grid id of the vector.
+    pub fn grid_id(&self, vector: Vector3<f32>) -> (u32, u32) {
+        let grid_x = (self.axis_to_grid_id_fn)(vector.x);
+        let grid_y = (self.axis_to_grid_id_fn)(vector.y);
+
+        (grid_x, grid_y)
+    }
+
+    /// Returns grid id and cell coordinates inside that grid.
+    /// (grid_x, grid_y, x, y, z)
+    pub fn cell(&self, vector: Vector3<f32>) -> (u32, u32, u32, u32, u32) {
+        let (grid_x, grid_y) = self.grid_id(vector);
+
+        let grid_start_x = (self.grid_id_to_axis_fn)(grid_x);
+        let grid_start_y = (self.grid_id_to_axis_fn)(grid_y);
+
+        let origin = Vector3::new(grid_start_x, grid_start_y, 0.0);
+        let cell = cell_axes(vector, origin, 1.0, self.rounding);
+
+        (grid_x, grid_y, cell.x as u32, cell.y as u32, cell.z as u32)
+    }
+
+    /// Returns grid id and cell coordinates inside that grid,
+    /// or `None` if the transform yields negative or overflowing cell coordinates.
+    pub fn checked_cell(&self, vector: Vector3<f32>) -> Option<(u32, u32, u32, u32, u32)> {
+        let (grid_x, grid_y) = self.grid_id(vector);
+
+        let grid_start_x = (self.grid_id_to_axis_fn)(grid_x);
+        let grid_start_y = (self.grid_id_to_axis_fn)(grid_y);
+
+        let origin = Vector3::new(grid_start_x, grid_start_y, 0.0);
+        let cell = cell_axes(vector, origin, 1.0, self.rounding);
+
+        let x = checked_axis(cell.x)?;
+        let y = checked_axis(cell.y)?;
+        let z = checked_axis(cell.z)?;
+
+        Some((grid_x, grid_y, x, y, z))
+    }
+}
+
+/// Cell of the world vector in a tile grid with the cell zero at the origin,
+/// the convention `WorldToCell`, `FolderGridProvider` and `MapGridProvider` look
+/// cells up by, so tiles rasterized with it line up with the lookups.
+///
+/// The grid x and y axes run opposite to the world ones, `round((origin - vector) / cell_size)`,
+/// while the z axis runs along the world one, `round((vector - origin) / cell_size)`.
+/// Halves round away from zero, i.e. to the cell farther from the origin.
+/// Coordinates below zero saturate to zero.
+///
+/// Unlike `world_to_grid`, this is about cells of stored tiles, not the search lattice.
+pub fn world_to_cell(
+    vector: Vector3<f32>,
+    origin: Vector3<f32>,
+    cell_size: f32,
+) -> (u32, u32, u32) {
+    let cell = cell_axes(vector, origin, cell_size, Rounding::Round);
+
+    (cell.x as u32, cell.y as u32, cell.z as u32)
+}
+
+/// World vector of the cell center, the inverse of `world_to_cell`.
+pub fn cell_to_world(cell: (u32, u32, u32), origin: Vector3<f32>, cell_size: f32) -> Vector3<f32> {
+    Vector3::new(
+        origin.x - cell.0 as f32 * cell_size,
+        origin.y - cell.1 as f32 * cell_size,
+        origin.z + cell.2 as f32 * cell_size,
+    )
+}
+
+/// Rounded cell coordinates, see `world_to_cell`.
+fn cell_axes(
+    vector: Vector3<f32>,
+    origin: Vector3<f32>,
+    cell_size: f32,
+    rounding: Rounding,
+) -> Vector3<f32> {
+    Vector3::new(
+        rounding.apply((origin.x - vector.x) / cell_size),
+        rounding.apply((origin.y - vector.y) / cell_size),
+        rounding.apply((vector.z - origin.z) / cell_size),
+    )
+}
+
+fn checked_axis(axis: f32) -> Option<u32> {
+    if axis >= 0.0 && axis <= u32::MAX as f32 {
+        Some(axis as u32)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pathfinding::{find_path, snap_to_grid};
+    use crate::provider::FnProvider;
+    use crate::transform::{
+        cell_to_world, grid_to_world, transform_path, world_to_cell, world_to_grid, Rounding,
+        Topology, WorldToCell, Wrapping,
+    };
+    use nalgebra::Vector3;
+
+    #[test]
+    fn test_cell() {
+        let axis_to_grid_id_fn = |axis: f32| (32.0 - (axis / 533.33)).floor() as u32;
+        let grid_id_to_axis_fn = |grid_id| (32.0 - grid_id as f32) * 533.3;
+
+        let world_to_cell = WorldToCell::new(axis_to_grid_id_fn, grid_id_to_axis_fn);
+
+        let vector = Vector3::new(-1604.0, 1163.0, 111.4);
+
+        assert_eq!(world_to_cell.grid_id(vector), (35, 29));
+        assert_eq!(world_to_cell.cell(vector), (35, 29, 4, 437, 111));
+        assert_eq!(
+            world_to_cell.checked_cell(vector),
+            Some((35, 29, 4, 437, 111))
+        );
+    }
+
+    #[test]
+    fn test_checked_cell() {
+        let axis_to_grid_id_fn = |axis: f32| (32.0 - (axis / 533.33)).floor() as u32;
+        let grid_id_to_axis_fn = |grid_id| (32.0 - grid_id as f32) * 533.3;
+
+        let world_to_cell = WorldToCell::new(axis_to_grid_id_fn, grid_id_to_axis_fn);
+
+        assert_eq!(
+            world_to_cell.checked_cell(Vector3::new(100000.0, 1163.0, 111.0)),
+            None
+        );
+        assert_eq!(
+            world_to_cell.checked_cell(Vector3::new(-1604.0, 1163.0, -5.0)),
+            None
+        );
+        assert_eq!(
+            world_to_cell.checked_cell(Vector3::new(f32::NAN, 1163.0, 111.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_rounding() {
+        let world_to_cell = || WorldToCell::new(|_| 0, |_| 10.0);
+
+        // 10.0 - 5.5 = 4.5 cells into the grid, on the boundary of two cells.
+        let vector = Vector3::new(5.5, 10.0, 2.5);
+
+        assert_eq!(world_to_cell().cell(vector), (0, 0, 5, 0, 3));
+        assert_eq!(
+            world_to_cell()
+                .with_rounding(Rounding::Floor)
+                .checked_cell(vector),
+            Some((0, 0, 4, 0, 2))
+        );
+        assert_eq!(
+            world_to_cell()
+                .with_rounding(Rounding::Ceil)
+                .checked_cell(vector),
+            Some((0, 0, 5, 0, 3))
+        );
+        assert_eq!(
+            world_to_cell()
+                .with_rounding(Rounding::Nearest)
+                .checked_cell(vector),
+            Some((0, 0, 4, 0, 2))
+        );
+    }
+
+    #[test]
+    fn test_rounding_nearest() {
+        assert_eq!(Rounding::Nearest.apply(0.5), 0.0);
+        assert_eq!(Rounding::Nearest.apply(1.5), 2.0);
+        assert_eq!(Rounding::Nearest.apply(-2.5), -2.0);
+        assert_eq!(Rounding::Nearest.apply(2.4), 2.0);
+        assert_eq!(Rounding::Nearest.apply(2.6), 3.0);
+    }
+
+    #[test]
+    fn test_wrapping() {
+        let wrapping = Wrapping::new(Topology::WrapX, 20.0, 10.0);
+
+        assert_eq!(
+            wrapping.wrap(Vector3::new(-1.0, -1.0, 3.0)),
+            Vector3::new(19.0, -1.0, 3.0)
+        );
+        assert_eq!(
+            wrapping.offset(Vector3::new(1.0, 1.0, 0.0), Vector3::new(18.0, 8.0, 0.0)),
+            Vector3::new(-3.0, 7.0, 0.0)
+        );
+
+        let wrapping = Wrapping::new(Topology::WrapXY, 20.0, 10.0);
+
+        assert_eq!(
+            wrapping.wrap(Vector3::new(21.0, 10.0, 0.0)),
+            Vector3::new(1.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            wrapping.offset(Vector3::new(1.0, 1.0, 0.0), Vector3::new(18.0, 8.0, 0.0)),
+            Vector3::new(-3.0, -3.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn test_grid_to_world_round_trip() {
+        let origin = Vector3::new(-100.0, 250.0, 10.0);
+        let cell_size = 4.0;
+
+        for vector in &[
+            Vector3::new(-100.0, 250.0, 10.0),
+            Vector3::new(-57.3, 301.9, 23.0),
+            Vector3::new(12.0, 198.5, 10.1),
+        ] {
+            let cell = snap_to_grid(world_to_grid(*vector, origin, cell_size));
+            let world = grid_to_world(cell, origin, cell_size);
+
+            assert!((world - vector).amax() <= cell_size / 2.0);
+            assert_eq!(snap_to_grid(world_to_grid(world, origin, cell_size)), cell);
+        }
+    }
+
+    #[test]
+    fn test_transform_path() {
+        let origin = Vector3::new(100.0, 0.0, 50.0);
+        let cell_size = 2.5;
+        let grid_provider = FnProvider::new(|vector| vector.z != 0.0);
+
+        let start = world_to_grid(Vector3::new(100.0, 0.0, 50.0), origin, cell_size);
+        let goal = world_to_grid(Vector3::new(110.0, 0.0, 50.0), origin, cell_size);
+
+        let path = find_path(start, goal, &grid_provider).unwrap();
+        let world_path = transform_path(&path, origin, cell_size);
+
+        assert_eq!(world_path.len(), 4);
+        assert_eq!(world_path.first(), Some(&Vector3::new(102.5, 0.0, 50.0)));
+        assert_eq!(world_path.last(), Some(&Vector3::new(110.0, 0.0, 50.0)));
+    }
+
+    #[test]
+    fn test_world_to_cell() {
+        let origin = Vector3::new(-1599.9, 1599.9, 0.0);
+
+        assert_eq!(
+            world_to_cell(Vector3::new(-1604.0, 1163.0, 111.4), origin, 1.0),
+            (4, 437, 111)
+        );
+        // Halves belong to the cell farther from the origin.
+        assert_eq!(
+            world_to_cell(Vector3::new(-1602.4, 1597.4, 2.5), origin, 1.0),
+            (3, 3, 3)
+        );
+        assert_eq!(
+            world_to_cell(Vector3::new(-1590.0, 1610.0, -4.0), origin, 1.0),
+            (0, 0, 0)
+        );
+
+        let origin = Vector3::new(100.0, 100.0, 10.0);
+        let cell = (3, 7, 2);
+        let world = cell_to_world(cell, origin, 4.0);
+
+        assert_eq!(world, Vector3::new(88.0, 72.0, 18.0));
+        assert_eq!(world_to_cell(world, origin, 4.0), cell);
+        assert_eq!(
+            world_to_cell(world + Vector3::new(1.9, -1.9, 1.9), origin, 4.0),
+            cell
+        );
+    }
+}